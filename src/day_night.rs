@@ -1,5 +1,7 @@
 use hord3::{defaults::default_rendering::vectorinator_binned::triangles::{collux_f32_a_u8, collux_u8_a_f32}, horde::geometry::vec3d::Vec3Df};
 
+pub const NIGHT_SKY_BRIGHTNESS:u8 = 40;
+
 pub struct DayNight {
     daylight_color:(u8, u8, u8),
     sunset_color:(u8,u8,u8),
@@ -60,4 +62,10 @@ impl DayNight {
         }
         (color, vector, is_night)
     }
-}
\ No newline at end of file
+    /// Brightness of full skylight, 255 at noon down to NIGHT_SKY_BRIGHTNESS in the middle of the night
+    pub fn get_sky_brightness(&self) -> u8 {
+        let cycle_progress = self.current_tick as f32 / (self.half_time * 2) as f32;
+        let daylight_factor = ((cycle_progress * std::f32::consts::TAU).cos() + 1.0) * 0.5;
+        (NIGHT_SKY_BRIGHTNESS as f32 + daylight_factor * (255 - NIGHT_SKY_BRIGHTNESS) as f32) as u8
+    }
+}
@@ -0,0 +1,81 @@
+use hord3::horde::{game_engine::world::WorldComputeHandler, geometry::{rotation::{Orientation, Rotation}, vec3d::{Vec3D, Vec3Df}}};
+
+use crate::{game_engine::{CoolGameEngineBase, CoolGameEngineTID, CoolVoxel}, game_entity::{colliders::AABB, director::{Director, DirectorEvent, DirectorKind, DirectorUpdate}, Collider, ColliderEvent, ColliderEventVariant, GameEntityVecRead, MeshEvent, MeshEventVariant, Movement, MovementEvent, MovementEventVariant, NewGameEntity, Stats}, game_map::{get_voxel_center, get_voxel_pos, GameMap, GameMapEvent, WorldVoxelPos}};
+
+// Voxels that lose their support (see game_map::voxel_physics) become entity_2 entities for the time of their fall,
+// using the same movement and collisions as everything else, and get put back in the world where they land.
+// Entities can't be removed, so landed ones get their mesh hidden and are parked far below the world, out of everything's way,
+// then shown again when reused for the next voxels that fall.
+
+pub const FALLING_VOXEL_STATIC_TYPE:usize = 0;
+pub const FALLING_VOXEL_HALF_SIZE:f32 = 0.49;
+const PARKING_SPOT:Vec3Df = Vec3Df::new(0.0, 0.0, -100000.0);
+
+fn falling_collider(pos:Vec3Df) -> AABB {
+    AABB::new(pos - Vec3D::all_ones() * FALLING_VOXEL_HALF_SIZE, pos + Vec3D::all_ones() * FALLING_VOXEL_HALF_SIZE)
+}
+
+/// Turns the voxels the GameMap detached into falling entities, reusing parked ones first
+pub fn spawn_falling_voxels(engine:&CoolGameEngineBase, detached:Vec<(WorldVoxelPos, CoolVoxel)>) {
+    if detached.is_empty() {
+        return
+    }
+    let mut writer = engine.entity_2.get_write();
+    let mut parked = (0..writer.director.len()).filter(|i| {*writer.director[*i].get_kind() == DirectorKind::FallingVoxel(None)}).collect::<Vec<usize>>();
+    for (pos, voxel) in detached {
+        let center = get_voxel_center(pos);
+        match parked.pop() {
+            Some(id) => {
+                writer.movement[id].pos = center;
+                writer.movement[id].speed = Vec3Df::zero();
+                writer.movement[id].touching_ground = false;
+                writer.collider[id].collider = falling_collider(center);
+                writer.director[id] = Director::new(DirectorKind::FallingVoxel(Some(voxel)), String::from("Falling voxel"));
+                writer.mesh_info[id].set_hidden(false);
+            },
+            None => {
+                writer.new_ent(NewGameEntity::new(Movement{against_wall:false, touching_ground:false, pos:center, speed:Vec3D::zero(), orient:Orientation::zero(), rotat:Rotation::from_orientation(Orientation::zero())}, Stats {static_type_id:FALLING_VOXEL_STATIC_TYPE, health:0, damage:0, stamina:0, ground_speed:0.0, jump_height:0.0, keys:Vec::new()}, Collider{team:0, collider:falling_collider(center)}, Director::new(DirectorKind::FallingVoxel(Some(voxel)), String::from("Falling voxel"))));
+            }
+        }
+    }
+}
+
+/// Where a voxel that stopped around `pos` ends up, None if there's no room for it
+fn landing_spot(world:&GameMap<CoolVoxel>, pos:Vec3Df) -> Option<WorldVoxelPos> {
+    let voxel_pos = get_voxel_pos(pos);
+    for spot in [voxel_pos, voxel_pos + Vec3D::new(0, 0, 1)] {
+        match world.get_voxel_at(spot) {
+            Some(_) => if !world.is_voxel_solid(spot) {
+                return Some(spot)
+            },
+            None => return None
+        }
+    }
+    None
+}
+
+/// Moves a falling voxel entity with the speed after_main_tick computed, and puts it back in the world once it stops
+pub fn falling_voxel_after_tick<'a>(id:usize, ents:&GameEntityVecRead<'a, CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>, voxel:&CoolVoxel, mut spd:Vec3Df, touching_ground:bool) {
+    let pos = ents.movement[id].pos;
+    spd.x = 0.0;
+    spd.y = 0.0;
+    let out_of_world = world.world.get_voxel_at(get_voxel_pos(pos + spd)).is_none();
+    if touching_ground || out_of_world {
+        if !out_of_world {
+            match landing_spot(&world.world, pos + spd) {
+                Some(spot) => world.tunnels.send_event(GameMapEvent::UpdateVoxelAt(spot, voxel.clone())),
+                None => ()
+            }
+        }
+        ents.tunnels.director_out.send(DirectorEvent::new(id, None, DirectorUpdate::UpdateKind(DirectorKind::FallingVoxel(None))));
+        ents.tunnels.mesh_info_out.send(MeshEvent::new(id, MeshEventVariant::UpdateHidden(true)));
+        ents.tunnels.movement_out.send(MovementEvent::new(id, None, MovementEventVariant::UpdatePos(PARKING_SPOT)));
+        ents.tunnels.movement_out.send(MovementEvent::new(id, None, MovementEventVariant::UpdateSpeed(Vec3Df::zero())));
+        ents.tunnels.collider_out.send(ColliderEvent::new(id, None, ColliderEventVariant::UpdateCollider(falling_collider(PARKING_SPOT))));
+    }
+    else {
+        ents.tunnels.movement_out.send(MovementEvent::new(id, None, MovementEventVariant::UpdatePos(pos + spd)));
+        ents.tunnels.movement_out.send(MovementEvent::new(id, None, MovementEventVariant::UpdateSpeed(spd)));
+        ents.tunnels.collider_out.send(ColliderEvent::new(id, None, ColliderEventVariant::UpdateCollider(falling_collider(pos + spd))));
+    }
+}
@@ -0,0 +1,139 @@
+use hord3::horde::geometry::vec3d::Vec3D;
+
+use crate::{game_engine::{CoolVoxel, ExtraVoxelData}, game_map::{GameMap, GameMapEvent, Voxel, VoxelType, WorldVoxelPos}, world_gen::{AIR, DEEP_WATER, WATER}};
+
+// Water as a cellular automaton :
+// - generated water (no FluidLevel data, water or deep water type) is a source at FLUID_MAX and never changes on its own
+// - flowing water has a FluidLevel between 1 and FLUID_MAX - 1, water right under any water is at FLUID_MAX - 1,
+//   otherwise it's one less than the best neighbour on its side that sits on something (water spreads once it can't fall anymore)
+// - a flowing cell that nothing feeds anymore dries up
+// Only the awake voxels of the GameMap are looked at, so an untouched ocean costs nothing.
+// The tick only reads the world, everything it wants to change goes through GameMapEvents.
+
+pub const FLUID_MAX:u8 = 8;
+pub const FLUID_TICK_INTERVAL:usize = 4;
+pub const MAX_FLUID_CELLS_PER_TICK:usize = 4096;
+
+const HORIZONTAL:[Vec3D<i32> ; 4] = [
+    Vec3D::new(1, 0, 0),
+    Vec3D::new(-1, 0, 0),
+    Vec3D::new(0, 1, 0),
+    Vec3D::new(0, -1, 0),
+];
+
+const UP:Vec3D<i32> = Vec3D::new(0, 0, 1);
+const DOWN:Vec3D<i32> = Vec3D::new(0, 0, -1);
+
+pub fn is_water_type(voxel_type:u16) -> bool {
+    voxel_type == *WATER || voxel_type == *DEEP_WATER
+}
+
+/// None if the voxel isn't water
+pub fn fluid_level(voxel:&CoolVoxel) -> Option<u8> {
+    if !is_water_type(voxel.voxel_type) {
+        return None
+    }
+    match &voxel.extra_voxel_data {
+        Some(datas) => {
+            for data in datas {
+                match data {
+                    ExtraVoxelData::FluidLevel(level) => return Some(*level),
+                    _ => ()
+                }
+            }
+            Some(FLUID_MAX)
+        },
+        None => Some(FLUID_MAX)
+    }
+}
+
+pub fn flowing_water(template:&CoolVoxel, level:u8) -> CoolVoxel {
+    let mut voxel = template.clone();
+    voxel.voxel_type = *WATER;
+    let mut datas = voxel.extra_voxel_data.take().unwrap_or_else(Vec::new);
+    datas.retain(|data| {match data {ExtraVoxelData::FluidLevel(_) => false, _ => true}});
+    datas.push(ExtraVoxelData::FluidLevel(level));
+    voxel.extra_voxel_data = Some(datas);
+    voxel
+}
+
+fn dried_up(template:&CoolVoxel) -> CoolVoxel {
+    let mut voxel = template.clone();
+    voxel.voxel_type = *AIR;
+    voxel.extra_voxel_data = None;
+    voxel
+}
+
+fn is_empty(world:&GameMap<CoolVoxel>, voxel:&CoolVoxel) -> bool {
+    world.get_voxel_types()[voxel.voxel_id()].is_completely_empty()
+}
+
+/// Water falls before spreading, so a neighbour only feeds sideways if it isn't above air
+fn is_supported(world:&GameMap<CoolVoxel>, pos:WorldVoxelPos) -> bool {
+    match world.get_voxel_at(pos + DOWN) {
+        Some(below) => !is_empty(world, below),
+        None => true
+    }
+}
+
+fn target_level(world:&GameMap<CoolVoxel>, pos:WorldVoxelPos) -> u8 {
+    match world.get_voxel_at(pos + UP) {
+        Some(above) => if fluid_level(above).is_some() {
+            return FLUID_MAX - 1
+        },
+        None => ()
+    }
+    let mut best = 0;
+    for dir in HORIZONTAL {
+        match world.get_voxel_at(pos + dir) {
+            Some(neighbour) => match fluid_level(neighbour) {
+                Some(level) => if level > 1 && is_supported(world, pos + dir) {
+                    best = best.max(level - 1);
+                },
+                None => ()
+            },
+            None => ()
+        }
+    }
+    best
+}
+
+/// What changes at `pos` this tick, if anything
+fn step_cell(world:&GameMap<CoolVoxel>, pos:WorldVoxelPos) -> Option<CoolVoxel> {
+    let voxel = world.get_voxel_at(pos)?;
+    let current = match fluid_level(voxel) {
+        Some(FLUID_MAX) => return None,
+        Some(level) => level,
+        None => if voxel.voxel_type == *AIR {0} else {return None}
+    };
+    let target = target_level(world, pos);
+    if target == current {
+        None
+    }
+    else if target == 0 {
+        Some(dried_up(voxel))
+    }
+    else {
+        Some(flowing_water(voxel, target))
+    }
+}
+
+/// One fluid step over the awake voxels, the looked at voxels are put back to sleep and the changes wake up their neighbours
+pub fn fluid_tick(world:&GameMap<CoolVoxel>) -> Vec<GameMapEvent<CoolVoxel>> {
+    let awake = world.get_awake_voxels(MAX_FLUID_CELLS_PER_TICK);
+    if awake.is_empty() {
+        return Vec::new()
+    }
+    let mut changes = Vec::with_capacity(64);
+    for pos in awake.iter() {
+        match step_cell(world, *pos) {
+            Some(voxel) => changes.push((*pos, voxel)),
+            None => ()
+        }
+    }
+    let mut events = vec![GameMapEvent::SleepVoxels(awake)];
+    if changes.len() > 0 {
+        events.push(GameMapEvent::UpdateVoxelsBatch(changes));
+    }
+    events
+}
@@ -1,5 +1,7 @@
 use std::{collections::HashSet, f32::consts::PI, sync::Arc};
 
+use crate::game_map::voxel_cube_mesh;
+
 use hord3::{defaults::default_rendering::{vectorinator_binned::{meshes::{Mesh, MeshLOD, MeshLODS, MeshLODType, MeshTriangles}, shapes_to_tris::{cylinder_to_render_comp, vec_to_complex}, textures::rgb_to_argb}}, horde::geometry::{rotation::Rotation, shapes_3d::{Cylinder, Sphere, Square}, vec3d::{Vec3D, Vec3Df}}};
 
 pub fn line_3D_from_to(start:Vec3Df, stop:Vec3Df, texture:u32, side:f32, field:u32, light:(u8,u8,u8)) -> MeshLOD {
@@ -211,4 +213,9 @@ pub fn second_spread_out_ent_mesh() -> Mesh {
     
     tris.merge_with(vec_to_complex(&Sphere::new(Vec3D::new(0.0, -6.0, 0.0), 1.0).get_triangles::<2>(false), &vec![7 ; sphere.len()], &vec![[(255,255,255) ; 3] ; sphere.len()], &vec![[(0.0, 0.0), (0.0, 1.0), (1.0,1.0)] ; sphere.len()],& vec![0 ; sphere.len()]));
     Mesh::new(MeshLODS::new(vec![MeshLODType::Mesh(Arc::new(tris))]),"SECOND_SPREAD_OUT_ENT_MESH".to_string(), 10.0)
-}
\ No newline at end of file
+}
+/// Stand-in for a voxel that came loose, a voxel cube with that texture
+pub fn falling_voxel_mesh(texture:u32) -> Mesh {
+    let cube = voxel_cube_mesh(texture, (255,255,255));
+    Mesh::new(MeshLODS::new(vec![MeshLODType::Mesh(Arc::new(cube))]), "FALLING_VOXEL_MESH".to_string(), 2.0)
+}
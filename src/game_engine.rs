@@ -4,7 +4,7 @@ use engine_derive::GameEngine;
 use hord3::{defaults::default_rendering::vectorinator_binned::{rendering_spaces::ViewportData, shaders::NoOpShader, Vectorinator}, horde::{game_engine::{engine::{GameEngine, MovingObjectID}, entity::{Entity, EntityVec, MultiplayerEntity, Renderable}, multiplayer::Identify, world::{WorldComputeHandler, WorldHandler, WorldOutHandler, WorldWriteHandler}}, geometry::vec3d::{Vec3D, Vec3Df}, rendering::camera::Camera, scheduler::IndividualTask, sound::{ARWWaves, WavesHandler}}};
 use to_from_bytes_derive::{FromBytes, ToBytes};
 
-use crate::{cutscene::{game_shader::GameShader, reverse_camera_coords::reverse_from_raster_to_worldpos}, game_entity::{actions::{ActionsEvent, ActionsUpdate}, colliders::AABB, Collider, ColliderEvent, ColliderEventVariant, GameEntity, GameEntityVecRead, GameEntityVecWrite, MovementEvent, MovementEventVariant}, game_map::{get_voxel_pos, GameMap, GameMapEvent, Voxel, VoxelLight, VoxelModel, VoxelType}, proxima_link::HordeProximaAIRequest};
+use crate::{falling_voxels::falling_voxel_after_tick, fluids::{fluid_tick, FLUID_TICK_INTERVAL}, traps::{trap_tick, TRAP_TICK_INTERVAL}, cutscene::{game_shader::GameShader, reverse_camera_coords::reverse_from_raster_to_worldpos}, game_entity::{actions::{ActionsEvent, ActionsUpdate}, colliders::AABB, director::{DirectorEvent, DirectorKind, DirectorUpdate}, Collider, ColliderEvent, ColliderEventVariant, GameEntity, GameEntityVecRead, GameEntityVecWrite, MeshEvent, MeshEventVariant, Movement, MovementEvent, MovementEventVariant}, game_map::{cutout::CutoutMask, get_voxel_pos, voxel_physics::VoxelPhysics, GameMap, GameMapEvent, RenderLayer, Voxel, VoxelLight, VoxelModel, VoxelType, ENTITY_GRID_VEC}, proxima_link::HordeProximaAIRequest, world_gen::AIR};
 
 
 #[derive(Clone, FromBytes, ToBytes, PartialEq, Debug)]
@@ -12,6 +12,7 @@ pub struct CoolVoxel {
     pub voxel_type:u16,
     pub orient:u8,
     pub light:VoxelLight,
+    pub sky_light:u8,
     pub extra_voxel_data:Option<Vec<ExtraVoxelData>>,
 }
 
@@ -24,9 +25,48 @@ pub struct CoolVoxel {
 #[derive(Clone, FromBytes, ToBytes, PartialEq, Debug)]
 pub struct PassageData {
     open_with_adjacent:bool,
+    /// 0 if no key is needed
     key_id:u16,
     possible_corridor:bool,
     is_entry:bool,
+    open:bool,
+    /// voxel types the passage switches between, see passages.rs
+    closed_type:u16,
+    open_type:u16,
+}
+
+impl PassageData {
+    pub fn new(open_with_adjacent:bool, key_id:u16, possible_corridor:bool, is_entry:bool, closed_type:u16, open_type:u16) -> Self {
+        Self { open_with_adjacent, key_id, possible_corridor, is_entry, open:false, closed_type, open_type }
+    }
+    pub fn opens_with_adjacent(&self) -> bool {
+        self.open_with_adjacent
+    }
+    pub fn get_key_id(&self) -> u16 {
+        self.key_id
+    }
+    pub fn is_possible_corridor(&self) -> bool {
+        self.possible_corridor
+    }
+    pub fn is_entry(&self) -> bool {
+        self.is_entry
+    }
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+    /// Type of the voxel for that state
+    pub fn type_when(&self, open:bool) -> u16 {
+        if open {self.open_type} else {self.closed_type}
+    }
+    pub fn with_open(&self, open:bool) -> Self {
+        let mut passage = self.clone();
+        passage.open = open;
+        passage
+    }
+    /// Both voxels belong to the same passage if they're adjacent
+    pub fn same_passage(&self, other:&PassageData) -> bool {
+        self.key_id == other.key_id && self.closed_type == other.closed_type && self.open_type == other.open_type
+    }
 }
 
 /// A trap is 
@@ -39,7 +79,55 @@ pub struct TrapData {
     activation_type:ActivationType,
     action:TrapAction,
     cooldown:TrapCooldown,
-    activate_with_all_adjacent:bool
+    activate_with_all_adjacent:bool,
+    /// health taken from entities (or voxels, for projectiles) by one activation
+    damage:i32,
+}
+
+impl TrapData {
+    pub fn new(activation_type:ActivationType, action:TrapAction, cooldown:TrapCooldown, activate_with_all_adjacent:bool, damage:i32) -> Self {
+        Self { activation_type, action, cooldown, activate_with_all_adjacent, damage }
+    }
+    pub fn get_activation_type(&self) -> &ActivationType {
+        &self.activation_type
+    }
+    pub fn get_action(&self) -> &TrapAction {
+        &self.action
+    }
+    pub fn get_cooldown(&self) -> &TrapCooldown {
+        &self.cooldown
+    }
+    pub fn activates_with_all_adjacent(&self) -> bool {
+        self.activate_with_all_adjacent
+    }
+    pub fn get_damage(&self) -> i32 {
+        self.damage
+    }
+    pub fn is_ready(&self) -> bool {
+        match self.cooldown {
+            TrapCooldown::SingleUse { activated } => !activated,
+            TrapCooldown::Ticks { current, .. } => current == 0
+        }
+    }
+    pub fn fired(&self) -> Self {
+        let mut fired = self.clone();
+        fired.cooldown = match self.cooldown {
+            TrapCooldown::SingleUse { .. } => TrapCooldown::SingleUse { activated: true },
+            TrapCooldown::Ticks { max, .. } => TrapCooldown::Ticks { max, current: max }
+        };
+        fired
+    }
+    /// None if there was nothing to cool down
+    pub fn cooled_by(&self, ticks:usize) -> Option<Self> {
+        match self.cooldown {
+            TrapCooldown::Ticks { max, current } if current > 0 => {
+                let mut cooled = self.clone();
+                cooled.cooldown = TrapCooldown::Ticks { max, current: current.saturating_sub(ticks) };
+                Some(cooled)
+            },
+            _ => None
+        }
+    }
 }
 #[derive(Clone, FromBytes, ToBytes, PartialEq, Debug)]
 pub enum TrapCooldown {
@@ -48,8 +136,11 @@ pub enum TrapCooldown {
 }
 #[derive(Clone, FromBytes, ToBytes, PartialEq, Debug)]
 pub enum TrapAction {
+    /// shot from the voxel center towards its oriented top side, see traps.rs
     Projectile,
+    /// hitbox relative to the voxel center
     StraightDamage {hitbox:AABB},
+    /// pushes what's above the voxel along its oriented top side
     Effect
 }
 
@@ -67,13 +158,42 @@ pub enum ActivationType {
 pub enum ExtraVoxelData {
     IsPassage(PassageData),
     IsLightSource(VoxelLight),
-    IsTrap(TrapData)
-    
+    IsTrap(TrapData),
+    /// Flowing water, see fluids.rs
+    FluidLevel(u8),
+    /// Health left, only on damaged voxels
+    Health(u16),
 }
 
 impl CoolVoxel {
     pub fn new(voxel_type:u16, orient:u8, light:VoxelLight, extra_voxel_data:Option<Vec<ExtraVoxelData>>) -> Self {
-        Self { voxel_type, orient, light, extra_voxel_data }
+        Self { voxel_type, orient, light, sky_light:0, extra_voxel_data }
+    }
+    pub fn get_trap(&self) -> Option<&TrapData> {
+        self.extra_voxel_data.as_ref()?.iter().find_map(|data| {match data {ExtraVoxelData::IsTrap(trap) => Some(trap), _ => None}})
+    }
+    /// Same voxel with its trap replaced
+    pub fn with_trap(&self, trap:TrapData) -> Self {
+        let mut voxel = self.clone();
+        let mut datas = voxel.extra_voxel_data.take().unwrap_or_else(Vec::new);
+        datas.retain(|data| {match data {ExtraVoxelData::IsTrap(_) => false, _ => true}});
+        datas.push(ExtraVoxelData::IsTrap(trap));
+        voxel.extra_voxel_data = Some(datas);
+        voxel
+    }
+    pub fn get_passage(&self) -> Option<&PassageData> {
+        self.extra_voxel_data.as_ref()?.iter().find_map(|data| {match data {ExtraVoxelData::IsPassage(passage) => Some(passage), _ => None}})
+    }
+    /// Same voxel opened or closed, with the type of that state
+    pub fn with_passage_open(&self, open:bool) -> Option<Self> {
+        let passage = self.get_passage()?.with_open(open);
+        let mut voxel = self.clone();
+        voxel.voxel_type = passage.type_when(open);
+        let mut datas = voxel.extra_voxel_data.take().unwrap_or_else(Vec::new);
+        datas.retain(|data| {match data {ExtraVoxelData::IsPassage(_) => false, _ => true}});
+        datas.push(ExtraVoxelData::IsPassage(passage));
+        voxel.extra_voxel_data = Some(datas);
+        Some(voxel)
     }
 }
 
@@ -85,12 +205,51 @@ pub struct CoolVoxelType {
     pub is_light_source:Option<VoxelLight>,
     pub name:String,
     pub texture_path:Option<String>,
-    pub base_extra_voxel_data:Option<ExtraVoxelData>
+    pub base_extra_voxel_data:Option<ExtraVoxelData>,
+    /// one texture per side, indexed like the sides of the model before orientation
+    pub face_textures:Option<Vec<usize>>,
+    /// octants of a custom shape, 0 for a plain cube
+    pub vertices_taken:u8,
+    pub render_layer:RenderLayer,
+    /// see game_map::cutout
+    pub cutout_mask:Option<CutoutMask>,
+    pub physics:VoxelPhysics,
+    pub max_health:Option<u16>,
+    pub repairs:bool,
 }
 
 impl CoolVoxelType {
     pub fn new(empty_sides:u8, texture:usize, light_passthrough:VoxelLight, is_light_source:Option<VoxelLight>, name:String, texture_path:Option<PathBuf>, base_extra_voxel_data:Option<ExtraVoxelData>) -> Self {
-        Self { empty_sides, texture, light_passthrough, is_light_source, name, texture_path:texture_path.map(|path| {path.to_string_lossy().to_string()}), base_extra_voxel_data }
+        Self { empty_sides, texture, light_passthrough, is_light_source, name, texture_path:texture_path.map(|path| {path.to_string_lossy().to_string()}), base_extra_voxel_data, face_textures:None, vertices_taken:0, render_layer:RenderLayer::Opaque, cutout_mask:None, physics:VoxelPhysics::Static, max_health:None, repairs:false }
+    }
+    pub fn with_face_textures(mut self, face_textures:[usize ; 6]) -> Self {
+        self.face_textures = Some(face_textures.to_vec());
+        self
+    }
+    pub fn with_render_layer(mut self, render_layer:RenderLayer) -> Self {
+        self.render_layer = render_layer;
+        self
+    }
+    pub fn with_cutout_mask(mut self, cutout_mask:Option<CutoutMask>) -> Self {
+        self.cutout_mask = cutout_mask;
+        self
+    }
+    /// See game_map::voxel_physics
+    pub fn with_physics(mut self, physics:VoxelPhysics) -> Self {
+        self.physics = physics;
+        self
+    }
+    /// See game_map::durability
+    pub fn with_health(mut self, max_health:u16, repairs:bool) -> Self {
+        self.max_health = Some(max_health);
+        self.repairs = repairs;
+        self
+    }
+    /// Sub-voxel shape, see game_map::voxel_shapes for the octant layout
+    pub fn with_shape(mut self, vertices_taken:u8, empty_sides:u8) -> Self {
+        self.vertices_taken = vertices_taken;
+        self.empty_sides = empty_sides;
+        self
     }
 }
 
@@ -102,14 +261,38 @@ impl VoxelType for CoolVoxelType {
         self.empty_sides
     }
     fn vertices_taken(&self) -> u8 {
-        0
+        self.vertices_taken
     }
     fn kind_of_model(&self) -> crate::game_map::VoxelModel {
-        VoxelModel::WrappedTexture(self.texture)
+        if self.vertices_taken != 0 {
+            return VoxelModel::Custom
+        }
+        match &self.face_textures {
+            Some(textures) if textures.len() == 6 => VoxelModel::SpecifiedTexture([textures[0], textures[1], textures[2], textures[3], textures[4], textures[5]]),
+            _ => VoxelModel::WrappedTexture(self.texture)
+        }
     }
     fn light_passthrough(&self) -> VoxelLight {
         self.light_passthrough.clone()
     }
+    fn render_layer(&self) -> RenderLayer {
+        self.render_layer
+    }
+    fn cutout_mask(&self) -> Option<&CutoutMask> {
+        self.cutout_mask.as_ref()
+    }
+    fn physics(&self) -> VoxelPhysics {
+        self.physics
+    }
+    fn max_health(&self) -> Option<u16> {
+        self.max_health
+    }
+    fn repairs(&self) -> bool {
+        self.repairs
+    }
+    fn light_emission(&self) -> Option<VoxelLight> {
+        self.is_light_source
+    }
 }
 
 impl Voxel for CoolVoxel {
@@ -123,6 +306,42 @@ impl Voxel for CoolVoxel {
     fn light_level(&self) -> crate::game_map::VoxelLight {
         self.light
     }
+    fn set_light_level(&mut self, light:VoxelLight) {
+        self.light = light;
+    }
+    fn sky_light(&self) -> u8 {
+        self.sky_light
+    }
+    fn set_sky_light(&mut self, level:u8) {
+        self.sky_light = level;
+    }
+    fn as_empty(&self) -> Self {
+        Self { voxel_type:*AIR, orient:0, light:VoxelLight::zero_light(), sky_light:self.sky_light, extra_voxel_data:None }
+    }
+    fn has_trap(&self) -> bool {
+        self.get_trap().is_some()
+    }
+    fn has_extra_data(&self) -> bool {
+        self.extra_voxel_data.is_some()
+    }
+    fn light_emission(&self) -> Option<VoxelLight> {
+        self.extra_voxel_data.as_ref()?.iter().find_map(|data| {match data {ExtraVoxelData::IsLightSource(light) => Some(*light), _ => None}})
+    }
+    fn health(&self) -> Option<u16> {
+        match &self.extra_voxel_data {
+            Some(datas) => datas.iter().find_map(|data| {match data {ExtraVoxelData::Health(health) => Some(*health), _ => None}}),
+            None => None
+        }
+    }
+    fn set_health(&mut self, health:Option<u16>) {
+        let mut datas = self.extra_voxel_data.take().unwrap_or_else(Vec::new);
+        datas.retain(|data| {match data {ExtraVoxelData::Health(_) => false, _ => true}});
+        match health {
+            Some(health) => datas.push(ExtraVoxelData::Health(health)),
+            None => ()
+        }
+        self.extra_voxel_data = if datas.is_empty() {None} else {Some(datas)};
+    }
 }
 
 fn get_push_to_next_integer_coords_in_dir(start:Vec3Df, dir:Vec3Df) -> Vec3Df {
@@ -219,16 +438,17 @@ const OTHER_DIRS:[Vec3Df ; 5] = [
 fn compute_tick<'a>(turn:EntityTurn, id:usize, first_ent:&GameEntityVecRead<'a, CoolGameEngineTID>, second_ent:&GameEntityVecRead<'a, CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>, extra_data:&ExtraData) {
     match turn {
         EntityTurn::entity_1 => {
+            if *first_ent.director[id].get_kind() == DirectorKind::Dead {
+                return
+            }
+            if first_ent.stats[id].health <= 0 {
+                kill_entity(id, first_ent, world);
+                return
+            }
             let movement = &first_ent.movement[id];
             let mut total_push = Vec3D::zero();
-            for i in world.world.set_grid.get_iter_from_to(movement.pos, movement.pos + movement.speed, 2, 1.0) {
-                if i != id {
-                    let other_move = &first_ent.movement[i];
-                    let distance = other_move.pos.dist(&movement.pos);
-                    if distance < 0.5 {
-                        total_push += (movement.pos - other_move.pos) * ((0.5 - distance) * 2.0)
-                    }
-                }
+            for (i, distance) in world.world.set_grid.in_radius(movement.pos, 0.5, ENTITY_GRID_VEC, |i| {first_ent.movement.get(i).filter(|_| {i != id}).map(|other| {other.pos})}) {
+                total_push += (movement.pos - first_ent.movement[i].pos) * ((0.5 - distance) * 2.0)
             }
             first_ent.tunnels.movement_out.send(MovementEvent::new(id, None, MovementEventVariant::AddToSpeed(total_push)));
 
@@ -245,6 +465,30 @@ fn compute_tick<'a>(turn:EntityTurn, id:usize, first_ent:&GameEntityVecRead<'a,
     }
 }
 
+/// The world's own simulations, run once per tick before the entities' turns (whether there are entities or not),
+/// their events get applied along with the entities' ones
+pub fn world_tick<'a>(first_ent:&GameEntityVecRead<'a, CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>, tick:usize) {
+    if tick % FLUID_TICK_INTERVAL == 0 {
+        for event in fluid_tick(&world.world) {
+            world.tunnels.send_event(event);
+        }
+    }
+    if tick % TRAP_TICK_INTERVAL == 0 {
+        trap_tick(first_ent, world, tick);
+    }
+}
+
+/// Entities can't be removed from their EntityVec, so a dead one stops acting, leaves the set_grid and gets hidden
+fn kill_entity<'a>(id:usize, first_ent:&GameEntityVecRead<'a, CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>) {
+    first_ent.tunnels.director_out.send(DirectorEvent::new(id, None, DirectorUpdate::UpdateKind(DirectorKind::Dead)));
+    first_ent.tunnels.mesh_info_out.send(MeshEvent::new(id, MeshEventVariant::UpdateHidden(true)));
+    first_ent.tunnels.movement_out.send(MovementEvent::new(id, None, MovementEventVariant::UpdateSpeed(Vec3Df::zero())));
+    match world.world.set_grid.get_remove_update(id, ENTITY_GRID_VEC) {
+        Some(update) => world.tunnels.send_event(GameMapEvent::UpdateSetGrid(update)),
+        None => ()
+    }
+}
+
 fn get_nudge_to_nearest_next_whole(number:f32, delta_to_add:f32) -> f32 {
     if number.is_sign_positive() {
         let fract = number.fract();
@@ -273,7 +517,7 @@ fn compute_nudges_from(vertex:Vec3Df, spd:Vec3Df, collider:&Collider, world:&Wor
     let mut nudges = Vec3Df::all_ones();
     let voxel = match world.world.get_voxel_at(get_voxel_pos(vertex)) {
         Some(voxel) => voxel.clone(),
-        None => {CoolVoxel::new(0, 0, VoxelLight::zero_light(),None)}
+        None => {CoolVoxel::new(*AIR, 0, VoxelLight::zero_light(),None)}
     };
     if !world.world.get_voxel_types()[voxel.voxel_type as usize].is_completely_empty() {    
         let z_nudge = get_nudge_to_nearest_next_whole(vertex.z, 0.01);
@@ -329,58 +573,67 @@ fn compute_nudges_from(vertex:Vec3Df, spd:Vec3Df, collider:&Collider, world:&Wor
 }
 
 
+/// Speed after gravity, air resistance and the world's collisions, whether the entity is on the ground and whether it's against a wall
+fn integrate_movement(movement:&Movement, collider:&Collider, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>) -> (Vec3Df, bool, bool) {
+    let mut spd = movement.speed;
+    spd *= AIR_RESISTANCE;
+    spd.z -= GRAVITY;
+    if spd.z.abs() > 0.45 {
+        spd.z = 0.45 * spd.z.signum()
+    }
+    if spd.x.abs() > 0.45 {
+        spd.x = 0.45 * spd.x.signum()
+    }
+    if spd.y.abs() > 0.45 {
+        spd.y = 0.45 * spd.y.signum()
+    }
+    let mut touching_ground = false;
+    let mut against_wall = false;
+    let moved_aabb = (collider.collider + spd);
+    let mut smallest_nudge = Vec3Df::all_ones();
+    for vertex in moved_aabb.get_ground_vertices() {
+        let (nudges, vertical) = compute_nudges_from(vertex, spd, collider, world);
+        touching_ground = touching_ground | vertical;
+        if touching_ground {
+            if nudges.z.abs() < smallest_nudge.z.abs() {
+                smallest_nudge = nudges;
+            }
+        }
+        else {
+            if nudges.norme_square() < smallest_nudge.norme_square() {
+                smallest_nudge = nudges;
+            }
+        }
+    }
+    if !touching_ground {
+        for vertex in moved_aabb.get_top_vertices() {
+            let (nudges, vertical) = compute_nudges_from(vertex, spd, collider, world);
+            if nudges.norme_square() < smallest_nudge.norme_square() {
+                smallest_nudge = nudges;
+            }
+        }
+    }
+    if smallest_nudge != Vec3Df::all_ones() {
+        if smallest_nudge.x != 0.0 || smallest_nudge.y != 0.0 && smallest_nudge.z > 0.0 {
+            against_wall = true;
+        }
+        spd += smallest_nudge;
+    }
+    (spd, touching_ground, against_wall)
+}
+
 fn after_main_tick<'a>(turn:EntityTurn, id:usize, first_ent:&GameEntityVecRead<'a, CoolGameEngineTID>, second_ent:&GameEntityVecRead<'a, CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>, extra_data:&ExtraData) {
     match turn {
         EntityTurn::entity_1 => {
+            if *first_ent.director[id].get_kind() == DirectorKind::Dead {
+                return
+            }
             let movement = &first_ent.movement[id];
             let collider = &first_ent.collider[id];
             let static_type = &first_ent.static_types[first_ent.stats[id].static_type_id];
             let mut movement_pos = movement.pos;
             //let mut movement_add = Vec3D::zero();
-            let mut spd = movement.speed;
-            spd *= AIR_RESISTANCE;
-            spd.z -= GRAVITY;
-            if spd.z.abs() > 0.45 {
-                spd.z = 0.45 * spd.z.signum()
-            }
-            if spd.x.abs() > 0.45 {
-                spd.x = 0.45 * spd.x.signum()
-            }
-            if spd.y.abs() > 0.45 {
-                spd.y = 0.45 * spd.y.signum()
-            }
-            let mut touching_ground = false;
-            let mut against_wall = false;
-            let moved_aabb = (collider.collider + spd);
-            let mut smallest_nudge = Vec3Df::all_ones();
-            for vertex in moved_aabb.get_ground_vertices() {
-                let (nudges, vertical) = compute_nudges_from(vertex, spd, collider, world);
-                touching_ground = touching_ground | vertical;
-                if touching_ground {
-                    if nudges.z.abs() < smallest_nudge.z.abs() {
-                        smallest_nudge = nudges;
-                    }
-                }
-                else {
-                    if nudges.norme_square() < smallest_nudge.norme_square() {
-                        smallest_nudge = nudges;
-                    }
-                }
-            }
-            if !touching_ground {
-                for vertex in moved_aabb.get_top_vertices() {
-                    let (nudges, vertical) = compute_nudges_from(vertex, spd, collider, world);
-                    if nudges.norme_square() < smallest_nudge.norme_square() {
-                        smallest_nudge = nudges;
-                    }
-                }
-            }
-            if smallest_nudge != Vec3Df::all_ones() {
-                if smallest_nudge.x != 0.0 || smallest_nudge.y != 0.0 && smallest_nudge.z > 0.0 {
-                    against_wall = true;
-                }
-                spd += smallest_nudge;
-            }
+            let (spd, touching_ground, against_wall) = integrate_movement(movement, collider, world);
            
             
             /*match world.world.get_type_of_voxel_at(get_voxel_pos((movement_pos + spd + DOWN_DIR /*+ Vec3D::new(0.0, 0.0, -GRAVITY)*/))) {
@@ -394,7 +647,7 @@ fn after_main_tick<'a>(turn:EntityTurn, id:usize, first_ent:&GameEntityVecRead<'
                     spd.z -= GRAVITY;
                 }
             }*/
-            match world.world.set_grid.get_point_move_update(&movement.pos, &(movement_pos + spd), id, 2) {
+            match world.world.set_grid.get_point_move_update(&(movement_pos + spd), id, ENTITY_GRID_VEC) {
                 Some(update) => {
                     //dbg!(update.clone());
                     world.tunnels.send_event(GameMapEvent::UpdateSetGrid(update))
@@ -417,7 +670,15 @@ fn after_main_tick<'a>(turn:EntityTurn, id:usize, first_ent:&GameEntityVecRead<'
             
             first_ent.director[id].do_after_tick(id, first_ent, second_ent, world, &extra_data, extra_data.tick.load(Ordering::Relaxed));
         },
-        _ => ()
+        EntityTurn::entity_2 => {
+            match second_ent.director[id].get_kind() {
+                DirectorKind::FallingVoxel(Some(voxel)) => {
+                    let (spd, touching_ground, _) = integrate_movement(&second_ent.movement[id], &second_ent.collider[id], world);
+                    falling_voxel_after_tick(id, second_ent, world, voxel, spd, touching_ground);
+                },
+                _ => ()
+            }
+        }
     }
 }
 
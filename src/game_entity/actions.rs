@@ -1,7 +1,7 @@
 use hord3::horde::{game_engine::{entity::{Component, ComponentEvent, StaticComponent}, multiplayer::Identify, world::{WorldComputeHandler, WorldEvent}}, geometry::vec3d::Vec3Df};
 use to_from_bytes_derive::{FromBytes, ToBytes};
 
-use crate::{game_engine::{CoolGameEngineTID, CoolVoxel}, game_entity::{director::{Director, DirectorEvent, DirectorUpdate}, planner::{Plan, PlannerEvent, PlannerUpdate}, GameEntityVecRead, MovementEvent, MovementEventVariant}, game_map::{get_voxel_pos, GameMap, GameMapEvent, VoxelLight, VoxelType, WorldVoxelPos}};
+use crate::{game_engine::{CoolGameEngineTID, CoolVoxel}, game_entity::{director::{Director, DirectorEvent, DirectorUpdate}, planner::{Plan, PlannerEvent, PlannerUpdate}, GameEntityVecRead, MovementEvent, MovementEventVariant}, game_map::{get_voxel_pos, GameMap, GameMapEvent, VoxelLight, VoxelType, WorldVoxelPos}, passages::{in_reach, interact_at, open_passage}};
 
 #[derive(Clone, ToBytes, FromBytes, PartialEq, Debug)]
 pub struct Action {
@@ -48,6 +48,7 @@ impl Action {
             ActionKind::PathToPosition(position, tolerance) => world.world.get_voxel_at(get_voxel_pos(position)).and_then(|voxel| {if world.world.get_voxel_types()[voxel.voxel_type as usize].is_completely_empty() {Some(true)} else {None}}).is_some(),
             ActionKind::ChangeVoxel(position, _) => world.world.get_chunk_at(world.world.get_chunk_pos_i(position)).is_some(),
             ActionKind::StopAt(pos, _, _) => true,
+            ActionKind::Interact(position) | ActionKind::OpenPassage(position) => world.world.get_voxel_at(position).is_some() && in_reach(agent_id, position, first_ent),
         }
     }
     pub fn is_done<'a>(
@@ -67,7 +68,9 @@ impl Action {
             ActionKind::StopAt(pos, speed_tolerance, pos_tolerance) => {
                 let movement = &first_ent.movement[agent_id];
                 movement.pos.dist(&pos) < pos_tolerance && movement.speed.norme() < speed_tolerance
-            }
+            },
+            ActionKind::Interact(_) => false,
+            ActionKind::OpenPassage(position) => world.world.get_voxel_at(position).and_then(|voxel| {voxel.get_passage()}).is_some_and(|passage| {passage.is_open()}),
         }
     }
     pub fn perform<'a>(
@@ -195,6 +198,24 @@ impl Action {
                         ActionResult::Done
                     },
                     ActionKind::PathToPosition(position, tolerance) => ActionResult::Error(ActionError::ImpossibleAction),
+                    ActionKind::Interact(position) => {
+                        first_ent.tunnels.actions_out.send(ActionsEvent::new(agent_id, None, ActionsUpdate::RemoveAction(self.id)));
+                        if interact_at(agent_id, *position, first_ent, world) {
+                            ActionResult::Done
+                        }
+                        else {
+                            ActionResult::Error(ActionError::ImpossibleAction)
+                        }
+                    },
+                    ActionKind::OpenPassage(position) => {
+                        first_ent.tunnels.actions_out.send(ActionsEvent::new(agent_id, None, ActionsUpdate::RemoveAction(self.id)));
+                        if open_passage(agent_id, *position, first_ent, world) {
+                            ActionResult::Done
+                        }
+                        else {
+                            ActionResult::Error(ActionError::ImpossibleAction)
+                        }
+                    },
                 }
             }
         }
@@ -245,7 +266,11 @@ pub enum ActionKind {
     PathToPosition(Vec3Df, f32),
     MoveTowards(Vec3Df, f32),
     StopAt(Vec3Df, f32, f32),
-    ChangeVoxel(WorldVoxelPos, CoolVoxel)
+    ChangeVoxel(WorldVoxelPos, CoolVoxel),
+    /// Toggles a passage or fires a PlayerInteract trap, see passages.rs
+    Interact(WorldVoxelPos),
+    /// Only opens, done right away if the passage already is
+    OpenPassage(WorldVoxelPos),
 }
 
 #[derive(Clone, ToBytes, FromBytes, PartialEq, Debug)]
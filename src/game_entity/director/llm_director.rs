@@ -5,7 +5,10 @@ use html_parser::{Dom, Node};
 use proxima_backend::{ai_interaction::endpoint_api::EndpointRequestVariant, database::{chats::SessionType, configuration::{ChatConfiguration, ChatSetting}, context::{ContextData, ContextPart, ContextPosition, WholeContext}}};
 use to_from_bytes_derive::{FromBytes, ToBytes};
 
-use crate::{game_engine::{CoolGameEngineTID, CoolVoxel}, game_entity::{actions::{Action, ActionCounter, ActionKind, ActionSource, ActionTimer, ActionsEvent, ActionsUpdate}, director::{DirectorAlert, DirectorEvent}, GameEntityVecRead}, game_map::{get_voxel_pos, GameMap, Voxel, VoxelLight, WorldVoxelPos}, proxima_link::{HordeProximaAIRequest, HordeProximaAIResponse}};
+use crate::{game_engine::{CoolGameEngineTID, CoolVoxel}, game_entity::{actions::{Action, ActionCounter, ActionKind, ActionSource, ActionTimer, ActionsEvent, ActionsUpdate}, director::{DirectorAlert, DirectorEvent}, GameEntityVecRead}, game_map::{get_voxel_pos, raycaster::has_line_of_sight, GameMap, Voxel, VoxelLight, WorldVoxelPos, ENTITY_GRID_VEC}, proxima_link::{HordeProximaAIRequest, HordeProximaAIResponse}, world_gen::{AIR, METAL}};
+
+/// How far SAY local carries
+const SAY_LOCAL_RANGE:f32 = 10.0;
 
 static PATHING_POSITIONS:LazyLock<Vec<Vec3D<i32>>> = LazyLock::new(|| {
     vec![
@@ -83,8 +86,10 @@ pub fn get_world_slice_string<'a>(
         }
     }
     let mut render_agents:HashMap<(i32,i32), usize> = HashMap::with_capacity(16);
+    let eyes = first_ent.movement[agent_id].pos;
     for (i, ent) in first_ent.movement.iter().enumerate() {
-        if i != agent_id {
+        // agents behind walls aren't shown
+        if i != agent_id && has_line_of_sight(&world.world, eyes, ent.pos) {
             let pos = get_voxel_pos(ent.pos);
             if pos.z == z && pos.x >= from.x && pos.x < to.x && pos.y >= from.y && pos.y < to.y {
                 render_agents.insert((pos.x, pos.y), i);
@@ -117,7 +122,10 @@ pub fn get_world_slice_string<'a>(
                     None => 
                     {
                         if world.world.is_voxel_solid(Vec3D::new(x, y, z)) {
-                            if let Some(voxel) = world.world.get_voxel_at(Vec3D::new(x, y, z)) && voxel.voxel_id() == 9 {
+                            if let Some(voxel) = world.world.get_voxel_at(Vec3D::new(x, y, z)) && voxel.get_passage().is_some() {
+                                final_string += &format!("  D  ");
+                            }
+                            else if let Some(voxel) = world.world.get_voxel_at(Vec3D::new(x, y, z)) && voxel.voxel_id() == *METAL as usize {
                                 final_string += &format!("  µ  ");
                             }
                             else {
@@ -344,23 +352,9 @@ impl LLMDirector {
                     "SAY" => match words[1] {
                         "closest" => if others.len() > 1 {
                             let text = String::from(others[1]);
-                            let mut closest:Option<(usize, f32)> = None;
                             let agent_pos = first_ent.movement[agent_id].pos;
-                            for i in 0..first_ent.director.len() {
-                                if i != agent_id {
-                                    match closest {
-                                        Some((id, dist)) => {
-                                            let new_dist = agent_pos.dist(&first_ent.movement[i].pos);
-                                            if new_dist < dist {
-                                                closest = Some((i, new_dist));
-                                            }
-                                        },
-                                        None => {closest = Some((i, agent_pos.dist(&first_ent.movement[i].pos)));}
-                                    }
-                                }
-                            }
-                            match closest {
-                                Some((id, dist)) => {first_ent.tunnels.director_out.send(DirectorEvent::new(id, Some(CoolGameEngineTID::entity_1(agent_id)), super::DirectorUpdate::SendAlert(DirectorAlert::HeardWords(agent_id, text))));},
+                            match world.world.set_grid.k_nearest(agent_pos, 1, f32::INFINITY, ENTITY_GRID_VEC, |i| {first_ent.movement.get(i).filter(|_| {i != agent_id}).map(|other| {other.pos})}).first() {
+                                Some((id, _)) => {first_ent.tunnels.director_out.send(DirectorEvent::new(*id, Some(CoolGameEngineTID::entity_1(agent_id)), super::DirectorUpdate::SendAlert(DirectorAlert::HeardWords(agent_id, text))));},
                                 None => ()
                             }
                         },
@@ -368,14 +362,9 @@ impl LLMDirector {
                             let text = String::from(others[1]);
                             let agent_pos = first_ent.movement[agent_id].pos;
                             let mut total_count = 0;
-                            for i in 0..first_ent.director.len() {
-                                if i != agent_id {
-                                    let dist = agent_pos.dist(&first_ent.movement[i].pos);
-                                    if dist < 10.0 {
-                                        first_ent.tunnels.director_out.send(DirectorEvent::new(i, Some(CoolGameEngineTID::entity_1(agent_id)), super::DirectorUpdate::SendAlert(DirectorAlert::HeardWords(agent_id, text.clone()))));
-                                        total_count += 1;
-                                    }
-                                }
+                            for (i, _) in world.world.set_grid.in_radius(agent_pos, SAY_LOCAL_RANGE, ENTITY_GRID_VEC, |i| {first_ent.movement.get(i).filter(|_| {i != agent_id}).map(|other| {other.pos})}) {
+                                first_ent.tunnels.director_out.send(DirectorEvent::new(i, Some(CoolGameEngineTID::entity_1(agent_id)), super::DirectorUpdate::SendAlert(DirectorAlert::HeardWords(agent_id, text.clone()))));
+                                total_count += 1;
                             }
                             if total_count > 0 {
                                 self.feedback.push(format!("Your last SAY local command reached {} other agents", total_count));
@@ -450,15 +439,19 @@ impl LLMDirector {
                         match words[1] {
                             "place" => if let Ok(x) = words[2].parse::<i32>() && let Ok(y) = words[3].parse::<i32>() && let Ok(z) = words[4].parse::<i32>() {
                                 let id = counter.get_next_id();
-                                first_ent.tunnels.actions_out.send(ActionsEvent::new(agent_id, None, ActionsUpdate::AddAction(Action::new(id, tick, ActionTimer::Delay(500), ActionKind::ChangeVoxel(Vec3D::new(x, y, z), CoolVoxel::new(9, 0, VoxelLight::max_light(), None)), ActionSource::Director))));
+                                first_ent.tunnels.actions_out.send(ActionsEvent::new(agent_id, None, ActionsUpdate::AddAction(Action::new(id, tick, ActionTimer::Delay(500), ActionKind::ChangeVoxel(Vec3D::new(x, y, z), CoolVoxel::new(*METAL, 0, VoxelLight::max_light(), None)), ActionSource::Director))));
                             },
                             "destroy" => if let Ok(x) = words[2].parse::<i32>() && let Ok(y) = words[3].parse::<i32>() && let Ok(z) = words[4].parse::<i32>() {
                                 let id = counter.get_next_id();
-                                first_ent.tunnels.actions_out.send(ActionsEvent::new(agent_id, None, ActionsUpdate::AddAction(Action::new(id, tick, ActionTimer::Delay(500), ActionKind::ChangeVoxel(Vec3D::new(x, y, z), CoolVoxel::new(0, 0, VoxelLight::max_light(), None)), ActionSource::Director))));
+                                first_ent.tunnels.actions_out.send(ActionsEvent::new(agent_id, None, ActionsUpdate::AddAction(Action::new(id, tick, ActionTimer::Delay(500), ActionKind::ChangeVoxel(Vec3D::new(x, y, z), CoolVoxel::new(*AIR, 0, VoxelLight::max_light(), None)), ActionSource::Director))));
                             },
                             _ => ()
                         }
                     },
+                    "OPEN" => if words.len() == 4 && let Ok(x) = words[1].parse::<i32>() && let Ok(y) = words[2].parse::<i32>() && let Ok(z) = words[3].parse::<i32>() {
+                        let id = counter.get_next_id();
+                        first_ent.tunnels.actions_out.send(ActionsEvent::new(agent_id, None, ActionsUpdate::AddAction(Action::new(id, tick, ActionTimer::Delay(500), ActionKind::OpenPassage(Vec3D::new(x, y, z)), ActionSource::Director))));
+                    },
                     "FILL" => if words.len() == 8 {
                         match words[1] {
                             "place" => if let Ok(mut x1) = words[2].parse::<i32>() && let Ok(mut y1) = words[3].parse::<i32>() && let Ok(mut z1) = words[4].parse::<i32>() && let Ok(mut dx) = words[5].parse::<i32>() && let Ok(mut dy) = words[6].parse::<i32>() && let Ok(mut dz) = words[7].parse::<i32>() {
@@ -468,7 +461,7 @@ impl LLMDirector {
                                     for y in y1..y2 {
                                         for z in z1..z2 {
                                             let id = counter.get_next_id();
-                                            first_ent.tunnels.actions_out.send(ActionsEvent::new(agent_id, None, ActionsUpdate::AddAction(Action::new(id, tick, delay.clone(), ActionKind::ChangeVoxel(Vec3D::new(x, y, z), CoolVoxel::new(9, 0, VoxelLight::max_light(), None)), ActionSource::Director))));
+                                            first_ent.tunnels.actions_out.send(ActionsEvent::new(agent_id, None, ActionsUpdate::AddAction(Action::new(id, tick, delay.clone(), ActionKind::ChangeVoxel(Vec3D::new(x, y, z), CoolVoxel::new(*METAL, 0, VoxelLight::max_light(), None)), ActionSource::Director))));
                                         }
                                     }
                                 }
@@ -480,7 +473,7 @@ impl LLMDirector {
                                     for y in y1..y2 {
                                         for z in z1..z2 {
                                             let id = counter.get_next_id();
-                                            first_ent.tunnels.actions_out.send(ActionsEvent::new(agent_id, None, ActionsUpdate::AddAction(Action::new(id, tick, delay.clone(), ActionKind::ChangeVoxel(Vec3D::new(x, y, z), CoolVoxel::new(0, 0, VoxelLight::max_light(), None)), ActionSource::Director))));
+                                            first_ent.tunnels.actions_out.send(ActionsEvent::new(agent_id, None, ActionsUpdate::AddAction(Action::new(id, tick, delay.clone(), ActionKind::ChangeVoxel(Vec3D::new(x, y, z), CoolVoxel::new(*AIR, 0, VoxelLight::max_light(), None)), ActionSource::Director))));
                                         }
                                     }
                                 }
@@ -37,6 +37,9 @@ impl Director {
     pub fn get_name(&self) -> &String {
         &self.name
     }
+    pub fn get_kind(&self) -> &DirectorKind {
+        &self.kind
+    }
     pub fn do_tick<'a>(
         &self,
         agent_id:usize,
@@ -78,6 +81,10 @@ impl Director {
                                 _ => ()
                             }
                         },
+                        ActionKind::OpenPassage(pos) => match result {
+                            ActionResult::Done => new_director.feedback.push(format!("the OPEN {} {} {} action succeeded !", pos.x, pos.y, pos.z)),
+                            _ => new_director.feedback.push(format!("the OPEN {} {} {} action failed : there is no passage there, it is too far, or you don't have its key", pos.x, pos.y, pos.z)),
+                        },
                         _ => ()
                     }
                 }
@@ -134,7 +141,11 @@ pub enum DirectorAlert {
 pub enum DirectorKind {
     Player,
     LLM(LLMDirector),
-    Nothing
+    Nothing,
+    /// Carries a voxel while it falls, None once it landed and waits to be reused (see falling_voxels.rs)
+    FallingVoxel(Option<CoolVoxel>),
+    /// Health went down to 0, see game_engine::kill_entity
+    Dead,
 }
 
 #[derive(Clone, PartialEq)]
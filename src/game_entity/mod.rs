@@ -153,7 +153,8 @@ impl<ID:Identify> Component<ID> for Collider {
 #[derive(Clone, PartialEq, ToBytes, FromBytes)]
 pub struct MeshInfo {
     instance_id:Option<usize>,
-
+    /// the instance stays around but isn't drawn
+    hidden:bool,
 }
 
 #[derive(Clone)]
@@ -168,9 +169,16 @@ pub struct MeshEvent {
     variant:MeshEventVariant
 }
 
+impl MeshEvent {
+    pub fn new(id:usize, variant:MeshEventVariant) -> Self {
+        Self { id, variant }
+    }
+}
+
 #[derive(Clone, ToBytes, FromBytes)]
 pub enum MeshEventVariant {
     UpdateInstanceID(Option<usize>),
+    UpdateHidden(bool),
 }
 
 impl<ID:Identify> ComponentEvent<MeshInfo, ID> for MeshEvent {
@@ -184,10 +192,17 @@ impl<ID:Identify> ComponentEvent<MeshInfo, ID> for MeshEvent {
     fn apply_to_component(self, components:&mut Vec<MeshInfo>) {
         match self.variant {
             MeshEventVariant::UpdateInstanceID(new_inst_id) => components[self.id].instance_id = new_inst_id,
+            MeshEventVariant::UpdateHidden(hidden) => components[self.id].hidden = hidden,
         }
     }
 }
 
+impl MeshInfo {
+    pub fn set_hidden(&mut self, hidden:bool) {
+        self.hidden = hidden;
+    }
+}
+
 impl StaticComponent for StaticMeshInfo {
 
 }
@@ -196,7 +211,7 @@ impl<ID:Identify> Component<ID> for MeshInfo {
     type CE = MeshEvent;
     type SC = StaticMeshInfo;
     fn from_static(static_comp:&Self::SC) -> Self {
-        Self { instance_id: None }
+        Self { instance_id: None, hidden: false }
     }
 }
 
@@ -209,6 +224,17 @@ pub struct Stats {
     pub stamina:i32,
     pub ground_speed:f32,
     pub jump_height:f32,
+    /// key ids of the locked passages this entity can open
+    pub keys:Vec<u16>,
+}
+
+/// Health entities are spawned with, they die once it's down to 0
+pub const ENTITY_MAX_HEALTH:i32 = 100;
+
+impl Stats {
+    pub fn has_key(&self, key_id:u16) -> bool {
+        key_id == 0 || self.keys.contains(&key_id)
+    }
 }
 
 #[derive(Clone, ToBytes, FromBytes)]
@@ -218,11 +244,21 @@ pub struct StatEvent<ID:Identify> {
     variant:StatEventVariant
 }
 
+impl<ID:Identify> StatEvent<ID> {
+    pub fn new(id:usize, source:Option<ID>, variant:StatEventVariant) -> Self {
+        Self { id, source, variant }
+    }
+}
+
 #[derive(Clone, ToBytes, FromBytes)]
 pub enum StatEventVariant {
     UpdateHealth(i32),
     UpdateDamage(i32),
-    UpdateStamina(i32)
+    UpdateStamina(i32),
+    /// Relative so several hits in the same tick all count
+    TakeDamage(i32),
+    GiveKey(u16),
+    RemoveKey(u16),
 }
 
 #[derive(Clone)]
@@ -247,6 +283,11 @@ impl<ID:Identify> ComponentEvent<Stats, ID> for StatEvent<ID> {
             StatEventVariant::UpdateDamage(new_dmg) => components[self.id].damage = new_dmg,
             StatEventVariant::UpdateHealth(new_health) => components[self.id].health = new_health,
             StatEventVariant::UpdateStamina(new_stam) => components[self.id].stamina = new_stam,
+            StatEventVariant::TakeDamage(amount) => components[self.id].health -= amount,
+            StatEventVariant::GiveKey(key_id) => if !components[self.id].keys.contains(&key_id) {
+                components[self.id].keys.push(key_id);
+            },
+            StatEventVariant::RemoveKey(key_id) => components[self.id].keys.retain(|key| {*key != key_id}),
         }
     }
 }
@@ -255,7 +296,7 @@ impl<ID:Identify> Component<ID> for Stats {
     type CE = StatEvent<ID>;
     type SC = StaticStats;
     fn from_static(static_comp:&Self::SC) -> Self {
-        Self { static_type_id: 0, health: 0, damage: 0, stamina: 0, jump_height:1.0, ground_speed:0.2 }
+        Self { static_type_id: 0, health: 0, damage: 0, stamina: 0, jump_height:1.0, ground_speed:0.2, keys:Vec::new() }
     }
 }
 
@@ -270,7 +311,7 @@ impl<ID:Identify> NewEntity<GameEntity,ID> for NewGameEntity {
         GameEntity {
             movement:self.movement,
             stats:self.stats,
-            mesh_info:MeshInfo { instance_id: None },
+            mesh_info:MeshInfo { instance_id: None, hidden: false },
             collider:self.collider,
             actions:Actions::new(),
             director:self.director,
@@ -286,12 +327,13 @@ impl<'a, ID:Identify> RenderGameEntity<VectorinatorWrite<'a>, ID> for GameEntity
                 let mut instance = rendering_data.meshes.instances[2].get_instance_mut(id);
                 instance.change_pos(movement.pos);
                 instance.change_orient(movement.orient);
+                instance.change_visibility(!mesh_info.hidden);
             },
             None => {
                 if !rendering_data.meshes.does_mesh_exist(&static_type.mesh_info.mesh_id) {
                     rendering_data.meshes.add_mesh(static_type.mesh_info.mesh_data.clone());
                 }
-                mesh_info.instance_id = Some(rendering_data.meshes.add_instance(MeshInstance::new(movement.pos, movement.orient, static_type.mesh_info.mesh_id.clone(), true, false, false), 2))
+                mesh_info.instance_id = Some(rendering_data.meshes.add_instance(MeshInstance::new(movement.pos, movement.orient, static_type.mesh_info.mesh_id.clone(), !mesh_info.hidden, false, false), 2))
             }
         }
     }
@@ -3,7 +3,7 @@ use std::{collections::{HashMap, HashSet, VecDeque}, sync::{LazyLock, OnceLock}}
 use hord3::horde::{game_engine::{entity::{Component, ComponentEvent, StaticComponent}, multiplayer::Identify, world::WorldComputeHandler}, geometry::vec3d::{Vec3D, Vec3Df}};
 use to_from_bytes_derive::{FromBytes, ToBytes};
 
-use crate::{game_engine::{CoolGameEngineTID, CoolVoxel}, game_entity::{actions::{Action, ActionCounter, ActionResult, ActionSource, ActionTimer}, GameEntityVecRead}, game_map::{get_voxel_pos, GameMap}};
+use crate::{game_engine::{CoolGameEngineTID, CoolVoxel}, game_entity::{actions::{Action, ActionCounter, ActionResult, ActionSource, ActionTimer}, GameEntityVecRead}, game_map::{get_voxel_pos, GameMap}, passages::walk_cost};
 
 const DIRECTIONS:[Vec3D<i32> ; 12] = [
     Vec3D::new(1, 0, 0),
@@ -107,6 +107,10 @@ impl Plan {
                 for node in path {
                     let id = counter.get_next_id();
                     let pos = path_data.nodes[*node].position;
+                    if path_data.nodes[*node].opens_passage {
+                        let open_id = counter.get_next_id();
+                        actions.push(Action::new(open_id, started_at, ActionTimer::Delay(500), super::actions::ActionKind::OpenPassage(pos), ActionSource::Planner));
+                    }
                     actions.push(Action::new(id, started_at, ActionTimer::Delay(500), super::actions::ActionKind::MoveTowards(Vec3Df::new(pos.x as f32, pos.y as f32, pos.z as f32), path_data.tolerance), ActionSource::Planner));
                 }
                 let id = counter.get_next_id();
@@ -170,7 +174,7 @@ impl PathfindingData {
         data.explored_positions.insert(start_pos_vox);
         let heuristic = start_pos.dist(&end_pos) as f64;
         data.nodes_map.insert(start_pos_vox, 0);
-        data.nodes.push(PathNode { parent: None, position: start_pos_vox, movement_cost:0.0, heuristic, total_cost:heuristic });
+        data.nodes.push(PathNode { parent: None, position: start_pos_vox, movement_cost:0.0, heuristic, total_cost:heuristic, opens_passage:false });
         data.open_set.push_back(0);
 
         while data.iterations < max_iterations && data.found_path.is_none() {
@@ -231,12 +235,16 @@ impl PathfindingData {
         //println!("{} {} {}", self.iterations, self.nodes.len(), self.nodes_map.len());
         self.iterations += 1;
         if let Some(best_node_id) = self.open_set.pop_front() {
-            let node_cost = self.nodes[best_node_id].movement_cost + 1.0;
+            let base_cost = self.nodes[best_node_id].movement_cost + 1.0;
             for dir in DIRECTIONS {
                 let new_pos = self.nodes[best_node_id].position + dir;
                 if true || !self.explored_positions.contains(&new_pos) { //explored_positions breaks making more efficient paths over existing ones
                     let heuristic = default_heuristic(Vec3D::<f64>::new(new_pos.x as f64, new_pos.y as f64, new_pos.z as f64), Vec3D::<f64>::new(self.end_pos.x as f64, self.end_pos.y as f64, self.end_pos.z as f64));
-                    if !world.world.is_voxel_solid(new_pos) && world.world.is_voxel_solid(new_pos + Vec3D::new(0, 0, -1)) {
+                    // closed passages the agent can open are walkable, for a bit more
+                    let extra_cost = walk_cost(&world.world, new_pos, &first_ent.stats[agent_id]);
+                    if let Some(extra_cost) = extra_cost && world.world.is_voxel_solid(new_pos + Vec3D::new(0, 0, -1)) {
+                        let node_cost = base_cost + extra_cost;
+                        let opens_passage = extra_cost > 0.0;
                         match self.nodes_map.get(&new_pos) {
                             Some(node_id) => {
                                 let mut add = None;
@@ -248,6 +256,7 @@ impl PathfindingData {
                                         node.movement_cost = node_cost;
                                         node.total_cost = heuristic + node_cost;
                                         node.parent = Some(best_node_id);
+                                        node.opens_passage = opens_passage;
                                     }
                                 }
                                 
@@ -259,7 +268,7 @@ impl PathfindingData {
                             None => {
                                 let new_last = self.nodes.len();
                                 let f_cost = node_cost + heuristic;
-                                self.nodes.push(PathNode { parent: Some(best_node_id), position: new_pos, movement_cost:node_cost, heuristic:heuristic, total_cost:f_cost });
+                                self.nodes.push(PathNode { parent: Some(best_node_id), position: new_pos, movement_cost:node_cost, heuristic:heuristic, total_cost:f_cost, opens_passage });
                                 self.last_node = new_last;
                                 self.nodes_map.insert(new_pos, new_last);
                                 self.add_to_prio_queue(new_last, f_cost);
@@ -296,6 +305,8 @@ pub struct PathNode {
     movement_cost:f64,
     heuristic:f64,
     total_cost:f64,
+    /// closed passage to open before walking in
+    opens_passage:bool,
 }
 #[derive(Clone, ToBytes, FromBytes, PartialEq)]
 pub enum PlanData {
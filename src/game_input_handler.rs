@@ -44,7 +44,9 @@ impl GameInputHandler {
         self.last_camera_used.orient.yaw += (delta.0 as f32 * 0.001 * self.sensitivity * PI);
         self.last_camera_used.orient.roll += (delta.1 as f32 * 0.001 * self.sensitivity * PI);
         self.last_camera_used.orient.roll = self.last_camera_used.orient.roll.clamp(0.0, PI);
-        let speed_coef = if self.current_keyboard.contains(&Button::R) {
+        // Ctrl+key are editor shortcuts, they don't move the camera
+        let moving = !self.current_keyboard.contains(&Button::Ctrl);
+        let speed_coef = if moving && self.current_keyboard.contains(&Button::R) {
             2.1
         }
         else {
@@ -57,10 +59,10 @@ impl GameInputHandler {
             self.last_camera_used.pos.z -= speed_coef;
         }
         
-        if self.current_keyboard.contains(&Button::W) {
+        if moving && self.current_keyboard.contains(&Button::W) {
             self.last_camera_used.pos += Orientation::new(self.last_camera_used.orient.yaw - PI/2.0, PI/2.0, 0.0).into_vec() * speed_coef;
         }
-        if self.current_keyboard.contains(&Button::S) {
+        if moving && self.current_keyboard.contains(&Button::S) {
             self.last_camera_used.pos += Orientation::new(self.last_camera_used.orient.yaw + PI/2.0, PI/2.0, 0.0).into_vec() * speed_coef;
         }
         
@@ -0,0 +1,97 @@
+use hord3::horde::geometry::vec3d::Vec3D;
+
+use super::{ChunkDims, GameMap, Voxel, WorldChunkPos, WorldVoxelPos};
+
+// Meshing a chunk only ever looks one voxel past it, so meshing jobs get the shell of voxels right around the chunk
+// instead of copies of the 26 chunks around. The shell is split by the chunk it comes from : a layer past each face,
+// a row past each edge and a single voxel past each corner. A part is None when the chunk on that side isn't loaded.
+// Positions are local to the meshed chunk, like with get_at_local, so the shell is looked up with positions just outside of it.
+
+#[derive(Clone)]
+pub struct ChunkBorders<V:Voxel> {
+    /// indexed by region_index, voxels ordered x first then y then z (see index_in_region), the chunk's own region stays None
+    regions:[Option<Vec<V>> ; 27],
+}
+
+/// Offsets of the 26 voxels (or chunks) around one
+pub(super) fn offsets_around() -> impl Iterator<Item = Vec3D<i32>> {
+    (-1..=1).flat_map(|z| {(-1..=1).flat_map(move |y| {(-1..=1).map(move |x| {Vec3D::new(x, y, z)})})}).filter(|offset| {*offset != Vec3D::new(0, 0, 0)})
+}
+
+/// -1 before the chunk along an axis, 1 past it and 0 inside
+fn side(coord:i32, length:i32) -> i32 {
+    if coord < 0 {-1} else if coord >= length {1} else {0}
+}
+
+/// Offset of the chunk a local position is in
+fn side_of(pos:WorldVoxelPos, dims:&ChunkDims) -> Vec3D<i32> {
+    Vec3D::new(side(pos.x, dims.chunk_length_i), side(pos.y, dims.chunk_width_i), side(pos.z, dims.chunk_height_i))
+}
+
+fn region_index(offset:Vec3D<i32>) -> usize {
+    ((offset.x + 1) + (offset.y + 1) * 3 + (offset.z + 1) * 9) as usize
+}
+
+/// Length of the region along an axis, the shell being one voxel thick past the chunk
+fn extent(side:i32, length:i32) -> i32 {
+    if side == 0 {length} else {1}
+}
+
+/// Index in its region of a local position right outside of the chunk
+fn index_in_region(pos:WorldVoxelPos, offset:Vec3D<i32>, dims:&ChunkDims) -> usize {
+    let along = |coord:i32, side:i32| {if side == 0 {coord} else {0}};
+    let (length, width) = (extent(offset.x, dims.chunk_length_i), extent(offset.y, dims.chunk_width_i));
+    (along(pos.x, offset.x) + along(pos.y, offset.y) * length + along(pos.z, offset.z) * length * width) as usize
+}
+
+/// Range of local coordinates of a region along an axis
+fn region_range(side:i32, length:i32) -> std::ops::Range<i32> {
+    match side {
+        -1 => -1..0,
+        0 => 0..length,
+        _ => length..length + 1
+    }
+}
+
+impl<V:Voxel> ChunkBorders<V> {
+    /// Borders of a chunk with nothing loaded around
+    pub fn empty() -> Self {
+        Self { regions: std::array::from_fn(|_| {None}) }
+    }
+    /// Voxel at a local position right outside of the chunk (past a face, an edge or a corner), None anywhere else or if that chunk isn't loaded
+    pub fn get_at_local(&self, pos:WorldVoxelPos, dims:&ChunkDims) -> Option<&V> {
+        let offset = side_of(pos, dims);
+        // only the voxels touching the chunk are kept
+        if offset == Vec3D::new(0, 0, 0) || pos.x < -1 || pos.y < -1 || pos.z < -1 || pos.x > dims.chunk_length_i || pos.y > dims.chunk_width_i || pos.z > dims.chunk_height_i {
+            return None
+        }
+        self.regions[region_index(offset)].as_ref().map(|region| {&region[index_in_region(pos, offset, dims)]})
+    }
+}
+
+impl<V:Voxel> GameMap<V> {
+    /// Copies the voxels right around the chunk at `chunk_pos` from the loaded chunks around it
+    pub fn get_chunk_borders(&self, chunk_pos:WorldChunkPos) -> ChunkBorders<V> {
+        let dims = &self.dims;
+        let mut borders = ChunkBorders::empty();
+        for offset in offsets_around() {
+            let neighbour = match self.get_chunk_at(chunk_pos + offset) {
+                Some(neighbour) => neighbour,
+                None => continue
+            };
+            let (xs, ys, zs) = (region_range(offset.x, dims.chunk_length_i), region_range(offset.y, dims.chunk_width_i), region_range(offset.z, dims.chunk_height_i));
+            let mut region = Vec::with_capacity(xs.len() * ys.len() * zs.len());
+            // same order as index_in_region
+            for z in zs {
+                for y in ys.clone() {
+                    for x in xs.clone() {
+                        let in_neighbour = WorldVoxelPos::new(x.rem_euclid(dims.chunk_length_i), y.rem_euclid(dims.chunk_width_i), z.rem_euclid(dims.chunk_height_i));
+                        region.push(neighbour.get_at_local(in_neighbour, dims).unwrap().clone());
+                    }
+                }
+            }
+            borders.regions[region_index(offset)] = Some(region);
+        }
+        borders
+    }
+}
@@ -0,0 +1,289 @@
+use std::{collections::HashMap, mem::size_of};
+
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use super::{GameMap, Voxel};
+
+// Voxels of a chunk are stored one of three ways :
+// - Uniform : the whole chunk is the same voxel (all air, all rock...), stored once
+// - Paletted : every different voxel of the chunk goes in a palette, and the chunk only keeps bit-packed palette indices
+//   (as few bits as the palette needs), voxels with extra data (traps, passages, damage...) are rare and go in a side table instead
+// - Unpacked : a plain Vec, what chunks turn into when a voxel is borrowed mutably (get_voxel_at_mut) or their palette gets too big
+// Writes (voxel edits and light updates alike) go through the palette : a new voxel is added to it, the indices getting wider when needed.
+// Every light level a chunk holds is a palette entry of its own, so a chunk whose palette would need more than MAX_PALETTE_BITS bits
+// is unpacked instead, a plain Vec not being much bigger by then.
+// Chunks get compacted back when they're built and by GameMap::compact_chunks, which the main loop calls every now and then,
+// palette entries nothing uses anymore only go away then. Reading never unpacks anything.
+
+/// Palette indices are packed in u64 words, never across two words
+const WORD_BITS:usize = 64;
+/// Widest palette indices before a chunk gets unpacked
+const MAX_PALETTE_BITS:usize = 8;
+
+#[derive(Clone, ToBytes, FromBytes)]
+pub enum ChunkStorage<V:Voxel> {
+    Uniform { voxel:V, len:u32 },
+    Paletted { palette:Vec<V>, bits:u8, len:u32, indices:Vec<u64>, extra:HashMap<u32, V> },
+    Unpacked(Vec<V>),
+}
+
+fn bits_for(palette_len:usize) -> usize {
+    (usize::BITS - (palette_len.max(2) - 1).leading_zeros()) as usize
+}
+
+fn read_index(indices:&[u64], bits:usize, index:usize) -> usize {
+    let per_word = WORD_BITS / bits;
+    ((indices[index / per_word] >> ((index % per_word) * bits)) & ((1 << bits) - 1)) as usize
+}
+
+fn write_index(indices:&mut [u64], bits:usize, index:usize, palette_index:usize) {
+    let per_word = WORD_BITS / bits;
+    let shift = (index % per_word) * bits;
+    let word = &mut indices[index / per_word];
+    *word = (*word & !(((1 << bits) - 1) << shift)) | ((palette_index as u64) << shift);
+}
+
+/// Same indices packed with `new_bits` bits each
+fn repacked(indices:&[u64], bits:usize, new_bits:usize, len:usize) -> Vec<u64> {
+    let mut repacked = vec![0_u64 ; len.div_ceil(WORD_BITS / new_bits)];
+    for i in 0..len {
+        write_index(&mut repacked, new_bits, i, read_index(indices, bits, i));
+    }
+    repacked
+}
+
+impl<V:Voxel> ChunkStorage<V> {
+    pub fn new(voxels:Vec<V>) -> Self {
+        let mut storage = ChunkStorage::Unpacked(voxels);
+        storage.compact();
+        storage
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            ChunkStorage::Uniform { len, .. } | ChunkStorage::Paletted { len, .. } => *len as usize,
+            ChunkStorage::Unpacked(voxels) => voxels.len()
+        }
+    }
+    pub fn is_unpacked(&self) -> bool {
+        match self {
+            ChunkStorage::Unpacked(_) => true,
+            _ => false
+        }
+    }
+    pub fn get(&self, index:usize) -> &V {
+        match self {
+            ChunkStorage::Uniform { voxel, .. } => voxel,
+            ChunkStorage::Paletted { palette, bits, indices, extra, .. } => match extra.get(&(index as u32)) {
+                Some(voxel) => voxel,
+                None => &palette[read_index(indices, *bits as usize, index)]
+            },
+            ChunkStorage::Unpacked(voxels) => &voxels[index]
+        }
+    }
+    /// Replaces the voxel at `index` through the palette, see the top of the file
+    pub fn set(&mut self, index:usize, new_voxel:V) {
+        match self {
+            ChunkStorage::Uniform { voxel, len } => {
+                if *voxel == new_voxel {
+                    return
+                }
+                let (voxel, len) = (voxel.clone(), *len);
+                *self = ChunkStorage::Paletted { palette: vec![voxel], bits: 1, len, indices: vec![0 ; (len as usize).div_ceil(WORD_BITS)], extra: HashMap::new() };
+            },
+            ChunkStorage::Paletted { palette, .. } => if !new_voxel.has_extra_data() && !palette.contains(&new_voxel) && bits_for(palette.len() + 1) > MAX_PALETTE_BITS {
+                self.unpack();
+            },
+            ChunkStorage::Unpacked(_) => ()
+        }
+        match self {
+            ChunkStorage::Paletted { palette, bits, len, indices, extra } => {
+                if new_voxel.has_extra_data() {
+                    extra.insert(index as u32, new_voxel);
+                    return
+                }
+                extra.remove(&(index as u32));
+                let palette_index = match palette.iter().position(|known| {*known == new_voxel}) {
+                    Some(palette_index) => palette_index,
+                    None => {
+                        palette.push(new_voxel);
+                        let new_bits = bits_for(palette.len());
+                        if new_bits > *bits as usize {
+                            *indices = repacked(indices, *bits as usize, new_bits, *len as usize);
+                            *bits = new_bits as u8;
+                        }
+                        palette.len() - 1
+                    }
+                };
+                write_index(indices, *bits as usize, index, palette_index);
+            },
+            ChunkStorage::Unpacked(voxels) => voxels[index] = new_voxel,
+            ChunkStorage::Uniform { .. } => unreachable!()
+        }
+    }
+    /// Unpacks the chunk if it wasn't already, writes that don't need a reference should use set
+    pub fn get_mut(&mut self, index:usize) -> &mut V {
+        self.unpack();
+        match self {
+            ChunkStorage::Unpacked(voxels) => &mut voxels[index],
+            _ => unreachable!()
+        }
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &V> {
+        (0..self.len()).map(move |i| {self.get(i)})
+    }
+    pub fn unpack(&mut self) {
+        if self.is_unpacked() {
+            return
+        }
+        let voxels = self.iter().cloned().collect();
+        *self = ChunkStorage::Unpacked(voxels);
+    }
+    /// Packs an unpacked chunk again
+    pub fn compact(&mut self) {
+        let voxels = match self {
+            ChunkStorage::Unpacked(voxels) => std::mem::take(voxels),
+            _ => return
+        };
+        let len = voxels.len() as u32;
+        let mut palette:Vec<V> = Vec::with_capacity(8);
+        let mut palette_indices = Vec::with_capacity(voxels.len());
+        let mut extra = HashMap::new();
+        // chunks are mostly runs of the same voxel, so the last match is tried before searching the palette
+        let mut last_index = 0;
+        for (i, voxel) in voxels.into_iter().enumerate() {
+            if voxel.has_extra_data() {
+                extra.insert(i as u32, voxel);
+                palette_indices.push(0);
+            }
+            else if palette.get(last_index).is_some_and(|last| {*last == voxel}) {
+                palette_indices.push(last_index);
+            }
+            else {
+                last_index = match palette.iter().position(|known| {*known == voxel}) {
+                    Some(index) => index,
+                    None => {
+                        palette.push(voxel);
+                        palette.len() - 1
+                    }
+                };
+                palette_indices.push(last_index);
+            }
+        }
+        if palette.len() == 1 && extra.is_empty() {
+            *self = ChunkStorage::Uniform { voxel: palette.pop().unwrap(), len };
+            return
+        }
+        if palette.is_empty() {
+            // only extra data voxels, still needs something for index 0
+            palette.push(extra.values().next().unwrap().as_empty());
+        }
+        let bits = bits_for(palette.len());
+        let mut indices = vec![0_u64 ; palette_indices.len().div_ceil(WORD_BITS / bits)];
+        for (i, palette_index) in palette_indices.into_iter().enumerate() {
+            write_index(&mut indices, bits, i, palette_index);
+        }
+        *self = ChunkStorage::Paletted { palette, bits: bits as u8, len, indices, extra };
+    }
+    /// Whether `f` holds for every different voxel of the chunk, packed chunks only look at their palette (entries included even if unused)
+    pub fn palette_all(&self, f:impl Fn(&V) -> bool) -> bool {
+        match self {
+            ChunkStorage::Uniform { voxel, .. } => f(voxel),
+            ChunkStorage::Paletted { palette, extra, .. } => palette.iter().all(&f) && extra.values().all(&f),
+            ChunkStorage::Unpacked(voxels) => voxels.iter().all(&f)
+        }
+    }
+    /// Approximate bytes used by the voxels, heap included but not what extra data points to
+    pub fn memory_used(&self) -> usize {
+        match self {
+            ChunkStorage::Uniform { .. } => size_of::<Self>(),
+            ChunkStorage::Paletted { palette, indices, extra, .. } => size_of::<Self>() + palette.len() * size_of::<V>() + indices.len() * size_of::<u64>() + extra.len() * (size_of::<V>() + size_of::<u32>()),
+            ChunkStorage::Unpacked(voxels) => size_of::<Self>() + voxels.len() * size_of::<V>()
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StorageReport {
+    pub chunks:usize,
+    pub uniform:usize,
+    pub paletted:usize,
+    pub unpacked:usize,
+    /// what everything would take as plain Vecs
+    pub bytes_unpacked:usize,
+    pub bytes_used:usize,
+}
+
+impl<V:Voxel> GameMap<V> {
+    /// Packs every chunk that got unpacked since the last call, returns how many were
+    pub fn compact_chunks(&mut self) -> usize {
+        let mut compacted = 0;
+        for chunk in self.chunks.values_mut() {
+            if chunk.voxels.is_unpacked() {
+                chunk.voxels.compact();
+                compacted += 1;
+            }
+        }
+        compacted
+    }
+    pub fn storage_report(&self) -> StorageReport {
+        let mut report = StorageReport { chunks: 0, uniform: 0, paletted: 0, unpacked: 0, bytes_unpacked: 0, bytes_used: 0 };
+        for chunk in self.chunks.values() {
+            report.chunks += 1;
+            match &chunk.voxels {
+                ChunkStorage::Uniform { .. } => report.uniform += 1,
+                ChunkStorage::Paletted { .. } => report.paletted += 1,
+                ChunkStorage::Unpacked(_) => report.unpacked += 1,
+            }
+            report.bytes_unpacked += size_of::<Vec<V>>() + chunk.voxels.len() * size_of::<V>();
+            report.bytes_used += chunk.voxels.memory_used();
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hord3::horde::geometry::vec3d::Vec3D;
+
+    use crate::{game_engine::CoolVoxel, game_map::VoxelLight, world_gen::{flat::{FlatGenerator, FlatParams}, WorldGenerator, AIR, ROCK}};
+
+    fn lit(voxel_type:u16, level:u8) -> CoolVoxel {
+        CoolVoxel::new(voxel_type, 0, VoxelLight::new(level, 255, 255, 255), None)
+    }
+
+    #[test]
+    fn set_writes_through_the_palette() {
+        let mut storage = ChunkStorage::new(vec![lit(*AIR, 0) ; 512]);
+        // enough entries to need wider indices a few times
+        for i in 0..40 {
+            storage.set(i * 12, lit(*ROCK, i as u8));
+        }
+        assert!(!storage.is_unpacked());
+        for i in 0..512 {
+            let expected = if i % 12 == 0 && i / 12 < 40 {lit(*ROCK, (i / 12) as u8)} else {lit(*AIR, 0)};
+            assert!(*storage.get(i) == expected);
+        }
+    }
+
+    #[test]
+    fn set_unpacks_past_the_widest_palette() {
+        let mut storage = ChunkStorage::new(vec![lit(*AIR, 0) ; 512]);
+        let distinct = |i:usize| {CoolVoxel::new(*ROCK, 0, VoxelLight::new((i % 256) as u8, (i / 256) as u8, 0, 0), None)};
+        for i in 0..(1 << MAX_PALETTE_BITS) {
+            storage.set(i, distinct(i));
+        }
+        assert!(storage.is_unpacked());
+        assert!(*storage.get(255) == distinct(255));
+        assert!(*storage.get(300) == lit(*AIR, 0));
+    }
+
+    #[test]
+    fn generated_rock_is_uniform() {
+        // well under the flat world's rock floor
+        let voxels = FlatGenerator::new(0, FlatParams::flat()).generate_chunk_voxels(Vec3D::new(0, 0, -16), Vec3D::new(8, 8, 8));
+        let storage = ChunkStorage::new(voxels);
+        assert!(matches!(storage, ChunkStorage::Uniform { .. }));
+        assert!(storage.get(0).voxel_type == *ROCK);
+    }
+}
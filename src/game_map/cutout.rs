@@ -0,0 +1,141 @@
+use hord3::horde::geometry::vec3d::{Vec3D, Vec3Df};
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use super::{chunk_borders::ChunkBorders, sky_shading::{ShadedLOD, ShadedPoint, VertexShade}, ChunkMesher, MapChunk, RenderLayer, Voxel, VoxelType, DIR_MASK, TRIS_INDICES_UVS};
+
+// Cutout voxels (fences) are see-through where their texture is transparent. The rasterizer has no alpha test, so the texture's alpha
+// is tested once when the voxel types are loaded instead : its opaque texels are merged into rectangles (see CutoutMask::from_alpha)
+// and every open face of a cutout voxel becomes one quad per rectangle, the transparent texels aren't covered by any triangle.
+// The mask comes from the type's main texture and applies to all its faces, these faces are never merged by the greedy mesher.
+// A cutout type whose texture has no transparent texel gets no mask and is drawn like an opaque cube.
+
+/// Texels with at least that alpha are drawn
+const ALPHA_THRESHOLD:u8 = 128;
+
+/// Rectangles covering the opaque texels of a texture, in texture coordinates (0 to 1)
+#[derive(Clone, PartialEq, Debug, ToBytes, FromBytes)]
+pub struct CutoutMask {
+    /// (u start, v start, u end, v end)
+    rects:Vec<(f32, f32, f32, f32)>,
+}
+
+impl CutoutMask {
+    /// From the alpha of every texel, row by row, None if nothing is transparent
+    pub fn from_alpha(width:usize, height:usize, alpha:&[u8]) -> Option<Self> {
+        let mut opaque:Vec<bool> = alpha.iter().map(|texel| {*texel >= ALPHA_THRESHOLD}).collect();
+        if opaque.iter().all(|texel| {*texel}) {
+            return None
+        }
+        let mut rects = Vec::with_capacity(16);
+        for y in 0..height {
+            for x in 0..width {
+                if !opaque[x + y * width] {
+                    continue
+                }
+                let mut rect_width = 1;
+                while x + rect_width < width && opaque[x + rect_width + y * width] {
+                    rect_width += 1;
+                }
+                let mut rect_height = 1;
+                'grow : while y + rect_height < height {
+                    for dx in 0..rect_width {
+                        if !opaque[x + dx + (y + rect_height) * width] {
+                            break 'grow;
+                        }
+                    }
+                    rect_height += 1;
+                }
+                for dy in 0..rect_height {
+                    for dx in 0..rect_width {
+                        opaque[x + dx + (y + dy) * width] = false;
+                    }
+                }
+                rects.push((x as f32 / width as f32, y as f32 / height as f32, (x + rect_width) as f32 / width as f32, (y + rect_height) as f32 / height as f32));
+            }
+        }
+        Some(Self { rects })
+    }
+    pub fn rect_count(&self) -> usize {
+        self.rects.len()
+    }
+}
+
+/// The part of a face showing `rect` of its texture, the corners of a face having the uvs of TRIS_INDICES_UVS
+fn add_rect(lod:&mut ShadedLOD, face:&[Vec3Df ; 4], (u_start, v_start, u_end, v_end):(f32, f32, f32, f32), shade:VertexShade, texture:u32) {
+    let (indices, uvs) = (TRIS_INDICES_UVS.1, TRIS_INDICES_UVS.2);
+    // position on the face for texture coordinates, between its corners
+    let at = |u:f32, v:f32| {
+        (0..4).fold(Vec3D::new(0.0, 0.0, 0.0), |sum, k| {
+            let weight = (if uvs[k].0 == 1.0 {u} else {1.0 - u}) * (if uvs[k].1 == 1.0 {v} else {1.0 - v});
+            sum + face[k] * weight
+        })
+    };
+    let rect_uvs = uvs.map(|(u, v)| {(u_start + u * (u_end - u_start), v_start + v * (v_end - v_start))});
+    let start_index = lod.x.len();
+    lod.add_points(&rect_uvs.map(|(u, v)| {at(u, v)}));
+    for tri in 0..2 {
+        let point = |k:usize| {
+            let index = indices[tri * 3 + k];
+            ShadedPoint::new(index + start_index, rect_uvs[index].0, rect_uvs[index].1, shade)
+        };
+        lod.triangles.add_triangle(point(0), point(1), point(2), texture);
+    }
+}
+
+impl<V:Voxel> ChunkMesher<V> {
+    pub(super) fn cutout_mask(&self, voxel:&V) -> Option<&CutoutMask> {
+        let voxel_type = &self.voxel_types[voxel.voxel_id()];
+        if voxel_type.render_layer() == RenderLayer::Cutout {
+            voxel_type.cutout_mask()
+        }
+        else {
+            None
+        }
+    }
+    pub(super) fn add_cutout_voxel(&self, lod:&mut ShadedLOD, chunk:&MapChunk<V>, around:&ChunkBorders<V>, local:Vec3D<i32>) {
+        let voxel = match chunk.get_at_local(local, &self.dims) {
+            Some(voxel) => voxel,
+            None => return
+        };
+        let mask = match self.cutout_mask(voxel) {
+            Some(mask) => mask,
+            None => return
+        };
+        let empty_dirs = chunk.get_empty_directions_local(local, &self.dims, around, &self.voxel_types);
+        let lights = chunk.get_lights_local(local, &self.dims, around);
+        let skies = chunk.get_sky_lights_local(local, &self.dims, around);
+        let local_f = Vec3D::new(local.x as f32, local.y as f32, local.z as f32);
+        for (i, dir_mask) in DIR_MASK.iter().enumerate() {
+            if empty_dirs & dir_mask == *dir_mask {
+                let face = TRIS_INDICES_UVS.0[i] + local_f;
+                let (shade, texture) = (self.get_face_shade(lights[i], skies[i]), self.get_face_texture(voxel, i));
+                for rect in &mask.rects {
+                    add_rect(lod, &face, *rect, shade, texture);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_registry::{get_voxel_types, voxel_id};
+
+    #[test]
+    fn masks_cover_the_opaque_texels() {
+        // opaque left column and bottom right texel
+        let mask = CutoutMask::from_alpha(2, 2, &[255, 0, 255, 200]).unwrap();
+        assert_eq!(mask.rects, vec![(0.0, 0.0, 0.5, 1.0), (0.5, 0.5, 1.0, 1.0)]);
+        let checkerboard = CutoutMask::from_alpha(4, 4, &(0..16).map(|i| {if (i % 4 + i / 4) % 2 == 0 {255} else {0}}).collect::<Vec<u8>>()).unwrap();
+        assert_eq!(checkerboard.rect_count(), 8);
+        assert!(CutoutMask::from_alpha(2, 2, &[255 ; 4]).is_none());
+    }
+
+    #[test]
+    fn fences_get_the_mask_of_their_texture() {
+        let fence = &get_voxel_types()[voxel_id("Fence") as usize];
+        // mur_grillage.png is a 16x16 checkerboard
+        assert_eq!(fence.cutout_mask().map(|mask| {mask.rect_count()}), Some(128));
+    }
+}
@@ -0,0 +1,74 @@
+use super::{GameMap, Voxel, VoxelType, WorldVoxelPos};
+
+// Voxel types with a max_health can be damaged, a voxel's current health is stored on the voxel itself (None meaning full),
+// and it breaks once that reaches 0.
+// Types that repair have their voxels recorded in their chunk the first time they get hurt or get replaced by another type through GameMapEvent::UpdateVoxelAt,
+// repair_walls puts every recorded voxel back the way it was, at full health.
+// Records are saved with their chunk, so a wall broken before saving still comes back after loading.
+
+impl<V:Voxel> GameMap<V> {
+    fn record_for_repair(&mut self, pos:WorldVoxelPos, original:&V) {
+        if !self.voxel_types[original.voxel_id()].repairs() {
+            return
+        }
+        let mut original = original.clone();
+        original.set_health(None);
+        match self.get_chunk_at_mut(self.get_chunk_pos_i(pos)) {
+            Some(chunk) => {chunk.repair_records.entry(pos).or_insert(original);},
+            None => ()
+        }
+    }
+    /// Records the voxel at `pos` if `new` destroys it
+    pub(super) fn record_if_destroyed(&mut self, pos:WorldVoxelPos, new:&V) {
+        match self.get_voxel_at(pos).cloned() {
+            Some(old) => if old.voxel_id() != new.voxel_id() {
+                self.record_for_repair(pos, &old);
+            },
+            None => ()
+        }
+    }
+    /// Health left of the voxel at `pos`, None if it can't be damaged
+    pub fn get_voxel_health(&self, pos:WorldVoxelPos) -> Option<u16> {
+        let voxel = self.get_voxel_at(pos)?;
+        let max_health = self.voxel_types[voxel.voxel_id()].max_health()?;
+        Some(voxel.health().unwrap_or(max_health))
+    }
+    /// Returns true if the voxel broke
+    pub fn damage_voxel(&mut self, pos:WorldVoxelPos, amount:u16) -> bool {
+        let voxel = match self.get_voxel_at(pos) {
+            Some(voxel) => voxel.clone(),
+            None => return false
+        };
+        let health = match self.get_voxel_health(pos) {
+            Some(health) => health,
+            None => return false
+        };
+        self.record_for_repair(pos, &voxel);
+        if amount >= health {
+            self.set_voxel_at(pos, voxel.as_empty());
+            true
+        }
+        else {
+            let mut damaged = voxel;
+            damaged.set_health(Some(health - amount));
+            self.update_voxel_data_at(pos, damaged);
+            false
+        }
+    }
+    /// Puts every recorded repairing voxel back, returns how many were actually changed
+    pub fn repair_walls(&mut self) -> usize {
+        let mut records = Vec::with_capacity(64);
+        for chunk in self.chunks.values_mut() {
+            records.extend(chunk.repair_records.drain());
+        }
+        let mut repaired = 0;
+        for (pos, original) in records {
+            let current_health = self.get_voxel_at(pos).map(|voxel| {(voxel.voxel_id(), voxel.health())});
+            if current_health != Some((original.voxel_id(), None)) {
+                self.set_voxel_at(pos, original);
+                repaired += 1;
+            }
+        }
+        repaired
+    }
+}
@@ -0,0 +1,61 @@
+use hord3::horde::geometry::vec3d::Vec3D;
+
+use super::{GameMap, Voxel, VoxelLight, VoxelType, WorldChunkPos, WorldVoxelPos};
+
+// Emissive voxels : a voxel whose extra data or type gives off light is one of the map's light sources for as long as it's there.
+// set_voxel_at adds or removes the light source of a position whenever the emission there changes, so lamps light up when placed
+// and go dark when broken. Light sources put by hand (the editor's light tool) on voxels that don't emit anything are left alone.
+// Generated chunks are scanned once inserted, their palettes first so chunks without any emissive voxel are skipped right away.
+
+impl<V:Voxel> GameMap<V> {
+    pub fn emission_of(&self, voxel:&V) -> Option<VoxelLight> {
+        voxel.light_emission().or_else(|| {self.voxel_types[voxel.voxel_id()].light_emission()})
+    }
+    /// Follows the emission at `pos` going from `old` to `new`
+    pub(super) fn update_emission_at(&mut self, pos:WorldVoxelPos, old:Option<VoxelLight>, new:Option<VoxelLight>) {
+        if old == new {
+            return
+        }
+        match new {
+            Some(light) => self.add_light_source(pos, light),
+            None => {self.remove_light_source(pos);}
+        }
+    }
+    /// Emissive voxels of a loaded chunk that aren't lit with their light yet
+    fn unlit_emissive_voxels(&self, chunk_pos:WorldChunkPos) -> Vec<(WorldVoxelPos, VoxelLight)> {
+        let chunk = match self.chunks.get(&chunk_pos) {
+            Some(chunk) => chunk,
+            None => return Vec::new()
+        };
+        if chunk.voxels.palette_all(|voxel| {self.emission_of(voxel).is_none()}) {
+            return Vec::new()
+        }
+        let mut found = Vec::with_capacity(8);
+        for z in 0..self.dims.chunk_height_i {
+            for y in 0..self.dims.chunk_width_i {
+                for x in 0..self.dims.chunk_length_i {
+                    let local = Vec3D::new(x, y, z);
+                    match chunk.get_at_local(local, &self.dims).and_then(|voxel| {self.emission_of(voxel)}) {
+                        Some(light) => if self.light_sources.get(&(chunk.origin_worldpos + local)) != Some(&light) {
+                            found.push((chunk.origin_worldpos + local, light));
+                        },
+                        None => ()
+                    }
+                }
+            }
+        }
+        found
+    }
+    /// Makes every emissive voxel of the chunk a light source
+    pub fn light_emissive_voxels(&mut self, chunk_pos:WorldChunkPos) {
+        for (pos, light) in self.unlit_emissive_voxels(chunk_pos) {
+            self.add_light_source(pos, light);
+        }
+    }
+    /// Same for every loaded chunk, for worlds generated all at once
+    pub fn light_all_emissive_voxels(&mut self) {
+        for chunk_pos in self.get_all_chunk_pos() {
+            self.light_emissive_voxels(chunk_pos);
+        }
+    }
+}
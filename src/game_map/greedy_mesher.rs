@@ -0,0 +1,228 @@
+use hord3::horde::geometry::vec3d::{Vec3D, Vec3Df};
+
+use super::{chunk_borders::ChunkBorders, multiply_corresponding_nonzero, sky_shading::{ShadedLOD, ShadedPoint, ShadedTriangles, VertexShade}, smooth_lighting::add_smooth_quad, ChunkMesher, GameMap, MapChunk, Voxel, VoxelType, WorldChunkPos, DIR_MASK, EXPLORATION, PERPENDICULAR, TRIS_INDICES_UVS};
+
+// Greedy meshing : for every face direction, each slice of the chunk perpendicular to it is turned into a 2D grid of faces
+// (None where no face is needed), then faces that share a texture and are evenly lit, all four corners having the same shade
+// (see smooth_lighting), are merged into the biggest rectangles possible. Faces shaded unevenly stay on their own, as a merged quad
+// would only blend between its outer corners.
+// The UVs are scaled with the rectangle so the texture repeats once per voxel instead of being stretched.
+
+#[derive(Clone, Copy, PartialEq)]
+struct FaceKey {
+    texture:u32,
+    colors:[VertexShade ; 4],
+}
+
+impl FaceKey {
+    fn can_merge(&self) -> bool {
+        self.colors.iter().all(|color| {*color == self.colors[0]})
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TriangleComparison {
+    pub naive:usize,
+    pub greedy:usize,
+}
+
+impl TriangleComparison {
+    pub fn saved(&self) -> usize {
+        self.naive.saturating_sub(self.greedy)
+    }
+}
+
+fn unit_abs(dir:Vec3D<i32>) -> Vec3D<i32> {
+    Vec3D::new(dir.x.abs(), dir.y.abs(), dir.z.abs())
+}
+
+fn to_float(dir:Vec3D<i32>) -> Vec3Df {
+    Vec3D::new(dir.x as f32, dir.y as f32, dir.z as f32)
+}
+
+pub(super) fn add_quad(lod:&mut ShadedLOD, points:&[Vec3Df ; 4], shade:VertexShade, texture:u32, (u_scale, v_scale):(f32, f32)) {
+    let start_index = lod.x.len();
+    let indices = TRIS_INDICES_UVS.1;
+    let uvs = TRIS_INDICES_UVS.2;
+    lod.add_points(points);
+    for tri in 0..2 {
+        let point = |k:usize| {
+            let index = indices[tri * 3 + k];
+            ShadedPoint::new(index + start_index, uvs[index].0 * u_scale, uvs[index].1 * v_scale, shade)
+        };
+        lod.triangles.add_triangle(point(0), point(1), point(2), texture);
+    }
+}
+
+impl<V:Voxel> ChunkMesher<V> {
+    pub(super) fn axis_length(&self, dir:Vec3D<i32>) -> i32 {
+        if dir.x != 0 {
+            self.dims.chunk_length_i
+        }
+        else if dir.y != 0 {
+            self.dims.chunk_width_i
+        }
+        else {
+            self.dims.chunk_height_i
+        }
+    }
+    fn get_face_key(&self, chunk:&MapChunk<V>, around:&ChunkBorders<V>, local:Vec3D<i32>, dir:usize) -> Option<FaceKey> {
+        let voxel = chunk.get_at_local(local, &self.dims)?;
+        let voxel_type = &self.voxel_types[voxel.voxel_id()];
+        if voxel_type.is_completely_empty() || self.is_custom(voxel) || self.is_translucent(voxel) || self.cutout_mask(voxel).is_some() {
+            return None
+        }
+        let empty_dirs = chunk.get_empty_directions_local(local, &self.dims, around, &self.voxel_types);
+        if empty_dirs & DIR_MASK[dir] != DIR_MASK[dir] {
+            return None
+        }
+        Some(FaceKey { texture: self.get_face_texture(voxel, dir), colors: self.get_vertex_colors(chunk, around, local, dir) })
+    }
+    pub(super) fn get_lod_greedy(&self, chunk:&MapChunk<V>, around:&ChunkBorders<V>) -> ShadedLOD {
+        let mut lod = ShadedLOD::new(Vec::with_capacity(300), Vec::with_capacity(300), Vec::with_capacity(300), ShadedTriangles::with_capacity(300));
+        for x in 0..self.dims.chunk_length_i {
+            for y in 0..self.dims.chunk_width_i {
+                for z in 0..self.dims.chunk_height_i {
+                    match chunk.get_at_local(Vec3D::new(x, y, z), &self.dims) {
+                        Some(voxel) => if self.is_custom(voxel) {
+                            self.add_custom_voxel(&mut lod, chunk, around, Vec3D::new(x, y, z));
+                        }
+                        else if self.cutout_mask(voxel).is_some() {
+                            self.add_cutout_voxel(&mut lod, chunk, around, Vec3D::new(x, y, z));
+                        },
+                        None => ()
+                    }
+                }
+            }
+        }
+        for dir in 0..6 {
+            let normal = unit_abs(EXPLORATION[dir]);
+            let (first, second) = PERPENDICULAR[dir].clone();
+            let (depth_len, first_len, second_len) = (self.axis_length(normal), self.axis_length(first), self.axis_length(second));
+            let mut faces = vec![None ; (first_len * second_len) as usize];
+            for depth in 0..depth_len {
+                for b in 0..second_len {
+                    for a in 0..first_len {
+                        faces[(a + b * first_len) as usize] = self.get_face_key(chunk, around, normal * depth + first * a + second * b, dir);
+                    }
+                }
+                for b in 0..second_len {
+                    for a in 0..first_len {
+                        let key = match faces[(a + b * first_len) as usize] {
+                            Some(key) => key,
+                            None => continue
+                        };
+                        let mut width = 1;
+                        while key.can_merge() && a + width < first_len && faces[(a + width + b * first_len) as usize] == Some(key) {
+                            width += 1;
+                        }
+                        let mut height = 1;
+                        'grow : while key.can_merge() && b + height < second_len {
+                            for da in 0..width {
+                                if faces[(a + da + (b + height) * first_len) as usize] != Some(key) {
+                                    break 'grow;
+                                }
+                            }
+                            height += 1;
+                        }
+                        for db in 0..height {
+                            for da in 0..width {
+                                faces[(a + da + (b + db) * first_len) as usize] = None;
+                            }
+                        }
+                        let start = normal * depth + first * a + second * b;
+                        let scaler = multiply_corresponding_nonzero(multiply_corresponding_nonzero(Vec3Df::all_ones(), to_float(first) * width as f32), to_float(second) * height as f32);
+                        let mut points = TRIS_INDICES_UVS.0[dir];
+                        points = points * scaler + to_float(start) + ((Vec3Df::all_ones() * 0.5).component_product(&scaler) - Vec3Df::all_ones() * 0.5);
+                        add_smooth_quad(&mut lod, &points, &key.colors, key.texture, (width as f32, height as f32));
+                    }
+                }
+            }
+        }
+        lod
+    }
+}
+
+impl<V:Voxel> GameMap<V> {
+    /// Triangle count of the one-quad-per-face mesh against the greedy one for a loaded chunk
+    pub fn compare_chunk_triangles(&self, chunk_pos:WorldChunkPos) -> Option<TriangleComparison> {
+        let chunk = self.chunks.get(&chunk_pos)?;
+        let around = self.get_chunk_borders(chunk_pos);
+        let mesher = self.get_mesher();
+        Some(TriangleComparison { naive: mesher.get_lod_without_step(chunk, &around).triangles.len(), greedy: mesher.get_lod_greedy(chunk, &around).triangles.len() })
+    }
+    /// Sum of compare_chunk_triangles over every loaded chunk
+    pub fn compare_world_triangles(&self) -> TriangleComparison {
+        let mut total = TriangleComparison { naive: 0, greedy: 0 };
+        for chunk_pos in self.chunks.keys() {
+            match self.compare_chunk_triangles(*chunk_pos) {
+                Some(comparison) => {
+                    total.naive += comparison.naive;
+                    total.greedy += comparison.greedy;
+                },
+                None => ()
+            }
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game_engine::CoolVoxel, game_map::{ChunkDims, VoxelLight}, voxel_registry::get_voxel_types, world_gen::{AIR, ROCK}};
+
+    /// Side of the block of voxels the tests mesh, kept away from the chunk's faces so missing neighbours don't matter
+    const BLOCK:std::ops::Range<i32> = 2..6;
+
+    fn in_block(pos:Vec3D<i32>) -> bool {
+        BLOCK.contains(&pos.x) && BLOCK.contains(&pos.y) && BLOCK.contains(&pos.z)
+    }
+
+    /// Triangle counts of a lone 8x8x8 chunk where `voxel` gives the voxel at every position
+    fn compare<F:FnMut(Vec3D<i32>) -> CoolVoxel>(mut voxel:F) -> TriangleComparison {
+        let mut map = GameMap::new(1, ChunkDims::new(8, 8, 8), get_voxel_types(), (0, 0, 0), 1);
+        map.generate_chunk(Vec3D::new(0, 0, 0), &mut voxel);
+        map.compare_chunk_triangles(Vec3D::new(0, 0, 0)).unwrap()
+    }
+
+    fn dark(voxel_type:u16) -> CoolVoxel {
+        CoolVoxel::new(voxel_type, 0, VoxelLight::zero_light(), None)
+    }
+
+    #[test]
+    fn full_cube() {
+        let comparison = compare(|pos| {dark(if in_block(pos) {*ROCK} else {*AIR})});
+        // 16 faces on each side, merged into one quad per side
+        assert_eq!(comparison.naive, 6 * 16 * 2);
+        assert_eq!(comparison.greedy, 6 * 2);
+    }
+
+    #[test]
+    fn checkerboard() {
+        let comparison = compare(|pos| {dark(if in_block(pos) && (pos.x + pos.y + pos.z) % 2 == 0 {*ROCK} else {*AIR})});
+        // every one of the 32 voxels shows its 6 faces and no two faces touch
+        assert_eq!(comparison.naive, 32 * 6 * 2);
+        assert_eq!(comparison.greedy, comparison.naive);
+    }
+
+    #[test]
+    fn mixed_light() {
+        // one voxel thick slab, the air on its -x half is lit
+        let comparison = compare(|pos| {
+            if in_block(pos) && pos.z == 3 {
+                dark(*ROCK)
+            }
+            else if pos.x < 4 {
+                CoolVoxel::new(*AIR, 0, VoxelLight::max_light(), None)
+            }
+            else {
+                dark(*AIR)
+            }
+        });
+        // 16 faces on top and bottom, 4 on each side
+        assert_eq!(comparison.naive, (2 * 16 + 4 * 4) * 2);
+        // top and bottom : a lit strip, a dark one and the 8 faces between them that are half lit, the y sides can't merge, the x sides are evenly lit
+        assert_eq!(comparison.greedy, (2 * 10 + 2 * 4 + 2) * 2);
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::{HashSet, VecDeque};
+
+use hord3::{defaults::default_rendering::vectorinator_binned::triangles::{collux_f32_a_u8, collux_u8_a_f32}, horde::geometry::vec3d::Vec3D};
+
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use super::{light_spreader::LightPos, GameMap, Voxel, VoxelLight, VoxelType, WorldChunkPos, WorldVoxelPos, EXPLORATION};
+
+// Voxel light is the strongest contribution that reaches a voxel, with the same falloff as LightSpread :
+// every voxel crossed removes (255 - passthrough level) and tints the light with the passthrough color.
+// - adding light is a plain BFS that only overwrites dimmer voxels
+// - removing light is a reverse BFS that darkens every neighbour dimmer than the removed light,
+//   neighbours that are as bright or brighter are lit by something else and get queued for re-propagation
+
+pub fn attenuate(light:VoxelLight, passthrough:VoxelLight) -> VoxelLight {
+    let new_level = light.level - (255 - passthrough.level).min(light.level);
+    if new_level == 0 {
+        return VoxelLight::zero_light()
+    }
+    let passthrough_f = collux_u8_a_f32((passthrough.r, passthrough.g, passthrough.b));
+    let current = collux_u8_a_f32((light.r, light.g, light.b));
+    let (r, g, b) = collux_f32_a_u8((current.0 * passthrough_f.0, current.1 * passthrough_f.1, current.2 * passthrough_f.2));
+    VoxelLight::new(new_level, r, g, b)
+}
+
+#[derive(Clone, Default, ToBytes, FromBytes)]
+pub struct LightEngine {
+    add_queue:VecDeque<LightPos>,
+    removal_queue:VecDeque<LightPos>,
+    touched:HashSet<WorldVoxelPos>,
+}
+
+impl LightEngine {
+    pub fn new() -> Self {
+        Self { add_queue: VecDeque::with_capacity(256), removal_queue: VecDeque::with_capacity(256), touched: HashSet::with_capacity(256) }
+    }
+    pub fn queue_add(&mut self, light:LightPos) {
+        self.add_queue.push_back(light);
+    }
+    pub fn queue_removal(&mut self, light:LightPos) {
+        self.removal_queue.push_back(light);
+    }
+    pub fn get_touched(&self) -> &HashSet<WorldVoxelPos> {
+        &self.touched
+    }
+    pub fn take_touched(&mut self) -> HashSet<WorldVoxelPos> {
+        std::mem::take(&mut self.touched)
+    }
+    fn set_light<V:Voxel>(&mut self, map:&mut GameMap<V>, pos:WorldVoxelPos, light:VoxelLight) {
+        if map.modify_voxel_at(pos, |voxel| {voxel.set_light_level(light)}) {
+            self.touched.insert(pos);
+        }
+    }
+    /// Lights `pos` with `light` if it is brighter than what is already there, then spreads it
+    pub fn light_up<V:Voxel>(&mut self, map:&mut GameMap<V>, pos:WorldVoxelPos, light:VoxelLight) {
+        if map.get_voxel_at(pos).is_some_and(|voxel| {voxel.light_level().level < light.level}) {
+            self.set_light(map, pos, light);
+        }
+        self.queue_add(LightPos::new(pos, light));
+        self.propagate(map);
+    }
+    /// Darkens `pos` and everything it used to light, then relights from whatever is left
+    pub fn darken<V:Voxel>(&mut self, map:&mut GameMap<V>, pos:WorldVoxelPos) {
+        match map.get_voxel_at(pos) {
+            Some(voxel) => {
+                let old_light = voxel.light_level();
+                self.set_light(map, pos, VoxelLight::zero_light());
+                self.queue_removal(LightPos::new(pos, old_light));
+            },
+            None => ()
+        }
+        self.unlight(map);
+        self.propagate(map);
+    }
+    /// Whether the light of `voxel` at `pos` can go on to its neighbours : light only goes through voxels that let some through (like in propagate),
+    /// the light opaque voxels carry stays there except for light sources
+    fn spreads_light<V:Voxel>(map:&GameMap<V>, pos:WorldVoxelPos, voxel:&V) -> bool {
+        voxel.light_level().level > 0 && (map.get_voxel_types()[voxel.voxel_id()].light_passthrough().level > 0 || map.light_sources.contains_key(&pos))
+    }
+    /// Pulls light in from the lit neighbours of `pos`, used when a voxel becomes more transparent
+    pub fn relight_from_neighbours<V:Voxel>(&mut self, map:&mut GameMap<V>, pos:WorldVoxelPos) {
+        for dir in EXPLORATION {
+            match map.get_voxel_at(pos + dir) {
+                Some(voxel) => if Self::spreads_light(map, pos + dir, voxel) {
+                    self.queue_add(LightPos::new(pos + dir, voxel.light_level()));
+                },
+                None => ()
+            }
+        }
+        self.propagate(map);
+    }
+    /// Spreads the light of the loaded chunks around into the chunk at `chunk_pos`, used once it gets loaded
+    pub fn light_from_around<V:Voxel>(&mut self, map:&mut GameMap<V>, chunk_pos:WorldChunkPos) {
+        let (origin, size) = match map.get_chunk_at(chunk_pos) {
+            Some(chunk) => (chunk.origin_worldpos, Vec3D::new(map.dims.chunk_length_i, map.dims.chunk_width_i, map.dims.chunk_height_i)),
+            None => return
+        };
+        for x in origin.x - 1..=origin.x + size.x {
+            for y in origin.y - 1..=origin.y + size.y {
+                for z in origin.z - 1..=origin.z + size.z {
+                    let pos = Vec3D::new(x, y, z);
+                    let border = x < origin.x || y < origin.y || z < origin.z || x >= origin.x + size.x || y >= origin.y + size.y || z >= origin.z + size.z;
+                    if border {
+                        match map.get_voxel_at(pos) {
+                            Some(voxel) => if Self::spreads_light(map, pos, voxel) {
+                                self.queue_add(LightPos::new(pos, voxel.light_level()));
+                            },
+                            None => ()
+                        }
+                    }
+                }
+            }
+        }
+        self.propagate(map);
+    }
+    pub fn unlight<V:Voxel>(&mut self, map:&mut GameMap<V>) {
+        while let Some(removed) = self.removal_queue.pop_front() {
+            for dir in EXPLORATION {
+                let new_pos = removed.pos() + dir;
+                let (neighbour_light, spreads) = match map.get_voxel_at(new_pos) {
+                    Some(voxel) => (voxel.light_level(), Self::spreads_light(map, new_pos, voxel)),
+                    None => continue
+                };
+                if neighbour_light.level == 0 {
+                    continue
+                }
+                match map.light_sources.get(&new_pos).copied() {
+                    Some(source) => {
+                        // sources never get darkened, they just light everything back up
+                        self.set_light(map, new_pos, source);
+                        self.queue_add(LightPos::new(new_pos, source));
+                    },
+                    None => if neighbour_light.level < removed.value().level {
+                        self.set_light(map, new_pos, VoxelLight::zero_light());
+                        self.queue_removal(LightPos::new(new_pos, neighbour_light));
+                    }
+                    else if spreads {
+                        self.queue_add(LightPos::new(new_pos, neighbour_light));
+                    }
+                }
+            }
+        }
+    }
+    pub fn propagate<V:Voxel>(&mut self, map:&mut GameMap<V>) {
+        while let Some(light) = self.add_queue.pop_front() {
+            for dir in EXPLORATION {
+                let new_pos = light.pos() + dir;
+                let (current_light, passthrough) = match map.get_voxel_at(new_pos) {
+                    Some(voxel) => (voxel.light_level(), map.get_voxel_types()[voxel.voxel_id()].light_passthrough()),
+                    None => continue
+                };
+                if passthrough.level == 0 {
+                    continue
+                }
+                let new_light = attenuate(light.value(), passthrough);
+                if new_light.level > current_light.level {
+                    self.set_light(map, new_pos, new_light);
+                    self.queue_add(LightPos::new(new_pos, new_light));
+                }
+            }
+        }
+    }
+}
+
+// Skylight is a separate 0..=SKY_MAX channel : it goes straight down without losing anything until it hits something opaque,
+// and loses one level per voxel when it spreads sideways (or up) into overhangs and caves.
+// How bright SKY_MAX actually is depends on the time of day, that is only applied when colouring meshes (see sky_shading).
+
+pub const SKY_MAX:u8 = 15;
+
+const DOWN:usize = 2;
+
+fn sky_after_step(level:u8, dir_index:usize) -> u8 {
+    if dir_index == DOWN && level == SKY_MAX {
+        SKY_MAX
+    }
+    else {
+        level.saturating_sub(1)
+    }
+}
+
+#[derive(Clone, Default, ToBytes, FromBytes)]
+pub struct SkyLightEngine {
+    add_queue:VecDeque<(WorldVoxelPos, u8)>,
+    removal_queue:VecDeque<(WorldVoxelPos, u8)>,
+    touched:HashSet<WorldVoxelPos>,
+}
+
+impl SkyLightEngine {
+    pub fn new() -> Self {
+        Self { add_queue: VecDeque::with_capacity(256), removal_queue: VecDeque::with_capacity(256), touched: HashSet::with_capacity(256) }
+    }
+    pub fn take_touched(&mut self) -> HashSet<WorldVoxelPos> {
+        std::mem::take(&mut self.touched)
+    }
+    fn set_sky<V:Voxel>(&mut self, map:&mut GameMap<V>, pos:WorldVoxelPos, level:u8) {
+        if map.modify_voxel_at(pos, |voxel| {voxel.set_sky_light(level)}) {
+            self.touched.insert(pos);
+        }
+    }
+    fn is_opaque<V:Voxel>(map:&GameMap<V>, voxel:&V) -> bool {
+        map.get_voxel_types()[voxel.voxel_id()].light_passthrough().level == 0
+    }
+    /// Fills every vertical column of the chunk from the top, using the chunk above as input (full sky if it isn't loaded),
+    /// then spreads sideways inside the chunk and into its loaded neighbours
+    pub fn light_chunk<V:Voxel>(&mut self, map:&mut GameMap<V>, chunk_pos:WorldChunkPos) {
+        let (origin, size) = match map.get_chunk_at(chunk_pos) {
+            Some(chunk) => (chunk.origin_worldpos, Vec3D::new(map.dims.chunk_length_i, map.dims.chunk_width_i, map.dims.chunk_height_i)),
+            None => return
+        };
+        for x in origin.x..origin.x + size.x {
+            for y in origin.y..origin.y + size.y {
+                let top = origin.z + size.z - 1;
+                let mut level = match map.get_voxel_at(Vec3D::new(x, y, top + 1)) {
+                    Some(above) => if above.sky_light() == SKY_MAX && !Self::is_opaque(map, above) {SKY_MAX} else {0},
+                    None => SKY_MAX
+                };
+                for z in (origin.z..=top).rev() {
+                    let pos = Vec3D::new(x, y, z);
+                    let opaque = Self::is_opaque(map, map.get_voxel_at(pos).unwrap());
+                    if opaque {
+                        level = 0;
+                    }
+                    if map.get_voxel_at(pos).unwrap().sky_light() != level {
+                        self.set_sky(map, pos, level);
+                    }
+                    if level == SKY_MAX {
+                        self.add_queue.push_back((pos, level));
+                    }
+                }
+            }
+        }
+        // lit neighbours around the chunk can spread into it too
+        for x in origin.x - 1..=origin.x + size.x {
+            for y in origin.y - 1..=origin.y + size.y {
+                for z in origin.z - 1..=origin.z + size.z {
+                    let pos = Vec3D::new(x, y, z);
+                    let border = x < origin.x || y < origin.y || z < origin.z || x >= origin.x + size.x || y >= origin.y + size.y || z >= origin.z + size.z;
+                    if border {
+                        match map.get_voxel_at(pos) {
+                            Some(voxel) => if voxel.sky_light() > 0 {
+                                self.add_queue.push_back((pos, voxel.sky_light()));
+                            },
+                            None => ()
+                        }
+                    }
+                }
+            }
+        }
+        // what used to be full sky right under this chunk might not be anymore
+        for x in origin.x..origin.x + size.x {
+            for y in origin.y..origin.y + size.y {
+                let below = Vec3D::new(x, y, origin.z - 1);
+                let bottom = Vec3D::new(x, y, origin.z);
+                if map.get_voxel_at(below).is_some_and(|voxel| {voxel.sky_light() == SKY_MAX}) && map.get_voxel_at(bottom).is_some_and(|voxel| {voxel.sky_light() < SKY_MAX}) {
+                    self.set_sky(map, below, 0);
+                    self.removal_queue.push_back((below, SKY_MAX));
+                }
+            }
+        }
+        self.unlight(map);
+        self.propagate(map);
+    }
+    pub fn darken<V:Voxel>(&mut self, map:&mut GameMap<V>, pos:WorldVoxelPos) {
+        match map.get_voxel_at(pos) {
+            Some(voxel) => {
+                let old_level = voxel.sky_light();
+                self.set_sky(map, pos, 0);
+                self.removal_queue.push_back((pos, old_level));
+            },
+            None => ()
+        }
+        self.unlight(map);
+        self.propagate(map);
+    }
+    pub fn relight_from_neighbours<V:Voxel>(&mut self, map:&mut GameMap<V>, pos:WorldVoxelPos) {
+        for dir in EXPLORATION {
+            match map.get_voxel_at(pos + dir) {
+                Some(voxel) => if voxel.sky_light() > 0 {
+                    self.add_queue.push_back((pos + dir, voxel.sky_light()));
+                },
+                None => if dir.z == 1 {
+                    // nothing loaded above means open sky
+                    self.add_queue.push_back((pos + dir, SKY_MAX));
+                }
+            }
+        }
+        self.propagate(map);
+    }
+    fn unlight<V:Voxel>(&mut self, map:&mut GameMap<V>) {
+        while let Some((pos, level)) = self.removal_queue.pop_front() {
+            for (i, dir) in EXPLORATION.iter().enumerate() {
+                let new_pos = pos + *dir;
+                let neighbour_level = match map.get_voxel_at(new_pos) {
+                    Some(voxel) => voxel.sky_light(),
+                    None => continue
+                };
+                if neighbour_level == 0 {
+                    continue
+                }
+                if neighbour_level < level || (i == DOWN && level == SKY_MAX && neighbour_level == SKY_MAX) {
+                    self.set_sky(map, new_pos, 0);
+                    self.removal_queue.push_back((new_pos, neighbour_level));
+                }
+                else {
+                    self.add_queue.push_back((new_pos, neighbour_level));
+                }
+            }
+        }
+    }
+    fn propagate<V:Voxel>(&mut self, map:&mut GameMap<V>) {
+        while let Some((pos, level)) = self.add_queue.pop_front() {
+            for (i, dir) in EXPLORATION.iter().enumerate() {
+                let new_pos = pos + *dir;
+                let new_level = sky_after_step(level, i);
+                if new_level == 0 {
+                    continue
+                }
+                let current = match map.get_voxel_at(new_pos) {
+                    Some(voxel) => if Self::is_opaque(map, voxel) {continue} else {voxel.sky_light()},
+                    None => continue
+                };
+                if new_level > current {
+                    self.set_sky(map, new_pos, new_level);
+                    self.add_queue.push_back((new_pos, new_level));
+                }
+            }
+        }
+    }
+}
@@ -2,11 +2,13 @@ use std::{collections::{HashSet, VecDeque}, hash::Hash};
 
 use hord3::{defaults::default_rendering::vectorinator_binned::triangles::{collux_f32_a_u8, collux_u8_a_f32}, horde::geometry::vec3d::Vec3D};
 
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
 use crate::{game_engine::CoolVoxel, game_map::VoxelLight};
 
 use super::{GameMap, Voxel, VoxelType, EXPLORATION};
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ToBytes, FromBytes)]
 pub struct LightPos {
     pos:Vec3D<i32>,
     value:VoxelLight,
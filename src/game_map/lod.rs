@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use hord3::horde::geometry::vec3d::{Vec3D, Vec3Df};
+
+use super::{get_chunk_pos_i, sky_shading::{ShadedLOD, ShadedTriangles}, smooth_lighting::add_smooth_quad, ChunkDims, ChunkMesher, GameMap, MapChunk, Voxel, VoxelType, WorldChunkPos, WorldVoxelPos, TRIS_INDICES_UVS};
+
+// Chunk LODs, most to least detailed :
+// - the greedy mesh
+// - stepped meshes merging step^3 voxels, with steps of 2, 4 and 8 along every axis that's a multiple of them
+//   (an axis that isn't keeps its biggest step that divides it, levels that wouldn't change anything are skipped)
+// - a heightfield impostor : a few quads following the top surface of the whole column of chunks, carried by the topmost
+//   non empty chunk of the column while every other chunk gets an empty level, so distant terrain is one surface per column.
+//   Any change to a chunk of a column (or of the columns its last vertices sample) makes the column stale : before the next
+//   meshing jobs go out, the chunk carrying the impostor is found again and remeshed, along with the one that carried it before if
+//   that changed. The job gets copies of the column's chunks and samples the surface itself. Caves or overhangs don't show at that distance.
+// Which level gets drawn depends on the distance times the map's lod factor.
+
+pub const DEFAULT_LOD_FACTOR:f32 = 0.025;
+const LOD_STEPS:[i32 ; 4] = [1, 2, 4, 8];
+/// Quads along each side of a chunk in its heightfield impostor
+const IMPOSTOR_CELLS:i32 = 4;
+
+/// Biggest power of two up to `wanted` that divides `length`
+fn axis_step(length:i32, wanted:i32) -> i32 {
+    let mut step = 1;
+    while step * 2 <= wanted && length % (step * 2) == 0 {
+        step *= 2;
+    }
+    step
+}
+
+/// Voxel borders of the impostor vertices along an axis, both ends included
+fn impostor_vertices(length:i32) -> Vec<i32> {
+    let cells = IMPOSTOR_CELLS.min(length);
+    (0..=cells).map(|i| {i * length / cells}).collect()
+}
+
+impl ChunkDims {
+    /// Steps of every stepped LOD along each axis, (1, 1, 1) for the full detail one first
+    pub fn lod_steps(&self) -> Vec<Vec3D<i32>> {
+        let mut steps:Vec<Vec3D<i32>> = Vec::with_capacity(LOD_STEPS.len());
+        for wanted in LOD_STEPS {
+            let step = Vec3D::new(axis_step(self.chunk_length_i, wanted), axis_step(self.chunk_width_i, wanted), axis_step(self.chunk_height_i, wanted));
+            if steps.last() != Some(&step) {
+                steps.push(step);
+            }
+        }
+        steps
+    }
+}
+
+/// x and y of the chunks of a column
+pub type ColumnPos = (i32, i32);
+
+fn column_of(chunk_pos:WorldChunkPos) -> ColumnPos {
+    (chunk_pos.x, chunk_pos.y)
+}
+
+/// Chunks the impostor of a column is sampled from, copied for the meshing threads
+#[derive(Clone)]
+pub struct ColumnSnapshot<V:Voxel> {
+    /// origin of the chunk carrying the impostor
+    origin:WorldVoxelPos,
+    /// the chunk carrying the impostor, the loaded ones under and right above it, in its column and the +x, +y and +x+y ones
+    chunks:HashMap<WorldChunkPos, MapChunk<V>>,
+}
+
+/// Top surface of a chunk column, sampled at the impostor vertices
+#[derive(Clone)]
+pub struct ColumnHeightfield<V:Voxel> {
+    xs:Vec<i32>,
+    ys:Vec<i32>,
+    /// x first, height of the surface voxel from the chunk origin, the surface voxel and the one above it.
+    /// None where the column isn't loaded
+    samples:Vec<Option<(i32, V, V)>>,
+}
+
+impl<V:Voxel> ColumnHeightfield<V> {
+    /// How far under and over the chunk the surface goes
+    fn vertical_overflow(&self, chunk_height:i32) -> i32 {
+        self.samples.iter().flatten().fold(0, |overflow, (height, _, _)| {overflow.max(-height).max(height + 1 - chunk_height)})
+    }
+}
+
+impl<V:Voxel> MapChunk<V> {
+    fn is_all_empty(&self, voxel_types:&Vec<V::VT>) -> bool {
+        self.voxels.palette_all(|voxel| {voxel_types[voxel.voxel_id()].is_completely_empty()})
+    }
+}
+
+impl<V:Voxel> GameMap<V> {
+    pub fn get_lod_factor(&self) -> f32 {
+        self.lod_factor
+    }
+    /// Bigger is coarser, applies to the meshes built from then on
+    pub fn set_lod_factor(&mut self, lod_factor:f32) {
+        self.lod_factor = lod_factor;
+    }
+    /// A chunk of the column of `chunk_pos` changed, which can move the impostor to another chunk or change its shape,
+    /// the columns at -x, -y and -x-y sample it with their last vertices
+    pub(super) fn signal_column_changed(&mut self, chunk_pos:WorldChunkPos) {
+        let (x, y) = column_of(chunk_pos);
+        self.stale_columns.extend([(x, y), (x - 1, y), (x, y - 1), (x - 1, y - 1)]);
+    }
+    fn mark_impostor_remesh(&mut self, chunk_pos:WorldChunkPos) {
+        match self.get_chunk_at_mut(chunk_pos) {
+            Some(chunk) => {
+                chunk.mark_for_remesh();
+                self.remesh_fasttrack.push(chunk_pos);
+                self.rendering_up_to_date = false;
+            },
+            None => ()
+        }
+    }
+    /// Finds which chunk carries the impostor of every stale column and remeshes the chunks whose impostor changed
+    pub(super) fn update_stale_columns(&mut self) {
+        if self.stale_columns.is_empty() {
+            return
+        }
+        let stale = std::mem::take(&mut self.stale_columns);
+        let mut tops:HashMap<ColumnPos, i32> = HashMap::with_capacity(stale.len());
+        for (pos, chunk) in self.chunks.iter() {
+            let column = column_of(*pos);
+            if stale.contains(&column) && tops.get(&column).is_none_or(|top| {*top < pos.z}) && !chunk.is_all_empty(&self.voxel_types) {
+                tops.insert(column, pos.z);
+            }
+        }
+        for column in stale {
+            let new_top = tops.get(&column).copied();
+            let old_top = match new_top {
+                Some(top) => self.impostor_holders.insert(column, top),
+                None => self.impostor_holders.remove(&column)
+            };
+            match old_top {
+                Some(old) if Some(old) != new_top => self.mark_impostor_remesh(Vec3D::new(column.0, column.1, old)),
+                _ => ()
+            }
+            match new_top {
+                Some(top) => self.mark_impostor_remesh(Vec3D::new(column.0, column.1, top)),
+                None => ()
+            }
+        }
+    }
+    /// Copies of the chunks the impostor needs if the chunk carries the one of its column
+    pub(super) fn column_snapshot(&self, chunk_pos:WorldChunkPos) -> Option<ColumnSnapshot<V>> {
+        if self.impostor_holders.get(&column_of(chunk_pos)) != Some(&chunk_pos.z) {
+            return None
+        }
+        let mut chunks = HashMap::with_capacity(16);
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            // from right above the chunk down to the first unloaded one
+            let mut pos = Vec3D::new(chunk_pos.x + dx, chunk_pos.y + dy, chunk_pos.z + 1);
+            loop {
+                match self.chunks.get(&pos) {
+                    Some(chunk) => {chunks.insert(pos, chunk.clone());},
+                    None => if pos.z <= chunk_pos.z {
+                        break
+                    }
+                }
+                pos.z -= 1;
+            }
+        }
+        Some(ColumnSnapshot { origin: self.get_chunk_origin(chunk_pos), chunks })
+    }
+}
+
+impl<V:Voxel> ColumnSnapshot<V> {
+    fn get_voxel_at(&self, pos:WorldVoxelPos, dims:&ChunkDims) -> Option<&V> {
+        self.chunks.get(&get_chunk_pos_i(dims, pos)).and_then(|chunk| {chunk.get_at_worldpos(pos, dims)})
+    }
+}
+
+impl<V:Voxel> ChunkMesher<V> {
+    /// Height of the top non empty voxel of a column around `start_z` with that voxel and the one above, None if it runs into unloaded chunks
+    fn surface_sample(&self, column:&ColumnSnapshot<V>, x:i32, y:i32, start_z:i32) -> Option<(i32, V, V)> {
+        let is_empty = |voxel:&V| {self.voxel_types[voxel.voxel_id()].is_completely_empty()};
+        let mut z = start_z;
+        let mut voxel = column.get_voxel_at(Vec3D::new(x, y, z), &self.dims)?;
+        if is_empty(voxel) {
+            loop {
+                let below = column.get_voxel_at(Vec3D::new(x, y, z - 1), &self.dims)?;
+                if !is_empty(below) {
+                    return Some((z - 1, below.clone(), voxel.clone()))
+                }
+                z -= 1;
+                voxel = below;
+            }
+        }
+        else {
+            loop {
+                let above = column.get_voxel_at(Vec3D::new(x, y, z + 1), &self.dims)?;
+                if is_empty(above) {
+                    return Some((z, voxel.clone(), above.clone()))
+                }
+                z += 1;
+                voxel = above;
+            }
+        }
+    }
+    /// Samples the surface of the column for its impostor
+    pub(super) fn column_heightfield(&self, column:&ColumnSnapshot<V>) -> ColumnHeightfield<V> {
+        let origin = column.origin;
+        let top = origin.z + self.dims.chunk_height_i - 1;
+        let (xs, ys) = (impostor_vertices(self.dims.chunk_length_i), impostor_vertices(self.dims.chunk_width_i));
+        let mut samples = Vec::with_capacity(xs.len() * ys.len());
+        for y in &ys {
+            for x in &xs {
+                samples.push(self.surface_sample(column, origin.x + x, origin.y + y, top).map(|(z, surface, above)| {(z - origin.z, surface, above)}));
+            }
+        }
+        ColumnHeightfield { xs, ys, samples }
+    }
+    /// Impostor level, empty for chunks that aren't the top of their column
+    pub(super) fn get_heightfield_lod(&self, impostor:Option<&ColumnHeightfield<V>>) -> ShadedLOD {
+        let mut lod = ShadedLOD::new(Vec::with_capacity(64), Vec::with_capacity(64), Vec::with_capacity(64), ShadedTriangles::with_capacity(32));
+        let field = match impostor {
+            Some(field) => field,
+            None => return lod
+        };
+        let up = TRIS_INDICES_UVS.0[0];
+        for j in 0..field.ys.len() - 1 {
+            for i in 0..field.xs.len() - 1 {
+                // vertex of the cell for each corner of the up face
+                let cells = up.map(|point| {(i + (point.x > 0.0) as usize, j + (point.y > 0.0) as usize)});
+                let corners = cells.map(|(ci, cj)| {field.samples[ci + cj * field.xs.len()].as_ref()});
+                if corners.iter().any(|corner| {corner.is_none()}) {
+                    continue
+                }
+                let points:[Vec3Df ; 4] = std::array::from_fn(|k| {
+                    let (ci, cj) = cells[k];
+                    Vec3D::new(field.xs[ci] as f32 - 0.5, field.ys[cj] as f32 - 0.5, corners[k].unwrap().0 as f32 + 0.5)
+                });
+                let shades = corners.map(|corner| {
+                    let (_, _, above) = corner.unwrap();
+                    self.get_face_shade(above.light_level(), above.sky_light())
+                });
+                let texture = match &field.samples[i + j * field.xs.len()] {
+                    Some((_, surface, _)) => self.get_face_texture(surface, 0),
+                    None => continue
+                };
+                add_smooth_quad(&mut lod, &points, &shades, texture, ((field.xs[i + 1] - field.xs[i]) as f32, (field.ys[j + 1] - field.ys[j]) as f32));
+            }
+        }
+        lod
+    }
+    /// Radius around the chunk origin its meshes fit in
+    pub(super) fn mesh_radius(&self, impostor:Option<&ColumnHeightfield<V>>) -> f32 {
+        let height = self.dims.chunk_height_f + impostor.map(|field| {field.vertical_overflow(self.dims.chunk_height_i)}).unwrap_or(0) as f32;
+        (self.dims.chunk_length_f.powi(2) + self.dims.chunk_width_f.powi(2) + height.powi(2)).sqrt()
+    }
+}
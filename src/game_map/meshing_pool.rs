@@ -0,0 +1,154 @@
+use std::{cmp::Ordering, collections::{BinaryHeap, HashMap}, sync::{Arc, Condvar, LazyLock, Mutex}, thread::{self, JoinHandle}};
+
+use hord3::defaults::default_rendering::vectorinator_binned::meshes::MeshLODType;
+
+use super::{sky_shading::ShadedChunk, WorldChunkPos};
+
+// Chunk meshing runs on a few background threads : the render phase snapshots a chunk and the voxels around it (see chunk_borders) into a job,
+// and picks the finished LODs up on a later frame. Recolouring the meshes of a chunk for another sky brightness (see sky_shading) is a job too.
+// A chunk submitted again before its job started replaces it : the queue remembers the last job of every chunk
+// and workers drop the older ones they pop, so submitting never has to search the queue.
+// Every job carries a generation, the GameMap only keeps the result of the last job it sent for a chunk,
+// so a chunk that changed while being meshed just has its old result thrown away.
+// Results are sorted by mesh_vec since several GameMaps can share the pool.
+// The pool is a static, which never gets dropped : shutdown has to be called before exiting to stop the workers.
+
+pub struct MeshedChunk {
+    pub pos:WorldChunkPos,
+    pub mesh_vec:usize,
+    pub generation:u64,
+    pub lods:Vec<MeshLODType>,
+    pub translucent:Option<MeshLODType>,
+    /// see ChunkMesher::mesh_radius
+    pub radius:f32,
+    /// uncoloured meshes, kept when skylight reaches them
+    pub shaded:Option<ShadedChunk>,
+    /// sky brightness the meshes are coloured for
+    pub sky_brightness:u8,
+}
+
+impl MeshedChunk {
+    pub fn new(pos:WorldChunkPos, mesh_vec:usize, generation:u64, shaded:ShadedChunk, sky_brightness:u8) -> Self {
+        let (lods, translucent) = shaded.colored(sky_brightness);
+        let radius = shaded.radius;
+        Self { pos, mesh_vec, generation, lods, translucent, radius, shaded: if shaded.has_sky() {Some(shaded)} else {None}, sky_brightness }
+    }
+}
+
+struct MeshingJob {
+    /// lower is more urgent
+    priority:u32,
+    order:u64,
+    key:(usize, WorldChunkPos),
+    work:Box<dyn FnOnce() -> MeshedChunk + Send>,
+}
+
+impl PartialEq for MeshingJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority && self.order == other.order
+    }
+}
+
+impl Eq for MeshingJob {}
+
+impl PartialOrd for MeshingJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MeshingJob {
+    // BinaryHeap pops the biggest, so the lowest priority then the oldest job has to compare as the biggest
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.cmp(&self.priority).then_with(|| {other.order.cmp(&self.order)})
+    }
+}
+
+struct PoolQueue {
+    jobs:BinaryHeap<MeshingJob>,
+    /// order of the last job submitted for each chunk that hasn't started yet
+    latest:HashMap<(usize, WorldChunkPos), u64>,
+    next_order:u64,
+    shutting_down:bool,
+}
+
+struct PoolShared {
+    queue:Mutex<PoolQueue>,
+    available:Condvar,
+    results:Mutex<HashMap<usize, Vec<MeshedChunk>>>,
+}
+
+pub struct MeshingPool {
+    shared:Arc<PoolShared>,
+    workers:Mutex<Vec<JoinHandle<()>>>,
+}
+
+pub static MESHING_POOL:LazyLock<MeshingPool> = LazyLock::new(|| {
+    let workers = thread::available_parallelism().map(|count| {count.get()}).unwrap_or(2).saturating_sub(2).max(1);
+    MeshingPool::new(workers)
+});
+
+impl MeshingPool {
+    pub fn new(workers:usize) -> Self {
+        let shared = Arc::new(PoolShared { queue: Mutex::new(PoolQueue { jobs: BinaryHeap::with_capacity(256), latest: HashMap::with_capacity(256), next_order: 0, shutting_down: false }), available: Condvar::new(), results: Mutex::new(HashMap::with_capacity(4)) });
+        let workers = (0..workers).map(|i| {
+            let worker_shared = shared.clone();
+            thread::Builder::new().name(format!("chunk mesher {}", i)).spawn(move || {worker_loop(worker_shared)}).expect("couldn't start a chunk meshing thread")
+        }).collect();
+        Self { shared, workers: Mutex::new(workers) }
+    }
+    pub fn get_workers(&self) -> usize {
+        self.workers.lock().unwrap().len()
+    }
+    /// Queues a meshing job, replacing the job for the same chunk if it hasn't started yet
+    pub fn submit(&self, mesh_vec:usize, pos:WorldChunkPos, priority:u32, work:Box<dyn FnOnce() -> MeshedChunk + Send>) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let order = queue.next_order;
+        queue.next_order += 1;
+        queue.latest.insert((mesh_vec, pos), order);
+        queue.jobs.push(MeshingJob { priority, order, key: (mesh_vec, pos), work });
+        self.shared.available.notify_one();
+    }
+    pub fn take_results(&self, mesh_vec:usize) -> Vec<MeshedChunk> {
+        match self.shared.results.lock().unwrap().get_mut(&mesh_vec) {
+            Some(results) => std::mem::take(results),
+            None => Vec::new()
+        }
+    }
+    pub fn queued_jobs(&self) -> usize {
+        self.shared.queue.lock().unwrap().latest.len()
+    }
+    /// Lets the workers finish the job they're on and waits for them, queued jobs are dropped and nothing submitted afterwards runs
+    pub fn shutdown(&self) {
+        self.shared.queue.lock().unwrap().shutting_down = true;
+        self.shared.available.notify_all();
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap());
+        for worker in workers {
+            let _ = worker.join();
+        }
+    }
+}
+
+
+fn worker_loop(shared:Arc<PoolShared>) {
+    loop {
+        let job = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if queue.shutting_down {
+                    return
+                }
+                match queue.jobs.pop() {
+                    // jobs replaced by a later one for the same chunk are skipped
+                    Some(job) => if queue.latest.get(&job.key) == Some(&job.order) {
+                        queue.latest.remove(&job.key);
+                        break job
+                    },
+                    None => queue = shared.available.wait(queue).unwrap()
+                }
+            }
+        };
+        let meshed = (job.work)();
+        shared.results.lock().unwrap().entry(meshed.mesh_vec).or_insert_with(|| {Vec::with_capacity(16)}).push(meshed);
+    }
+}
@@ -1,18 +1,49 @@
-use std::{collections::{HashMap, VecDeque}, f32::consts::{PI, SQRT_2}, simd::{num::SimdFloat, Simd}, sync::{Arc, LazyLock}};
+use std::{collections::{HashMap, HashSet}, f32::consts::PI, simd::{num::SimdFloat, Simd}, sync::{Arc, LazyLock}};
 
-use hord3::{defaults::default_rendering::vectorinator_binned::{meshes::{Mesh, MeshID, MeshInstance, MeshLOD, MeshLODS, MeshLODType, MeshTriangles, TrianglePoint}, triangles::{collux_f32_a_u8, collux_one_simd_to_u8_level, collux_u8_a_f32, collux_u8_tuple_to_f32_simd}, Vectorinator, VectorinatorWrite}, horde::{game_engine::{entity::Renderable, multiplayer::Identify, world::{World, WorldEvent}}, geometry::{rotation::{Orientation, Rotation}, vec3d::{Vec3D, Vec3Df}}, rendering::RenderingBackend}, tests::engine_derive_test::TestRB};
+use hord3::{defaults::default_rendering::vectorinator_binned::{meshes::{Mesh, MeshID, MeshInstance, MeshLOD, MeshLODS, MeshTriangles, TrianglePoint}, triangles::{collux_f32_a_u8, collux_one_simd_to_u8_level, collux_u8_a_f32, collux_u8_tuple_to_f32_simd}, Vectorinator, VectorinatorWrite}, horde::{game_engine::{entity::Renderable, multiplayer::Identify, world::{World, WorldEvent}}, geometry::{rotation::{Orientation, Rotation}, vec3d::{Vec3D, Vec3Df}}, rendering::RenderingBackend}, tests::engine_derive_test::TestRB};
 use to_from_bytes::{FromBytes, ToBytes};
 use to_from_bytes_derive::{FromBytes, ToBytes};
-use vec_sparse_grid::{SetGrid, SetGridUpdate};
+use light_engine::{LightEngine, SkyLightEngine};
+use meshing_pool::{MeshedChunk, MESHING_POOL};
+use spatial_hash::{SetGrid, SetGridUpdate};
+use chunk_borders::{offsets_around, ChunkBorders};
+use chunk_storage::ChunkStorage;
+use cutout::CutoutMask;
+use lod::{ColumnHeightfield, ColumnPos, DEFAULT_LOD_FACTOR};
+use sky_shading::{ShadedChunk, ShadedLOD, ShadedPoint, ShadedTriangles, VertexShade};
+use voxel_physics::VoxelPhysics;
 
 
+pub mod chunk_borders;
+pub mod chunk_storage;
+pub mod cutout;
+pub mod durability;
+pub mod emissive;
+pub mod greedy_mesher;
+pub mod light_engine;
+pub mod lod;
+pub mod meshing_pool;
 pub mod light_spreader;
+pub mod persistence;
 pub mod raycaster;
-pub mod sparse_grid;
-pub mod vec_sparse_grid;
+pub mod sky_shading;
+pub mod smooth_lighting;
+pub mod spatial_hash;
+pub mod streaming;
+pub mod translucent;
+pub mod visibility;
+pub mod voxel_physics;
+pub mod voxel_shapes;
 
 pub const VEC_LENGTH:usize = 4;
+pub const SKY_BRIGHTNESS_STEP:u8 = 16;
+pub const SKY_RECOLOR_BUDGET:usize = 12;
+pub const TRANSLUCENT_VEC_OFFSET:usize = 8;
 pub const SET_CAPACITY:usize = 16;
+/// Side of the set_grid cells, entities are indexed by the cell of their position
+pub const ENTITY_CELL_SIZE:f32 = 4.0;
+/// set_grid layer of the entity_1 entities
+pub const ENTITY_GRID_VEC:usize = 2;
 
 
 #[derive(Clone, Copy,ToBytes,FromBytes, PartialEq, Eq, Hash, Debug)]
@@ -72,11 +103,30 @@ impl VoxelLight {
     }
 }
 
-pub trait Voxel:Clone + Send + Sync + FromBytes + ToBytes {
+pub trait Voxel:Clone + PartialEq + Send + Sync + FromBytes + ToBytes + 'static {
     type VT:VoxelType;
     fn voxel_id(&self) -> usize;
     fn orientation(&self) -> u8;
     fn light_level(&self) -> VoxelLight;
+    fn set_light_level(&mut self, light:VoxelLight);
+    fn sky_light(&self) -> u8;
+    fn set_sky_light(&mut self, level:u8);
+    /// What's left behind when this voxel falls or gets broken
+    fn as_empty(&self) -> Self;
+    fn has_trap(&self) -> bool {
+        false
+    }
+    /// Voxels with extra data are kept out of the chunk palettes, see chunk_storage.rs
+    fn has_extra_data(&self) -> bool {
+        false
+    }
+    /// Light given off by this voxel in particular, its type's is used otherwise
+    fn light_emission(&self) -> Option<VoxelLight> {
+        None
+    }
+    /// None when the voxel is at full health
+    fn health(&self) -> Option<u16>;
+    fn set_health(&mut self, health:Option<u16>);
     /*fn orientation_converted(&self) -> Orientation {
         // first 3 bits = which side of the voxel is the underside against
         // 000 => base
@@ -113,46 +163,55 @@ pub enum VoxelModel {
     Custom
 }
 
-pub trait VoxelType:Clone + Send + Sync + FromBytes + ToBytes {
+/// Opaque voxels hide whatever is behind them, cutout ones (fences) are drawn with the opaque geometry but don't hide their neighbours' faces
+/// and only cover the opaque texels of their texture (see cutout), translucent ones (water, glass) go in a separate mesh drawn afterwards
+#[derive(Clone, Copy, PartialEq, Debug, ToBytes, FromBytes)]
+pub enum RenderLayer {
+    Opaque,
+    Cutout,
+    Translucent,
+}
+
+pub trait VoxelType:Clone + Send + Sync + FromBytes + ToBytes + 'static {
     fn sides_empty(&self) -> u8;
     fn vertices_taken(&self) -> u8;
     fn kind_of_model(&self) -> VoxelModel;
+    fn render_layer(&self) -> RenderLayer {
+        RenderLayer::Opaque
+    }
+    /// Which parts of the faces of a cutout voxel are drawn, None to draw them whole
+    fn cutout_mask(&self) -> Option<&CutoutMask> {
+        None
+    }
+    fn physics(&self) -> VoxelPhysics {
+        VoxelPhysics::Static
+    }
+    /// None for voxels that can't be damaged
+    fn max_health(&self) -> Option<u16> {
+        None
+    }
+    /// Whether damaged voxels of this type come back on GameMapEvent::RepairWalls
+    fn repairs(&self) -> bool {
+        false
+    }
+    /// Light every voxel of this type gives off, see emissive.rs
+    fn light_emission(&self) -> Option<VoxelLight> {
+        None
+    }
     fn light_passthrough(&self) -> VoxelLight;
     fn empty_coming_from(&self, from:u8, orientation:u8) -> bool {
         let rotated_2_empty = self.empties_with_orientation(orientation);
         rotated_2_empty >> (from as u32) & 1 == 1
     }
     fn empties_with_orientation(&self, orientation:u8) -> u8 {
-        let rotat_1 = (orientation >> 3) & 0b00000011;
         let self_empty = self.sides_empty();
-        let mut rotated_1_empty:u8 = self_empty;
-        for (i, rotates) in ROTATES_ON_1.into_iter().enumerate() {
-            let target = LUT_ROTAT_1[rotat_1 as usize][i];
-            if target >= rotates {
-
-                rotated_1_empty |= self_empty & (1 << rotates) << (target - rotates)
-            }
-            else {
-                rotated_1_empty |= self_empty & (1 << rotates) >> (rotates - target)
+        let mut rotated_empty = 0;
+        for side in 0..6 {
+            if (self_empty >> side) & 1 == 1 {
+                rotated_empty |= 1 << voxel_shapes::orient_side(orientation, side);
             }
         }
-        let mut rotated_2_empty:u8 = 0;
-        let rotat_2 = orientation & 0b00000111;
-        for i in [0, 1, 2, 3, 4, 5] {
-            let target = LUT_ROTAT_2[rotat_2 as usize][i as usize];
-            if target >= i {
-
-                rotated_2_empty |= rotated_1_empty & (1 << i) << (target - i)
-            }
-            else {
-                rotated_2_empty |= rotated_1_empty & (1 << i) >> (i - target)
-            }
-        }
-        if self_empty != rotated_2_empty {
-            dbg!(self_empty, rotated_2_empty, rotated_1_empty);
-        }   
-        
-        rotated_2_empty
+        rotated_empty
     }
     fn full_coming_from(&self, from:u8, orientation:u8) -> bool {
         !self.empty_coming_from(from, orientation)
@@ -171,7 +230,7 @@ pub trait VoxelType:Clone + Send + Sync + FromBytes + ToBytes {
     fn easy_texture(&self) -> usize;
 }
 
-const EXPLORATION:[Vec3D<i32> ; 6] = [
+pub const EXPLORATION:[Vec3D<i32> ; 6] = [
     Vec3D::new(0, 0, 1),
     Vec3D::new(1, 0, 0),
     Vec3D::new(0, 0, -1),
@@ -189,6 +248,8 @@ const DIR_MASK:[u8 ; 6] = [
     0b00100000
 ];
 
+const OPPOSITE:[usize ; 6] = [2, 3, 0, 1, 5, 4];
+
 const PERPENDICULAR:[(Vec3D<i32>, Vec3D<i32>) ; 6] = [
     (Vec3D::new(1, 0, 0), Vec3D::new(0, 1, 0)),
     (Vec3D::new(0, 1, 0), Vec3D::new(0, 0, 1)),
@@ -214,6 +275,7 @@ const ROTATES_ON_1:[u32 ; 4] = [
     1, 3, 4, 5
 ];
 
+// quarter turns from +x towards +y
 const LUT_ROTAT_1:[[u32 ; 4] ; 4] = [
     [1, 3, 4, 5],
     [3, 4, 5, 1],
@@ -234,17 +296,34 @@ const EMPTY_VOXEL:u8 = 0b00111111;
 
 #[derive(Clone, ToBytes, FromBytes)]
 pub struct MapChunk<V:Voxel> {
-    voxels:Vec<V>,
+    voxels:ChunkStorage<V>,
     origin_worldpos:WorldVoxelPos,
     chunk_coord:WorldChunkPos,
     mesh_id:Option<usize>,
     mesh_updated:bool,
     mesh_instance:Option<usize>,
+    modified_since_save:bool,
+    translucent_mesh_id:Option<usize>,
+    translucent_instance:Option<usize>,
+    has_translucent:bool,
+    /// original state of the repairing voxels that got damaged, see durability.rs
+    repair_records:HashMap<WorldVoxelPos, V>,
+    /// indices in voxels of the voxels carrying a trap, so the trap tick doesn't have to scan whole chunks
+    trap_voxels:Vec<u32>,
+    /// which faces see each other through the chunk, None until it's computed again, see visibility.rs
+    face_links:Option<u16>,
 }
 
 #[derive(Clone)]
 pub enum GameMapEvent<V:Voxel> {
     UpdateVoxelAt(WorldVoxelPos, V),
+    UpdateVoxelsBatch(Vec<(WorldVoxelPos, V)>),
+    SleepVoxels(Vec<WorldVoxelPos>),
+    DamageVoxel(WorldVoxelPos, u16),
+    /// Same voxel with different extra data (trap cooldowns...), doesn't relight or remesh anything if the type didn't change
+    UpdateVoxelDataAt(WorldVoxelPos, V),
+    UpdateVoxelDataBatch(Vec<(WorldVoxelPos, V)>),
+    RepairWalls,
     UpdateSetGrid(SetGridUpdate)
 }
 
@@ -257,8 +336,19 @@ impl<ID:Identify, V:Voxel> WorldEvent<GameMap<V>, ID> for GameMapEvent<V> {
     }
     fn apply_event(self, world:&mut GameMap<V>) {
         match self {
-            GameMapEvent::UpdateVoxelAt(pos, new_voxel) => {world.get_voxel_at_mut(pos).and_then(|vox| { *vox = new_voxel; None::<()>}); world.modified_this_pos_signal_remesh(pos);},
-            GameMapEvent::UpdateSetGrid(set_grid_update) => world.set_grid.apply_update::<VEC_LENGTH, SET_CAPACITY>(set_grid_update),
+            GameMapEvent::UpdateVoxelAt(pos, new_voxel) => {
+                world.record_if_destroyed(pos, &new_voxel);
+                world.set_voxel_at(pos, new_voxel);
+            },
+            GameMapEvent::UpdateVoxelsBatch(changes) => world.set_voxels_batch(changes),
+            GameMapEvent::SleepVoxels(positions) => world.sleep_voxels(&positions),
+            GameMapEvent::DamageVoxel(pos, amount) => {world.damage_voxel(pos, amount);},
+            GameMapEvent::UpdateVoxelDataAt(pos, new_voxel) => world.update_voxel_data_at(pos, new_voxel),
+            GameMapEvent::UpdateVoxelDataBatch(changes) => for (pos, new_voxel) in changes {
+                world.update_voxel_data_at(pos, new_voxel);
+            },
+            GameMapEvent::RepairWalls => {world.repair_walls();},
+            GameMapEvent::UpdateSetGrid(set_grid_update) => world.set_grid.apply_update(set_grid_update),
         }
     }
 }
@@ -273,70 +363,179 @@ impl<V:Voxel, ID:Identify> World<ID> for GameMap<V> {
 
 impl<'a, V:Voxel> Renderable<VectorinatorWrite<'a>> for GameMap<V> {
     fn do_render_changes(&mut self, render_data:&mut VectorinatorWrite<'a>) {
+        let mesh_vec = self.mesh_vec;
+        for (mesh_id, instance) in self.released_meshes.drain(..) {
+            render_data.meshes.instances[mesh_vec].get_instance_mut(instance).change_visibility(false);
+            self.free_meshes.push((mesh_id, instance));
+        }
+        for (mesh_id, instance) in self.released_translucent_meshes.drain(..) {
+            render_data.meshes.instances[self.translucent_mesh_vec].get_instance_mut(instance).change_visibility(false);
+            self.free_translucent_meshes.push((mesh_id, instance));
+        }
+        self.apply_finished_meshes(render_data);
+        self.resort_translucent_around(self.get_chunk_pos_i(get_voxel_pos(render_data.camera.pos)));
+        self.cull_hidden_chunks(self.get_chunk_pos_i(get_voxel_pos(render_data.camera.pos)), render_data);
         if !self.rendering_up_to_date {
-            let mut iterator = if self.remesh_fasttrack.len() > 0 {
+            self.update_stale_columns();
+            let fasttracked = self.remesh_fasttrack.len() > 0;
+            let mut iterator = if fasttracked {
                 self.remesh_fasttrack.clone()
             }
+            else if self.sky_recolor_queue.len() > 0 {
+                self.recolor_sky_chunks();
+                Vec::new()
+            }
             else {
                 self.chunks.keys().map(|vector| {vector.clone()}).collect::<Vec<Vec3D<i32>>>()
             };
             self.remesh_fasttrack.clear();
-            //let mut test_chunk_tris = 0;
-            //let mut test_chunk_id = 0;
-            let mesh_vec = self.mesh_vec;
+            iterator.retain(|pos| {self.chunks.contains_key(pos)});
+            let mesher = Arc::new(self.get_mesher().with_camera(render_data.camera.pos));
+            let camera_chunk = self.get_chunk_pos_i(get_voxel_pos(render_data.camera.pos));
             for pos in iterator {
-                let around = self.get_chunks_around(pos);
                 let chunk = self.chunks.get(&pos).unwrap();
                 if chunk.mesh_updated == false || self.forced_rerender {
-                    let mut lods = Vec::new();
-                    if self.dims.chunk_height_i == self.dims.chunk_length_i && self.dims.chunk_length_i == self.dims.chunk_width_i && self.dims.chunk_height_i >= 8 {
-                        const STEPS:[i32 ; 4] = [1, 2, 4, 8];
-                        for step in STEPS {
-                            lods.push(MeshLODType::Mesh(Arc::new( self.get_lod_with_step(chunk, around, step))));
+                    // edited chunks first, then the closest to the camera
+                    let priority = if fasttracked {0} else {1 + ((pos.x - camera_chunk.x).abs() + (pos.y - camera_chunk.y).abs() + (pos.z - camera_chunk.z).abs()) as u32};
+                    let generation = self.next_mesh_generation;
+                    self.next_mesh_generation += 1;
+                    let snapshot = chunk.clone();
+                    let borders = self.get_chunk_borders(pos);
+                    let column = self.column_snapshot(pos);
+                    let job_mesher = mesher.clone();
+                    self.pending_meshes.insert(pos, generation);
+                    MESHING_POOL.submit(mesh_vec, pos, priority, Box::new(move || {
+                        let impostor = column.map(|column| {job_mesher.column_heightfield(&column)});
+                        let shaded = job_mesher.mesh_chunk(&snapshot, &borders, impostor.as_ref());
+                        MeshedChunk::new(pos, mesh_vec, generation, shaded, job_mesher.get_sky_brightness())
+                    }));
+                }
+            }
+            self.rendering_up_to_date = self.sky_recolor_queue.is_empty();
+            self.forced_rerender = false;
+            // YAHOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOo
+        }
+    }
+}
+
+impl<V:Voxel> GameMap<V> {
+    /// Swaps in the meshes the meshing threads are done with, results for chunks that changed or unloaded since are dropped
+    fn apply_finished_meshes<'a>(&mut self, render_data:&mut VectorinatorWrite<'a>) {
+        let mesh_vec = self.mesh_vec;
+        let mut any_applied = false;
+        for meshed in MESHING_POOL.take_results(mesh_vec) {
+            if self.pending_meshes.get(&meshed.pos) != Some(&meshed.generation) {
+                continue
+            }
+            self.pending_meshes.remove(&meshed.pos);
+            let pos = meshed.pos;
+            let size = meshed.radius;
+            let origin = get_float_pos(self.get_chunk_origin(pos));
+            let lod_factor = self.lod_factor;
+            // new meshes follow the last culling, the walk only changes when face links do
+            let shown = self.is_chunk_seen(pos);
+            let recycled = if self.chunks.get(&pos).is_some_and(|chunk| {chunk.mesh_id.is_none()}) {self.free_meshes.pop()} else {None};
+            let chunk = match self.chunks.get_mut(&pos) {
+                Some(chunk) => chunk,
+                None => continue
+            };
+            // the sky brightness changed since the job was sent
+            if meshed.shaded.is_some() && meshed.sky_brightness != self.sky_brightness {
+                self.sky_recolor_queue.push(pos);
+                self.rendering_up_to_date = false;
+            }
+            match meshed.shaded {
+                Some(shaded) => {self.shaded_meshes.insert(pos, shaded);},
+                None => {self.shaded_meshes.remove(&pos);}
+            }
+            match recycled {
+                Some((id, instance)) => {
+                    chunk.mesh_id = Some(id);
+                    chunk.mesh_instance = Some(instance);
+                    *render_data.meshes.instances[mesh_vec].get_instance_mut(instance) = MeshInstance::new(origin, Orientation::zero(), MeshID::Referenced(id), true, false, false);
+                },
+                None => ()
+            }
+            match chunk.mesh_id {
+                Some(id) => {
+                    render_data.meshes.set_mesh(&MeshID::Referenced(id), Mesh::new(MeshLODS::new(meshed.lods), format!("Chunk {} {} {} {}", pos.x, pos.y, pos.z, mesh_vec), size).with_lod_factor(lod_factor));
+                },
+                None => {
+                    let id = render_data.meshes.add_mesh(Mesh::new(MeshLODS::new(meshed.lods), format!("Chunk {} {} {} {}", pos.x, pos.y, pos.z, mesh_vec), size).with_lod_factor(lod_factor));
+                    chunk.mesh_id = Some(id);
+                }
+            }
+            match chunk.mesh_instance {
+                Some(index) => render_data.meshes.instances[mesh_vec].get_instance_mut(index).change_visibility(shown),
+                None => {
+                    chunk.mesh_instance = Some(render_data.meshes.add_instance(MeshInstance::new(origin, Orientation::zero(), MeshID::Referenced(chunk.mesh_id.unwrap()), shown, false, false), mesh_vec))
+                }
+            }
+            chunk.mesh_updated = true;
+            chunk.has_translucent = meshed.translucent.is_some();
+            let translucent_vec = self.translucent_mesh_vec;
+            match meshed.translucent {
+                Some(translucent) => {
+                    if chunk.translucent_mesh_id.is_none() {
+                        match self.free_translucent_meshes.pop() {
+                            Some((id, instance)) => {
+                                chunk.translucent_mesh_id = Some(id);
+                                chunk.translucent_instance = Some(instance);
+                                *render_data.meshes.instances[translucent_vec].get_instance_mut(instance) = MeshInstance::new(origin, Orientation::zero(), MeshID::Referenced(id), true, false, false);
+                            },
+                            None => ()
                         }
                     }
-                    else {
-                        lods.push(MeshLODType::Mesh(Arc::new( self.get_lod_without_step(chunk, around))));
+                    let mesh = Mesh::new(MeshLODS::new(vec![translucent]), format!("Translucent chunk {} {} {} {}", pos.x, pos.y, pos.z, mesh_vec), size);
+                    match chunk.translucent_mesh_id {
+                        Some(id) => render_data.meshes.set_mesh(&MeshID::Referenced(id), mesh),
+                        None => chunk.translucent_mesh_id = Some(render_data.meshes.add_mesh(mesh))
                     }
-                    lods.reverse();
-                    
-                    let size = self.dims.chunk_length_f*SQRT_2;
-                    let height = self.dims.chunk_height_f;
-                    let mut chunk = self.get_chunk_at_mut(pos).unwrap();
-                    match chunk.mesh_id {
-                        Some(id) => {
-                            render_data.meshes.set_mesh(&MeshID::Referenced(id), Mesh::new(MeshLODS::new(lods), format!("Chunk {} {} {} {}", pos.x, pos.y, pos.z, mesh_vec), size).with_lod_factor(0.025));
-                        },
+                    match chunk.translucent_instance {
+                        Some(index) => render_data.meshes.instances[translucent_vec].get_instance_mut(index).change_visibility(shown),
                         None => {
-                            /*let mut local_cool = false;
-                            if lod.triangles.len() > test_chunk_tris {
-                                test_chunk_tris = lod.triangles.len();
-                                local_cool = true;
-                            }*/
-                            let id = render_data.meshes.add_mesh(Mesh::new(MeshLODS::new(lods), format!("Chunk {} {} {} {}", pos.x, pos.y, pos.z, mesh_vec), size).with_lod_factor(0.025));
-                            /*if local_cool {
-                                test_chunk_id = id;
-                            }*/
-                            
-                            chunk.mesh_id = Some(id);   
+                            chunk.translucent_instance = Some(render_data.meshes.add_instance(MeshInstance::new(origin, Orientation::zero(), MeshID::Referenced(chunk.translucent_mesh_id.unwrap()), shown, false, false), translucent_vec))
                         }
                     }
-                    match chunk.mesh_instance {
-                        Some(index) => render_data.meshes.instances[mesh_vec].get_instance_mut(index).change_visibility(true),
-                        None => {
-                            chunk.mesh_instance = Some(render_data.meshes.add_instance(MeshInstance::new(get_float_pos(pos) * height, Orientation::zero(), MeshID::Referenced(chunk.mesh_id.unwrap()), true, false, false), mesh_vec))
-                        }
+                },
+                None => match chunk.translucent_instance {
+                    Some(index) => render_data.meshes.instances[translucent_vec].get_instance_mut(index).change_visibility(false),
+                    None => ()
+                }
+            }
+            any_applied = true;
+        }
+        if any_applied {
+            render_data.meshes.change_buffer_size_for_instance_vec(mesh_vec, 1);
+            render_data.meshes.change_buffer_size_for_instance_vec(self.translucent_mesh_vec, 1);
+        }
+    }
+    /// Translucent faces are sorted when meshing, so the chunks right around the camera are remeshed whenever it changes chunk
+    fn resort_translucent_around(&mut self, camera_chunk:WorldChunkPos) {
+        if self.sort_camera_chunk == Some(camera_chunk) {
+            return
+        }
+        self.sort_camera_chunk = Some(camera_chunk);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    match self.get_chunk_at_mut(camera_chunk + Vec3D::new(dx, dy, dz)) {
+                        Some(chunk) => if chunk.has_translucent {
+                            chunk.mark_for_remesh();
+                            self.rendering_up_to_date = false;
+                        },
+                        None => ()
                     }
-                    chunk.mesh_updated = true;
                 }
             }
-            // render_data.meshes.add_instance(MeshInstance::new(Vec3Df::new(5.0, 5.0, 5.0), Orientation::zero(), MeshID::Referenced(test_chunk_id), true, false, true), 3);
-            render_data.meshes.change_buffer_size_for_instance_vec(self.mesh_vec, 1);
-            self.rendering_up_to_date = true;
-            self.forced_rerender = false;
-            // YAHOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOOo
         }
     }
+    pub fn get_mesher(&self) -> ChunkMesher<V> {
+        ChunkMesher { dims: self.dims.clone(), voxel_types: self.voxel_types.clone(), min_light_levels: self.min_light_levels, sky_brightness: self.sky_brightness, camera_pos: Vec3D::zero() }
+    }
+    pub fn is_meshing_pending(&self) -> bool {
+        self.pending_meshes.len() > 0
+    }
 }
 
 static TRIS_INDICES_UVS:LazyLock<([[Vec3Df ; 4] ; 6], [usize ; 6], [(f32,f32) ; 4])> = std::sync::LazyLock::new(make_cube_tris);
@@ -375,6 +574,29 @@ fn make_cube_tris() -> ([[Vec3Df ; 4] ; 6], [usize ; 6], [(f32,f32) ; 4]) {
 
 }
 
+/// A single voxel centered on the origin with the same faces as the map's meshes, for voxels shown outside of the map
+pub fn voxel_cube_mesh(texture:u32, light:(u8, u8, u8)) -> MeshLOD {
+    let (faces, indices, uvs) = &*TRIS_INDICES_UVS;
+    let (mut x, mut y, mut z) = (Vec::with_capacity(24), Vec::with_capacity(24), Vec::with_capacity(24));
+    let mut triangles = MeshTriangles::with_capacity(12);
+    for face in faces {
+        let start_index = x.len();
+        for point in face {
+            x.push(point.x);
+            y.push(point.y);
+            z.push(point.z);
+        }
+        for tri in 0..2 {
+            let point = |k:usize| {
+                let index = indices[tri * 3 + k];
+                TrianglePoint::new(index + start_index, uvs[index].0, uvs[index].1, light.0, light.1, light.2)
+            };
+            triangles.add_triangle(point(0), point(1), point(2), texture, 0);
+        }
+    }
+    MeshLOD::new(x, y, z, triangles)
+}
+
 #[derive(Clone, ToBytes, FromBytes)]
 pub struct ChunkDims {
     chunk_width:usize,
@@ -427,7 +649,26 @@ impl ChunkDims {
 
 impl<V:Voxel> MapChunk<V> {
     pub fn new(orig_worldpos:WorldVoxelPos, chunk_pos:WorldChunkPos, data:Vec<V>) -> Self {
-        Self { voxels:data, origin_worldpos:orig_worldpos, chunk_coord:chunk_pos, mesh_id: None, mesh_updated: false, mesh_instance: None }
+        let trap_voxels = data.iter().enumerate().filter(|(_, voxel)| {voxel.has_trap()}).map(|(i, _)| {i as u32}).collect();
+        Self { trap_voxels, voxels:ChunkStorage::new(data), origin_worldpos:orig_worldpos, chunk_coord:chunk_pos, mesh_id: None, mesh_updated: false, mesh_instance: None, modified_since_save:false, translucent_mesh_id:None, translucent_instance:None, has_translucent:false, repair_records:HashMap::new(), face_links:None }
+    }
+    pub fn reset_render_state(&mut self) {
+        self.mesh_id = None;
+        self.mesh_instance = None;
+        self.translucent_mesh_id = None;
+        self.translucent_instance = None;
+        self.has_translucent = false;
+        self.mesh_updated = false;
+        self.modified_since_save = false;
+    }
+    pub fn is_modified_since_save(&self) -> bool {
+        self.modified_since_save
+    }
+    pub fn mark_saved(&mut self) {
+        self.modified_since_save = false;
+    }
+    pub fn get_chunk_coord(&self) -> WorldChunkPos {
+        self.chunk_coord
     }
     pub fn get_at_local(&self, pos:WorldVoxelPos, dims:&ChunkDims) -> Option<&V> {
         if pos.in_origin_prism(dims.chunk_length_i, dims.chunk_width_i, dims.chunk_height_i) {
@@ -438,30 +679,35 @@ impl<V:Voxel> MapChunk<V> {
         }
     }
     fn get_voxel_data(&self, pos:WorldVoxelPos, dims:&ChunkDims) -> &V {
-        &self.voxels[pos.x as usize + (pos.y as usize * dims.chunk_length) + (pos.z as usize * dims.chunk_slice_area)]
+        self.voxels.get(pos.x as usize + (pos.y as usize * dims.chunk_length) + (pos.z as usize * dims.chunk_slice_area))
     }
     fn get_voxel_data_mut(&mut self, pos:WorldVoxelPos, dims:&ChunkDims) -> &mut V {
-        &mut self.voxels[pos.x as usize + (pos.y as usize * dims.chunk_length) + (pos.z as usize * dims.chunk_slice_area)]
+        self.voxels.get_mut(pos.x as usize + (pos.y as usize * dims.chunk_length) + (pos.z as usize * dims.chunk_slice_area))
     }
     fn get_at_worldpos(&self, pos:WorldVoxelPos, dims:&ChunkDims) -> Option<&V> {
         self.get_at_local(pos - self.origin_worldpos, dims)
     }
-    fn get_voxels_around<'a>(&'a self, local_pos:WorldVoxelPos, surrounding_chunks:[Option<&'a MapChunk<V>> ; 6], dims:&ChunkDims) -> [Option<&'a V> ; 6] { 
+    fn get_voxels_around<'a>(&'a self, local_pos:WorldVoxelPos, surrounding_chunks:&'a ChunkBorders<V>, dims:&ChunkDims) -> [Option<&'a V> ; 6] { 
         let mut voxels = [None ; 6];
         for (i, dir) in EXPLORATION.into_iter().enumerate() {
-            match self.get_at_local(local_pos + dir, dims) {
-                Some(voxel) => voxels[i] = Some(voxel),
-                None => match surrounding_chunks[i] {
-                    Some(chunk) => voxels[i] = chunk.get_at_local(local_pos + dir + dims.get_add_to_other_local(i), dims),
-                    None => ()
-                }
-            }
+            voxels[i] = self.get_at_local(local_pos + dir, dims).or_else(|| {surrounding_chunks.get_at_local(local_pos + dir, dims)});
         }
         voxels
     }
     fn mark_for_remesh(&mut self) {
         self.mesh_updated = false;
     }
+    /// Replaces the voxel at a world position through the chunk's palette, false if it isn't in the chunk
+    fn set_at_worldpos(&mut self, pos:WorldVoxelPos, dims:&ChunkDims, voxel:V) -> bool {
+        let local = pos - self.origin_worldpos;
+        if local.in_origin_prism(dims.chunk_length_i, dims.chunk_width_i, dims.chunk_height_i) {
+            self.voxels.set(local.x as usize + (local.y as usize * dims.chunk_length) + (local.z as usize * dims.chunk_slice_area), voxel);
+            true
+        }
+        else {
+            false
+        }
+    }
     fn get_at_worldpos_mut(&mut self, pos:WorldVoxelPos, dims:&ChunkDims) -> Option<&mut V> {
         self.get_at_local_mut(pos - self.origin_worldpos, dims)
     }
@@ -473,7 +719,7 @@ impl<V:Voxel> MapChunk<V> {
             None
         }
     }
-    fn get_empty_directions_local<'a>(&self, pos:WorldVoxelPos, dims:&ChunkDims, chunks_around:[Option<&'a MapChunk<V>> ; 6], voxel_types:&Vec<V::VT>) -> u8 {
+    fn get_empty_directions_local<'a>(&self, pos:WorldVoxelPos, dims:&ChunkDims, chunks_around:&'a ChunkBorders<V>, voxel_types:&Vec<V::VT>) -> u8 {
         let center = self.get_at_local(pos, dims);
         let voxels_around = self.get_voxels_around(pos, chunks_around, dims);
         match center {
@@ -481,7 +727,10 @@ impl<V:Voxel> MapChunk<V> {
                 let mut self_empty = voxel_types[voxel.voxel_id()].empties_with_orientation(voxel.orientation());
                 for (i, dir) in EXPLORATION.iter().enumerate() {
                     match voxels_around[i] {
-                        Some(vox_a) => self_empty |= ((voxel_types[vox_a.voxel_id()].empty_coming_from(i as u8, vox_a.orientation()) as u8) << i),
+                        Some(vox_a) => {
+                            let see_through = voxel_types[vox_a.voxel_id()].render_layer() != RenderLayer::Opaque && vox_a.voxel_id() != voxel.voxel_id();
+                            self_empty |= ((voxel_types[vox_a.voxel_id()].empty_coming_from(i as u8, vox_a.orientation()) || see_through) as u8) << i
+                        },
                         None => ()
                     } 
                 }
@@ -491,7 +740,7 @@ impl<V:Voxel> MapChunk<V> {
             None => EMPTY_VOXEL
         }
     }
-    pub fn get_lights_local<'a>(&self, pos:WorldVoxelPos, dims:&ChunkDims, chunks_around:[Option<&'a MapChunk<V>> ; 6]) -> [VoxelLight ; 6] {
+    pub fn get_lights_local<'a>(&self, pos:WorldVoxelPos, dims:&ChunkDims, chunks_around:&'a ChunkBorders<V>) -> [VoxelLight ; 6] {
         let center = self.get_at_local(pos, dims);
         let voxels_around = self.get_voxels_around(pos, chunks_around, dims);
         let mut lights = [VoxelLight::new(0, 0, 0, 0) ; 6];
@@ -508,6 +757,17 @@ impl<V:Voxel> MapChunk<V> {
         }
         lights
     }
+    pub fn get_sky_lights_local<'a>(&self, pos:WorldVoxelPos, dims:&ChunkDims, chunks_around:&'a ChunkBorders<V>) -> [u8 ; 6] {
+        let voxels_around = self.get_voxels_around(pos, chunks_around, dims);
+        let mut skies = [0 ; 6];
+        for i in 0..6 {
+            match voxels_around[i] {
+                Some(vox_a) => skies[i] = vox_a.sky_light(),
+                None => ()
+            }
+        }
+        skies
+    }
     
 }
 
@@ -522,7 +782,42 @@ pub struct GameMap<V:Voxel> {
     forced_rerender:bool,
     min_light_levels:(u8,u8,u8),
     remesh_fasttrack:Vec<WorldChunkPos>,
-    pub set_grid:SetGrid
+    released_meshes:Vec<(usize, usize)>,
+    free_meshes:Vec<(usize, usize)>,
+    /// instance vec of the translucent chunk meshes, has to be drawn after every opaque one
+    translucent_mesh_vec:usize,
+    released_translucent_meshes:Vec<(usize, usize)>,
+    free_translucent_meshes:Vec<(usize, usize)>,
+    sort_camera_chunk:Option<WorldChunkPos>,
+    light_sources:HashMap<WorldVoxelPos, VoxelLight>,
+    /// kept between edits so their queues don't get allocated again every time, taken out while they run
+    light_engine:LightEngine,
+    sky_light_engine:SkyLightEngine,
+    sky_brightness:u8,
+    /// chunks whose meshes have to be coloured again for the current sky brightness
+    sky_recolor_queue:Vec<WorldChunkPos>,
+    /// uncoloured meshes of the chunks skylight reaches, see sky_shading
+    shaded_meshes:HashMap<WorldChunkPos, ShadedChunk>,
+    pending_meshes:HashMap<WorldChunkPos, u64>,
+    next_mesh_generation:u64,
+    /// voxels around recent changes that world simulations (fluids...) should look at
+    awake_voxels:HashSet<WorldVoxelPos>,
+    detached_voxels:Vec<(WorldVoxelPos, V)>,
+    pub set_grid:SetGrid,
+    lod_factor:f32,
+    visibility_camera_chunk:Option<WorldChunkPos>,
+    /// chunks need to be walked again to know which ones the camera sees
+    visibility_stale:bool,
+    /// chunks the last walk reached, None if it showed everything
+    seen_chunks:Option<HashSet<WorldChunkPos>>,
+    /// loaded area the last walk stayed in
+    visibility_area:Option<(WorldChunkPos, WorldChunkPos)>,
+    /// reached chunks whose face links may have changed, with the links the last walk went through
+    links_to_check:HashMap<WorldChunkPos, u16>,
+    /// height of the chunk carrying the impostor of each column (see lod)
+    impostor_holders:HashMap<ColumnPos, i32>,
+    /// columns where a chunk changed since the last meshing jobs went out
+    stale_columns:HashSet<ColumnPos>,
 }
 
 pub type WorldChunkPos = Vec3D<i32>;
@@ -536,6 +831,11 @@ pub fn get_chunk_pos_i(dims:&ChunkDims, pos:Vec3D<i32>) -> WorldChunkPos {
     )
 }
 
+/// Component of `step` along a unit axis
+fn step_along(step:Vec3D<i32>, axis:Vec3D<i32>) -> i32 {
+    step.x * axis.x.abs() + step.y * axis.y.abs() + step.z * axis.z.abs()
+}
+
 fn multiply_corresponding_nonzero(mut scaler:Vec3Df, dir:Vec3Df) -> Vec3Df {
     if dir.x != 0.0 {
         scaler.x *= dir.x;
@@ -551,16 +851,48 @@ fn multiply_corresponding_nonzero(mut scaler:Vec3Df, dir:Vec3Df) -> Vec3Df {
     }
 }
 
-impl<V:Voxel> GameMap<V> {
-    pub fn new(expected_chunks:usize, dims:ChunkDims, voxel_types:Vec<V::VT>, min_light_levels:(u8,u8,u8), mesh_vec:usize) -> Self {
-        Self { chunks: HashMap::with_capacity(expected_chunks), dims, voxel_types, forced_rerender:false, min_light_levels, mesh_vec, rendering_up_to_date: false, remesh_fasttrack:Vec::with_capacity(16), set_grid:SetGrid::new(5.0, Vec3D::all_ones() * -15, Vec3D::all_ones() * 15) }
+/// Everything the meshing functions need from the GameMap, cheap enough to clone and hand to the meshing threads
+#[derive(Clone)]
+pub struct ChunkMesher<V:Voxel> {
+    dims:ChunkDims,
+    voxel_types:Vec<V::VT>,
+    min_light_levels:(u8,u8,u8),
+    sky_brightness:u8,
+    camera_pos:Vec3Df,
+}
+
+impl<V:Voxel> ChunkMesher<V> {
+    /// Every LOD of the chunk (see lod), most detailed last like MeshLODS wants them, and its translucent mesh if any, still to be coloured (see sky_shading)
+    pub fn mesh_chunk(&self, chunk:&MapChunk<V>, around:&ChunkBorders<V>, impostor:Option<&ColumnHeightfield<V>>) -> ShadedChunk {
+        let mut lods = Vec::new();
+        for step in self.dims.lod_steps() {
+            if step == Vec3D::all_ones() {
+                lods.push(self.get_lod_greedy(chunk, around));
+            }
+            else {
+                lods.push(self.get_lod_with_step(chunk, around, step));
+            }
+        }
+        lods.push(self.get_heightfield_lod(impostor));
+        lods.reverse();
+        let translucent = self.get_translucent_lod(chunk, around);
+        ShadedChunk { lods, translucent, radius: self.mesh_radius(impostor) }
+    }
+    pub fn get_sky_brightness(&self) -> u8 {
+        self.sky_brightness
     }
-    fn get_lod_without_step(&self, chunk:&MapChunk<V>, around:[Option<&MapChunk<V>> ; 6]) -> MeshLOD {
+    fn get_face_shade(&self, light:VoxelLight, sky:u8) -> VertexShade {
+        let level = (light.level.max(50) as f32) / 255.0;
+        let converted_collux = collux_u8_a_f32((light.r, light.g, light.b));
+        let block_collux = collux_f32_a_u8((converted_collux.0 * level, converted_collux.1 * level, converted_collux.2 * level));
+        VertexShade::new((block_collux.0.max(self.min_light_levels.0), block_collux.1.max(self.min_light_levels.1), block_collux.2.max(self.min_light_levels.2)), sky)
+    }
+    fn get_lod_without_step(&self, chunk:&MapChunk<V>, around:&ChunkBorders<V>) -> ShadedLOD {
         let mut x = Vec::with_capacity(600);
         let mut y = Vec::with_capacity(600);
         let mut z = Vec::with_capacity(600);
-        let mut triangles = MeshTriangles::with_capacity(600);
-        let mut lod = MeshLOD::new(x, y, z, triangles);
+        let mut triangles = ShadedTriangles::with_capacity(600);
+        let mut lod = ShadedLOD::new(x, y, z, triangles);
         for x in 0..self.dims.chunk_length_i {
             for y in 0..self.dims.chunk_width_i {
                 for z in 0..self.dims.chunk_height_i {
@@ -569,6 +901,7 @@ impl<V:Voxel> GameMap<V> {
                     if !&self.voxel_types[voxel.voxel_id()].is_completely_empty() {
                         let empty_dirs = chunk.get_empty_directions_local(Vec3D::new(x, y, z), &self.dims, around, &self.voxel_types);
                         let lights = chunk.get_lights_local(Vec3D::new(x, y, z), &self.dims, around);
+                        let skies = chunk.get_sky_lights_local(Vec3D::new(x, y, z), &self.dims, around);
                         //dbg!(Vec3D::new(x, y, z));
                         for (i, mask) in DIR_MASK.iter().enumerate() {
                             /*if empty_dirs != 0 {
@@ -579,67 +912,50 @@ impl<V:Voxel> GameMap<V> {
                                 let indices = TRIS_INDICES_UVS.1;
                                 let uvs = TRIS_INDICES_UVS.2;
                                 let start_index = lod.x.len();
-                                let mut level = (lights[i].level.max(50) as f32) / 255.0;
-                                let mut converted_collux = collux_u8_a_f32((lights[i].r, lights[i].g, lights[i].b));
-                                let mut finished_collux = collux_f32_a_u8((converted_collux.0 * level, converted_collux.1 * level, converted_collux.2 * level));
-                                finished_collux = (finished_collux.0.max(self.min_light_levels.0), finished_collux.1.max(self.min_light_levels.1), finished_collux.2.max(self.min_light_levels.2));
+                                let finished_shade = self.get_face_shade(lights[i], skies[i]);
                                 points = points + Vec3D::new(x as f32, y as f32, z as f32); 
                                 lod.add_points(&points);
                                 lod.triangles.add_triangle(
-                                    TrianglePoint::new(
+                                    ShadedPoint::new(
                                         indices[0] + start_index,
                                         uvs[indices[0]].0,
                                         uvs[indices[0]].1,
-                                        finished_collux.0,
-                                        finished_collux.1,
-                                        finished_collux.2
+                                        finished_shade
                                     ),
-                                    TrianglePoint::new(
+                                    ShadedPoint::new(
                                         indices[1] + start_index,
                                         uvs[indices[1]].0,
                                         uvs[indices[1]].1,
-                                        finished_collux.0,
-                                        finished_collux.1,
-                                        finished_collux.2
+                                        finished_shade
                                     ),
-                                    TrianglePoint::new(
+                                    ShadedPoint::new(
                                         indices[2] + start_index,
                                         uvs[indices[2]].0,
                                         uvs[indices[2]].1,
-                                        finished_collux.0,
-                                        finished_collux.1,
-                                        finished_collux.2
+                                        finished_shade
                                     ),
-                                    self.voxel_types[voxel.voxel_id()].easy_texture() as u32, 
-                                    0
+                                    self.get_face_texture(voxel, i)
                                 );
                                 lod.triangles.add_triangle(
-                                    TrianglePoint::new(
+                                    ShadedPoint::new(
                                         indices[3] + start_index,
                                         uvs[indices[3]].0,
                                         uvs[indices[3]].1,
-                                        finished_collux.0,
-                                        finished_collux.1,
-                                        finished_collux.2
+                                        finished_shade
                                     ),
-                                    TrianglePoint::new(
+                                    ShadedPoint::new(
                                         indices[4] + start_index,
                                         uvs[indices[4]].0,
                                         uvs[indices[4]].1,
-                                        finished_collux.0,
-                                        finished_collux.1,
-                                        finished_collux.2
+                                        finished_shade
                                     ),
-                                    TrianglePoint::new(
+                                    ShadedPoint::new(
                                         indices[5] + start_index,
                                         uvs[indices[5]].0,
                                         uvs[indices[5]].1,
-                                        finished_collux.0,
-                                        finished_collux.1,
-                                        finished_collux.2
+                                        finished_shade
                                     ),
-                                    self.voxel_types[voxel.voxel_id()].easy_texture() as u32, 
-                                    0
+                                    self.get_face_texture(voxel, i)
                                 );
                             }
                         }
@@ -655,22 +971,23 @@ impl<V:Voxel> GameMap<V> {
         (i,mask):(usize,u8),
         scaler:&Vec3Df,
         chunk:&MapChunk<V>,
-        around:[Option<&MapChunk<V>> ; 6],
-        step:i32,
+        around:&ChunkBorders<V>,
+        step:Vec3D<i32>,
         taken_dirs:&mut Vec<u8>
-    ) -> Option<(u32, (u8,u8,u8))> {
+    ) -> Option<(u32, VertexShade)> {
         
             let lights = chunk.get_lights_local(Vec3D::new(x, y, z), &self.dims, around);
+            let skies = chunk.get_sky_lights_local(Vec3D::new(x, y, z), &self.dims, around);
             let mut any_voxel_needs_face = false;
             let mut any_voxel_full = false;
             let mut full_texture = 0;
 
-            'outer : for dx in x..x+step {
-                for dy in y..y+step {
-                    for dz in z..z+step {
+            'outer : for dx in x..x+step.x {
+                for dy in y..y+step.y {
+                    for dz in z..z+step.z {
                         match chunk.get_at_local(Vec3D::new(dx, dy, dz), &self.dims){
-                            Some(voxel) => if !&self.voxel_types[voxel.voxel_id()].is_completely_empty() {
-                                full_texture = self.voxel_types[voxel.voxel_id()].easy_texture() as u32;
+                            Some(voxel) => if !&self.voxel_types[voxel.voxel_id()].is_completely_empty() && !self.is_translucent(voxel) {
+                                full_texture = self.get_face_texture(voxel, i);
                                 // assumes that all blocks with textures are full (currently all that's implemented anyway)
                                 any_voxel_full = true;
                                 break 'outer;
@@ -683,9 +1000,9 @@ impl<V:Voxel> GameMap<V> {
                 }
             }
             if any_voxel_full {
-                'outer : for dx in x..x+step {
-                    for dy in y..y+step {
-                        for dz in z..z+step {
+                'outer : for dx in x..x+step.x {
+                    for dy in y..y+step.y {
+                        for dz in z..z+step.z {
                             let empty_dirs = chunk.get_empty_directions_local(Vec3D::new(dx, dy, dz), &self.dims, around, &self.voxel_types);
                             if empty_dirs & mask  == mask { //if a direction is empty (hint:it's a me, Mario)
                                 any_voxel_needs_face = true;
@@ -698,17 +1015,14 @@ impl<V:Voxel> GameMap<V> {
 
             if any_voxel_needs_face {
                 let mut points = TRIS_INDICES_UVS.0[i];
-                let mut level = (lights[i].level.max(50) as f32) / 255.0;
-                let mut converted_collux = collux_u8_a_f32((lights[i].r, lights[i].g, lights[i].b));
-                let mut finished_collux = collux_f32_a_u8((converted_collux.0 * level, converted_collux.1 * level, converted_collux.2 * level));
-                finished_collux = (finished_collux.0.max(self.min_light_levels.0), finished_collux.1.max(self.min_light_levels.1), finished_collux.2.max(self.min_light_levels.2));
-                if step == 1 {
+                let finished_shade = self.get_face_shade(lights[i], skies[i]);
+                if step == Vec3D::all_ones() {
                     points = points + Vec3D::new(x as f32, y as f32, z as f32); 
                 }
                 else {
                     points = points * *scaler + (Vec3D::new(x as f32, y as f32, z as f32)) + ((Vec3Df::all_ones() * 0.5).component_product(&scaler) - Vec3Df::all_ones() * 0.5);
                 }
-                Some((full_texture, finished_collux))
+                Some((full_texture, finished_shade))
             }
             else {
                 None
@@ -716,11 +1030,11 @@ impl<V:Voxel> GameMap<V> {
         
     }
 
-    fn is_area_all_same_in_dir(&self, chunk:&MapChunk<V>, around:[Option<&MapChunk<V>> ; 6], step:i32, start:(i32,i32,i32), dir:usize, mask:u8, area:(i32, i32), scaler:&Vec3Df, taken_dirs:&mut Vec<u8>) -> Option<(u32, (u8,u8,u8))> {
+    fn is_area_all_same_in_dir(&self, chunk:&MapChunk<V>, around:&ChunkBorders<V>, step:Vec3D<i32>, start:(i32,i32,i32), dir:usize, mask:u8, area:(i32, i32), scaler:&Vec3Df, taken_dirs:&mut Vec<u8>) -> Option<(u32, VertexShade)> {
         
-        let (mut first_dir, mut second_dir) = PERPENDICULAR[dir].clone();
-        first_dir *= step;
-        second_dir *= step;
+        let (first_dir, second_dir) = PERPENDICULAR[dir].clone();
+        let first_dir = first_dir * step_along(step, first_dir);
+        let second_dir = second_dir * step_along(step, second_dir);
         let mut same = None;
         for first in 0..area.0 {
             for second in 0..area.1 {
@@ -743,19 +1057,18 @@ impl<V:Voxel> GameMap<V> {
         same
     }
     
-    fn get_lod_with_step(&self, chunk:&MapChunk<V>, around:[Option<&MapChunk<V>> ; 6], step:i32) -> MeshLOD {
+    fn get_lod_with_step(&self, chunk:&MapChunk<V>, around:&ChunkBorders<V>, step:Vec3D<i32>) -> ShadedLOD {
         let mut x = Vec::with_capacity(600);
         let mut y = Vec::with_capacity(600);
         let mut z = Vec::with_capacity(600);
-        let mut triangles = MeshTriangles::with_capacity(600);
-        let mut lod = MeshLOD::new(x, y, z, triangles);
-        let scaler = Vec3D::new(step as f32, step as f32, step as f32);
-        let float_step = step as f32;
+        let mut triangles = ShadedTriangles::with_capacity(600);
+        let mut lod = ShadedLOD::new(x, y, z, triangles);
+        let scaler = Vec3D::new(step.x as f32, step.y as f32, step.z as f32);
         let mut taken_dirs = vec![0 ; self.dims.chunk_height * self.dims.chunk_length * self.dims.chunk_width];
         println!("Started {} {} {}", chunk.chunk_coord.x, chunk.chunk_coord.y, chunk.chunk_coord.z);
-        for x in (0..self.dims.chunk_length_i).step_by(step as usize) {
-            for y in (0..self.dims.chunk_width_i).step_by(step as usize) {
-                for z in (0..self.dims.chunk_height_i).step_by(step as usize) {
+        for x in (0..self.dims.chunk_length_i).step_by(step.x as usize) {
+            for y in (0..self.dims.chunk_width_i).step_by(step.y as usize) {
+                for z in (0..self.dims.chunk_height_i).step_by(step.z as usize) {
 
                     //let voxels = chunk.get_voxels_around(Vec3D::new(x, y, z), around, &self.dims);
                     //let voxel = chunk.get_at_local(Vec3D::new(x, y, z), &self.dims).unwrap();
@@ -767,8 +1080,13 @@ impl<V:Voxel> GameMap<V> {
                             let mut first_dir_greed = 1;
                             let mut second_dir_greed = 1;
                             let mut face_data = self.is_area_all_same_in_dir(chunk, around, step, (x,y,z), i, *mask, (first_dir_greed, second_dir_greed), &scaler, &mut taken_dirs);
+                            let (first_axis, second_axis) = PERPENDICULAR[i].clone();
+                            let (first_step, second_step) = (step_along(step, first_axis), step_along(step, second_axis));
+                            // room left in the chunk from this position along both sides of the face
+                            let first_room = self.axis_length(first_axis) - step_along(Vec3D::new(x, y, z), first_axis);
+                            let second_room = self.axis_length(second_axis) - step_along(Vec3D::new(x, y, z), second_axis);
 
-                            while (first_dir_greed + 1) * step < self.dims.chunk_height_i && (second_dir_greed + 1) * step < self.dims.chunk_height_i && face_data.is_some() {
+                            while (first_dir_greed + 1) * first_step <= first_room && (second_dir_greed + 1) * second_step <= second_room && face_data.is_some() {
                                 
                                 //println!("{} {} {}", first_dir_greed, second_dir_greed, step);
                                 let mut first_test_face = self.is_area_all_same_in_dir(chunk, around, step, (x,y,z), i, *mask, (first_dir_greed + 1, second_dir_greed), &scaler, &mut taken_dirs);
@@ -785,11 +1103,11 @@ impl<V:Voxel> GameMap<V> {
                                 }
                             }
                             match face_data {
-                                Some((full_texture, finished_collux)) => {
-                                    let u_s = first_dir_greed as f32;
-                                    let v_s = second_dir_greed as f32;
+                                Some((full_texture, finished_shade)) => {
+                                    let u_s = first_dir_greed as f32 * first_step as f32;
+                                    let v_s = second_dir_greed as f32 * second_step as f32;
                                     let (mut f_dir, mut s_dir) = PERPENDICULAR[i].clone();
-                                    let scaler = multiply_corresponding_nonzero(multiply_corresponding_nonzero(scaler, Vec3D::new(f_dir.x as f32, f_dir.y as f32, f_dir.z as f32) * u_s), Vec3D::new(s_dir.x as f32, s_dir.y as f32, s_dir.z as f32) * v_s);
+                                    let scaler = multiply_corresponding_nonzero(multiply_corresponding_nonzero(scaler, Vec3D::new(f_dir.x as f32, f_dir.y as f32, f_dir.z as f32) * first_dir_greed as f32), Vec3D::new(s_dir.x as f32, s_dir.y as f32, s_dir.z as f32) * second_dir_greed as f32);
 
                                     let start_index = lod.x.len();
                                     let indices = TRIS_INDICES_UVS.1;
@@ -798,60 +1116,46 @@ impl<V:Voxel> GameMap<V> {
                                     points = points * scaler + (Vec3D::new(x as f32, y as f32, z as f32)) + ((Vec3Df::all_ones() * 0.5).component_product(&scaler) - Vec3Df::all_ones() * 0.5);
                                     lod.add_points(&points);
                                     lod.triangles.add_triangle(
-                                        TrianglePoint::new(
+                                        ShadedPoint::new(
                                             indices[0] + start_index,
-                                            uvs[indices[0]].0 * float_step * u_s,
-                                            uvs[indices[0]].1 * float_step * v_s,
-                                            finished_collux.0,
-                                            finished_collux.1,
-                                            finished_collux.2
+                                            uvs[indices[0]].0 * u_s,
+                                            uvs[indices[0]].1 * v_s,
+                                            finished_shade
                                         ),
-                                        TrianglePoint::new(
+                                        ShadedPoint::new(
                                             indices[1] + start_index,
-                                            uvs[indices[1]].0 * float_step * u_s,
-                                            uvs[indices[1]].1 * float_step * v_s,
-                                            finished_collux.0,
-                                            finished_collux.1,
-                                            finished_collux.2
+                                            uvs[indices[1]].0 * u_s,
+                                            uvs[indices[1]].1 * v_s,
+                                            finished_shade
                                         ),
-                                        TrianglePoint::new(
+                                        ShadedPoint::new(
                                             indices[2] + start_index,
-                                            uvs[indices[2]].0 * float_step * u_s,
-                                            uvs[indices[2]].1 * float_step * v_s,
-                                            finished_collux.0,
-                                            finished_collux.1,
-                                            finished_collux.2
+                                            uvs[indices[2]].0 * u_s,
+                                            uvs[indices[2]].1 * v_s,
+                                            finished_shade
                                         ),
-                                        full_texture, 
-                                        0
+                                        full_texture
                                     );
                                     lod.triangles.add_triangle(
-                                        TrianglePoint::new(
+                                        ShadedPoint::new(
                                             indices[3] + start_index,
-                                            uvs[indices[3]].0 * float_step * u_s,
-                                            uvs[indices[3]].1 * float_step * v_s,
-                                            finished_collux.0,
-                                            finished_collux.1,
-                                            finished_collux.2
+                                            uvs[indices[3]].0 * u_s,
+                                            uvs[indices[3]].1 * v_s,
+                                            finished_shade
                                         ),
-                                        TrianglePoint::new(
+                                        ShadedPoint::new(
                                             indices[4] + start_index,
-                                            uvs[indices[4]].0 * float_step * u_s,
-                                            uvs[indices[4]].1 * float_step * v_s,
-                                            finished_collux.0,
-                                            finished_collux.1,
-                                            finished_collux.2
+                                            uvs[indices[4]].0 * u_s,
+                                            uvs[indices[4]].1 * v_s,
+                                            finished_shade
                                         ),
-                                        TrianglePoint::new(
+                                        ShadedPoint::new(
                                             indices[5] + start_index,
-                                            uvs[indices[5]].0 * float_step * u_s,
-                                            uvs[indices[5]].1 * float_step * v_s,
-                                            finished_collux.0,
-                                            finished_collux.1,
-                                            finished_collux.2
+                                            uvs[indices[5]].0 * u_s,
+                                            uvs[indices[5]].1 * v_s,
+                                            finished_shade
                                         ),
-                                        full_texture, 
-                                        0
+                                        full_texture
                                     );
                                 },
                                 None => ()
@@ -863,9 +1167,305 @@ impl<V:Voxel> GameMap<V> {
         }
         lod
     }
+}
+
+impl<V:Voxel> GameMap<V> {
+    pub fn new(expected_chunks:usize, dims:ChunkDims, voxel_types:Vec<V::VT>, min_light_levels:(u8,u8,u8), mesh_vec:usize) -> Self {
+        Self { chunks: HashMap::with_capacity(expected_chunks), dims, voxel_types, forced_rerender:false, min_light_levels, mesh_vec, rendering_up_to_date: false, remesh_fasttrack:Vec::with_capacity(16), released_meshes:Vec::with_capacity(16), free_meshes:Vec::with_capacity(16), translucent_mesh_vec:mesh_vec + TRANSLUCENT_VEC_OFFSET, released_translucent_meshes:Vec::with_capacity(16), free_translucent_meshes:Vec::with_capacity(16), sort_camera_chunk:None, light_sources:HashMap::with_capacity(16), light_engine:LightEngine::new(), sky_light_engine:SkyLightEngine::new(), sky_brightness:255, sky_recolor_queue:Vec::with_capacity(64), shaded_meshes:HashMap::with_capacity(64), pending_meshes:HashMap::with_capacity(64), next_mesh_generation:0, awake_voxels:HashSet::with_capacity(256), detached_voxels:Vec::with_capacity(16), set_grid:SetGrid::new(ENTITY_CELL_SIZE, VEC_LENGTH, SET_CAPACITY), lod_factor:DEFAULT_LOD_FACTOR, visibility_camera_chunk:None, visibility_stale:true, seen_chunks:None, visibility_area:None, links_to_check:HashMap::with_capacity(16), impostor_holders:HashMap::with_capacity(64), stale_columns:HashSet::with_capacity(16) }
+    }
+    /// Brightness of a full skylight voxel, quantized so the meshes skylight reaches only get recoloured (a few per frame) when it visibly changes
+    pub fn set_sky_brightness(&mut self, brightness:u8) {
+        let quantized = if brightness >= 255 - SKY_BRIGHTNESS_STEP {255} else {(brightness / SKY_BRIGHTNESS_STEP) * SKY_BRIGHTNESS_STEP};
+        if quantized != self.sky_brightness {
+            self.sky_brightness = quantized;
+            self.sky_recolor_queue.clear();
+            self.sky_recolor_queue.extend(self.shaded_meshes.keys());
+            if self.sky_recolor_queue.len() > 0 {
+                self.rendering_up_to_date = false;
+            }
+        }
+    }
+    /// Sends a few of the chunks waiting for the new sky brightness to the meshing threads to get recoloured
+    fn recolor_sky_chunks(&mut self) {
+        let (mesh_vec, sky_brightness) = (self.mesh_vec, self.sky_brightness);
+        let mut sent = 0;
+        while sent < SKY_RECOLOR_BUDGET && let Some(pos) = self.sky_recolor_queue.pop() {
+            // a remesh on its way replaces the recolouring (and gets recoloured when it comes back if it has to)
+            if self.pending_meshes.contains_key(&pos) {
+                continue
+            }
+            // the job hands it back with its result
+            let shaded = match self.shaded_meshes.remove(&pos) {
+                Some(shaded) => shaded,
+                None => continue
+            };
+            let generation = self.next_mesh_generation;
+            self.next_mesh_generation += 1;
+            self.pending_meshes.insert(pos, generation);
+            MESHING_POOL.submit(mesh_vec, pos, 1, Box::new(move || {MeshedChunk::new(pos, mesh_vec, generation, shaded, sky_brightness)}));
+            sent += 1;
+        }
+    }
+    pub fn get_sky_brightness(&self) -> u8 {
+        self.sky_brightness
+    }
+    /// Full skylight computation, top chunks first so every chunk gets its input from the one above
+    pub fn compute_skylight(&mut self) {
+        let mut chunk_positions = self.get_all_chunk_pos();
+        chunk_positions.sort_by_key(|pos| {-pos.z});
+        let mut engine = std::mem::take(&mut self.sky_light_engine);
+        for chunk_pos in chunk_positions {
+            engine.light_chunk(self, chunk_pos);
+        }
+        engine.take_touched();
+        self.sky_light_engine = engine;
+    }
+    pub fn insert_generated_chunk(&mut self, chunk:MapChunk<V>) {
+        let chunk_pos = chunk.chunk_coord;
+        self.insert_chunk(chunk);
+        let mut sky_engine = std::mem::take(&mut self.sky_light_engine);
+        sky_engine.light_chunk(self, chunk_pos);
+        let mut touched = sky_engine.take_touched();
+        self.sky_light_engine = sky_engine;
+        let mut engine = std::mem::take(&mut self.light_engine);
+        engine.light_from_around(self, chunk_pos);
+        touched.extend(engine.take_touched());
+        self.light_engine = engine;
+        self.signal_remesh_around(&touched);
+        self.light_emissive_voxels(chunk_pos);
+    }
     pub fn does_chunk_exist(&self, chunk:WorldChunkPos) -> bool {
         self.chunks.contains_key(&chunk)
     }
+    pub fn get_dims(&self) -> &ChunkDims {
+        &self.dims
+    }
+    fn signal_chunk_and_neighbours_remesh(&mut self, chunk_pos:WorldChunkPos) {
+        self.signal_column_changed(chunk_pos);
+        let mut must_re_render = false;
+        // meshes look at the chunks past their edges and corners too
+        for pos in offsets_around().map(|offset| {chunk_pos + offset}).chain([chunk_pos]) {
+            match self.get_chunk_at_mut(pos) {
+                Some(chunk) => {chunk.mark_for_remesh(); self.remesh_fasttrack.push(pos); must_re_render = true;},
+                None => ()
+            }
+        }
+        if must_re_render {
+            self.rendering_up_to_date = false;
+        }
+    }
+    pub fn insert_chunk(&mut self, mut chunk:MapChunk<V>) {
+        chunk.reset_render_state();
+        let chunk_pos = chunk.chunk_coord;
+        match self.chunks.insert(chunk_pos, chunk) {
+            Some(old_chunk) => {
+                self.release_chunk_meshes(&old_chunk);
+                self.signal_chunk_loaded(chunk_pos, Some(old_chunk.face_links));
+            },
+            None => self.signal_chunk_loaded(chunk_pos, None)
+        }
+        self.signal_chunk_and_neighbours_remesh(chunk_pos);
+    }
+    /// Gives back the meshes of a chunk that left the map (or got replaced) and forgets the mesh it was waiting for
+    fn release_chunk_meshes(&mut self, chunk:&MapChunk<V>) {
+        match (chunk.mesh_id, chunk.mesh_instance) {
+            (Some(id), Some(instance)) => {self.released_meshes.push((id, instance)); self.rendering_up_to_date = false;},
+            _ => ()
+        }
+        match (chunk.translucent_mesh_id, chunk.translucent_instance) {
+            (Some(id), Some(instance)) => self.released_translucent_meshes.push((id, instance)),
+            _ => ()
+        }
+        self.pending_meshes.remove(&chunk.chunk_coord);
+        self.shaded_meshes.remove(&chunk.chunk_coord);
+    }
+    pub fn remove_chunk(&mut self, chunk_pos:WorldChunkPos) -> Option<MapChunk<V>> {
+        match self.chunks.remove(&chunk_pos) {
+            Some(chunk) => {
+                self.release_chunk_meshes(&chunk);
+                self.signal_chunk_unloaded(chunk_pos, chunk.face_links);
+                self.signal_chunk_and_neighbours_remesh(chunk_pos);
+                Some(chunk)
+            },
+            None => None
+        }
+    }
+    /// Replaces the voxel at `pos`, keeps the lighting consistent and only remeshes the chunks whose faces could have changed,
+    /// then lets whatever isn't supported anymore fall
+    pub fn set_voxel_at(&mut self, pos:WorldVoxelPos, new_voxel:V) -> Option<V> {
+        let old_emission = self.emission_of(self.get_voxel_at(pos)?);
+        let new_emission = self.emission_of(&new_voxel);
+        // a broken lamp stops being a light source first, so replace_voxel_at doesn't light it up again just to darken it right after
+        if new_emission.is_none() {
+            self.update_emission_at(pos, old_emission, None);
+        }
+        let old_voxel = self.replace_voxel_at(pos, new_voxel)?;
+        if new_emission.is_some() {
+            self.update_emission_at(pos, old_emission, new_emission);
+        }
+        self.settle_around(pos);
+        Some(old_voxel)
+    }
+    fn replace_voxel_at(&mut self, pos:WorldVoxelPos, mut new_voxel:V) -> Option<V> {
+        let old_voxel = self.get_voxel_at(pos)?.clone();
+        self.update_trap_index(pos, new_voxel.has_trap());
+        let old_passthrough = self.voxel_types[old_voxel.voxel_id()].light_passthrough();
+        let new_passthrough = self.voxel_types[new_voxel.voxel_id()].light_passthrough();
+        // the light the new voxel carries was spread somewhere else (or generated, or saved before), only light sources light it up :
+        // it's written with the light that was there so the engines remove exactly that
+        new_voxel.set_light_level(old_voxel.light_level());
+        new_voxel.set_sky_light(old_voxel.sky_light());
+        let sight_changed = self.changes_face_links(&old_voxel, &new_voxel);
+        self.modify_voxel_at(pos, |voxel| {*voxel = new_voxel});
+        self.mark_modified_at(pos);
+        if sight_changed {
+            self.signal_face_links_changed(pos);
+        }
+        let mut engine = std::mem::take(&mut self.light_engine);
+        if new_passthrough.level < old_passthrough.level || (new_passthrough.level == old_passthrough.level && new_passthrough != old_passthrough) {
+            engine.darken(self, pos);
+        }
+        if new_passthrough.level > old_passthrough.level {
+            engine.relight_from_neighbours(self, pos);
+        }
+        match self.light_sources.get(&pos).copied() {
+            Some(source) => engine.light_up(self, pos, source),
+            None => ()
+        }
+        let mut sky_engine = std::mem::take(&mut self.sky_light_engine);
+        if new_passthrough.level == 0 && old_passthrough.level > 0 {
+            sky_engine.darken(self, pos);
+        }
+        else if new_passthrough.level > 0 && old_passthrough.level == 0 {
+            sky_engine.relight_from_neighbours(self, pos);
+        }
+        let mut touched = engine.take_touched();
+        touched.extend(sky_engine.take_touched());
+        touched.insert(pos);
+        self.light_engine = engine;
+        self.sky_light_engine = sky_engine;
+        self.wake_around(pos);
+        self.signal_remesh_around(&touched);
+        Some(old_voxel)
+    }
+    pub fn update_voxel_data_at(&mut self, pos:WorldVoxelPos, mut new_voxel:V) {
+        let old_voxel = match self.get_voxel_at(pos) {
+            Some(voxel) => voxel.clone(),
+            None => return
+        };
+        if old_voxel.voxel_id() != new_voxel.voxel_id() || old_voxel.orientation() != new_voxel.orientation() {
+            self.set_voxel_at(pos, new_voxel);
+            return
+        }
+        self.update_trap_index(pos, new_voxel.has_trap());
+        new_voxel.set_light_level(old_voxel.light_level());
+        new_voxel.set_sky_light(old_voxel.sky_light());
+        let (old_emission, new_emission) = (self.emission_of(&old_voxel), self.emission_of(&new_voxel));
+        self.modify_voxel_at(pos, |voxel| {*voxel = new_voxel});
+        self.mark_modified_at(pos);
+        self.update_emission_at(pos, old_emission, new_emission);
+    }
+    fn update_trap_index(&mut self, pos:WorldVoxelPos, has_trap:bool) {
+        let dims = self.dims.clone();
+        match self.get_chunk_at_mut(self.get_chunk_pos_i(pos)) {
+            Some(chunk) => {
+                let local = pos - chunk.origin_worldpos;
+                let index = (local.x as usize + (local.y as usize * dims.chunk_length) + (local.z as usize * dims.chunk_slice_area)) as u32;
+                if has_trap && !chunk.trap_voxels.contains(&index) {
+                    chunk.trap_voxels.push(index);
+                }
+                else if !has_trap {
+                    chunk.trap_voxels.retain(|trap| {*trap != index});
+                }
+            },
+            None => ()
+        }
+    }
+    /// World positions of the trap voxels of a loaded chunk
+    pub fn get_traps_in_chunk(&self, chunk_pos:WorldChunkPos) -> Vec<WorldVoxelPos> {
+        match self.chunks.get(&chunk_pos) {
+            Some(chunk) => chunk.trap_voxels.iter().map(|index| {
+                let index = *index as usize;
+                chunk.origin_worldpos + Vec3D::new((index % self.dims.chunk_length) as i32, ((index / self.dims.chunk_length) % self.dims.chunk_width) as i32, (index / self.dims.chunk_slice_area) as i32)
+            }).collect(),
+            None => Vec::new()
+        }
+    }
+    pub fn get_chunks_with_traps(&self) -> Vec<WorldChunkPos> {
+        self.chunks.iter().filter(|(_, chunk)| {chunk.trap_voxels.len() > 0}).map(|(pos, _)| {*pos}).collect()
+    }
+    pub fn set_voxels_batch(&mut self, changes:Vec<(WorldVoxelPos, V)>) {
+        for (pos, voxel) in changes {
+            self.set_voxel_at(pos, voxel);
+        }
+    }
+    pub fn wake_around(&mut self, pos:WorldVoxelPos) {
+        self.awake_voxels.extend([pos, pos + EXPLORATION[0], pos + EXPLORATION[1], pos + EXPLORATION[2], pos + EXPLORATION[3], pos + EXPLORATION[4], pos + EXPLORATION[5]]);
+    }
+    pub fn sleep_voxels(&mut self, positions:&Vec<WorldVoxelPos>) {
+        for pos in positions {
+            self.awake_voxels.remove(pos);
+        }
+    }
+    /// At most `max` of the awake voxels, in no particular order
+    pub fn get_awake_voxels(&self, max:usize) -> Vec<WorldVoxelPos> {
+        self.awake_voxels.iter().take(max).copied().collect()
+    }
+    pub fn add_light_source(&mut self, pos:WorldVoxelPos, light:VoxelLight) {
+        if !self.does_chunk_exist(self.get_chunk_pos_i(pos)) {
+            return
+        }
+        let mut engine = std::mem::take(&mut self.light_engine);
+        match self.light_sources.insert(pos, light) {
+            Some(_) => engine.darken(self, pos),
+            None => ()
+        }
+        engine.light_up(self, pos, light);
+        let mut touched = engine.take_touched();
+        self.light_engine = engine;
+        touched.insert(pos);
+        self.signal_remesh_around(&touched);
+    }
+    pub fn remove_light_source(&mut self, pos:WorldVoxelPos) -> Option<VoxelLight> {
+        let removed = self.light_sources.remove(&pos)?;
+        let mut engine = std::mem::take(&mut self.light_engine);
+        engine.darken(self, pos);
+        let mut touched = engine.take_touched();
+        self.light_engine = engine;
+        touched.insert(pos);
+        self.signal_remesh_around(&touched);
+        Some(removed)
+    }
+    pub fn get_light_sources(&self) -> &HashMap<WorldVoxelPos, VoxelLight> {
+        &self.light_sources
+    }
+    /// Marks every chunk holding one of `positions` or next to one of them (diagonally too) for remeshing, each chunk only once
+    pub fn signal_remesh_around(&mut self, positions:&HashSet<WorldVoxelPos>) {
+        let mut chunks_to_remesh = HashSet::with_capacity(8);
+        for pos in positions {
+            self.signal_column_changed(self.get_chunk_pos_i(*pos));
+            chunks_to_remesh.insert(self.get_chunk_pos_i(*pos));
+            for offset in offsets_around() {
+                chunks_to_remesh.insert(self.get_chunk_pos_i(*pos + offset));
+            }
+        }
+        for chunk_pos in chunks_to_remesh {
+            match self.get_chunk_at_mut(chunk_pos) {
+                Some(chunk) => {
+                    chunk.mark_for_remesh();
+                    self.remesh_fasttrack.push(chunk_pos);
+                    self.rendering_up_to_date = false;
+                },
+                None => ()
+            }
+        }
+    }
+    pub fn mark_chunk_saved(&mut self, chunk_pos:WorldChunkPos) {
+        match self.get_chunk_at_mut(chunk_pos) {
+            Some(chunk) => chunk.mark_saved(),
+            None => ()
+        }
+    }
     pub fn set_min_light_levels(&mut self, mins:(u8,u8,u8)) {
         self.min_light_levels = mins;
     }
@@ -874,6 +1474,7 @@ impl<V:Voxel> GameMap<V> {
         self.forced_rerender = true;
     }
     pub fn modified_this_pos_signal_remesh(&mut self, pos:WorldVoxelPos) {
+        self.signal_face_links_changed(pos);
         let mut must_re_render = false;
         let dims = self.dims.clone();
         let mut add_to_fasttrack = Vec::with_capacity(7);
@@ -887,6 +1488,11 @@ impl<V:Voxel> GameMap<V> {
         self.rendering_up_to_date = !must_re_render;
     }
     pub fn generate_chunk<F:FnMut(Vec3D<i32>) -> V>(&mut self, chunk_pos:WorldChunkPos, func:&mut F) {
+        let chunk = self.build_chunk(chunk_pos, func);
+        self.chunks.insert(chunk_pos, chunk);
+        self.signal_column_changed(chunk_pos);
+    }
+    pub fn build_chunk<F:FnMut(Vec3D<i32>) -> V>(&self, chunk_pos:WorldChunkPos, func:&mut F) -> MapChunk<V> {
         let mut chunk_data = Vec::with_capacity(self.dims.chunk_slice_area * self.dims.chunk_height);
         let mut orig_worldpos = Vec3D::new(chunk_pos.x * self.dims.chunk_length_i, chunk_pos.y * self.dims.chunk_width_i, chunk_pos.z * self.dims.chunk_height_i);
         for z in orig_worldpos.z..orig_worldpos.z + self.dims.chunk_height_i {
@@ -896,13 +1502,29 @@ impl<V:Voxel> GameMap<V> {
                 }
             }
         }
-        self.chunks.insert(chunk_pos, MapChunk::new(orig_worldpos, chunk_pos, chunk_data));
+        MapChunk::new(orig_worldpos, chunk_pos, chunk_data)
+    }
+    pub fn build_chunk_batched<F:FnMut(WorldVoxelPos, Vec3D<i32>) -> Vec<V>>(&self, chunk_pos:WorldChunkPos, func:&mut F) -> MapChunk<V> {
+        let orig_worldpos = Vec3D::new(chunk_pos.x * self.dims.chunk_length_i, chunk_pos.y * self.dims.chunk_width_i, chunk_pos.z * self.dims.chunk_height_i);
+        let chunk_data = func(orig_worldpos, Vec3D::new(self.dims.chunk_length_i, self.dims.chunk_width_i, self.dims.chunk_height_i));
+        assert_eq!(chunk_data.len(), self.dims.chunk_slice_area * self.dims.chunk_height);
+        MapChunk::new(orig_worldpos, chunk_pos, chunk_data)
+    }
+    pub fn generate_chunks_batched<F:FnMut(WorldVoxelPos, Vec3D<i32>) -> Vec<V>>(&mut self, start:WorldChunkPos, end:WorldChunkPos, func:&mut F) {
+        for xc in start.x..end.x {
+            for yc in start.y..end.y {
+                for zc in start.z..end.z {
+                    let chunk = self.build_chunk_batched(Vec3D::new(xc, yc, zc), func);
+                    self.chunks.insert(Vec3D::new(xc, yc, zc), chunk);
+                    self.signal_column_changed(Vec3D::new(xc, yc, zc));
+                }
+            }
+        }
     }
     pub fn get_all_chunk_pos(&self) -> Vec<Vec3D<i32>> {
-        self.chunks.clone().keys().map(|vector| {vector.clone()}).collect::<Vec<Vec3D<i32>>>()
+        self.chunks.keys().map(|vector| {vector.clone()}).collect::<Vec<Vec3D<i32>>>()
     }
     pub fn generate_chunks<F:FnMut(Vec3D<i32>) -> V>(&mut self, start:WorldChunkPos, end:WorldChunkPos, func:&mut F) {
-        self.set_grid = SetGrid::new(4.0, start * self.dims.chunk_length_i, end * self.dims.chunk_length_i);
         for xc in start.x..end.x {
             for yc in start.y..end.y {
                 for zc in start.z..end.z {
@@ -913,13 +1535,17 @@ impl<V:Voxel> GameMap<V> {
     }
     pub fn make_meshes_invisible<'a>(&mut self, write: &mut VectorinatorWrite<'a>) {
         write.meshes.change_visibility_of_all_instances_of_vec(self.mesh_vec, false);
+        write.meshes.change_visibility_of_all_instances_of_vec(self.translucent_mesh_vec, false);
     }
     pub fn change_mesh_vec(&mut self, new_vec:usize) {
         self.mesh_vec = new_vec;
+        self.translucent_mesh_vec = new_vec + TRANSLUCENT_VEC_OFFSET;
     }
 
     pub fn make_meshes_visible<'a>(&mut self, write: &mut VectorinatorWrite<'a>) {
         write.meshes.change_visibility_of_all_instances_of_vec(self.mesh_vec, true);
+        write.meshes.change_visibility_of_all_instances_of_vec(self.translucent_mesh_vec, true);
+        self.visibility_stale = true;
     }
     pub fn get_chunk_at(&self, chunk_pos:WorldChunkPos) -> Option<&MapChunk<V>> {
         self.chunks.get(&chunk_pos)
@@ -947,6 +1573,10 @@ impl<V:Voxel> GameMap<V> {
             (pos.z/self.dims.chunk_height_f - 1.0).trunc() as i32,
         )
     }
+    /// First voxel of the chunk
+    pub fn get_chunk_origin(&self, chunk_pos:WorldChunkPos) -> WorldVoxelPos {
+        Vec3D::new(chunk_pos.x * self.dims.chunk_length_i, chunk_pos.y * self.dims.chunk_width_i, chunk_pos.z * self.dims.chunk_height_i)
+    }
     pub fn get_chunk_pos_i(&self, pos:Vec3D<i32>) -> WorldChunkPos {
         Vec3D::new(
             (pos.x.div_floor(self.dims.chunk_length_i)),
@@ -964,6 +1594,24 @@ impl<V:Voxel> GameMap<V> {
     pub fn is_voxel_solid(&self, voxel:WorldVoxelPos) -> bool {
         self.get_voxel_at(voxel).is_some_and(|voxel| {!self.get_voxel_types()[voxel.voxel_id()].is_completely_empty()})
     }
+    /// Changes the voxel at `pos` without unpacking its chunk (see chunk_storage), false if it isn't loaded
+    pub fn modify_voxel_at<F:FnOnce(&mut V)>(&mut self, pos:WorldVoxelPos, f:F) -> bool {
+        let mut voxel = match self.get_voxel_at(pos) {
+            Some(voxel) => voxel.clone(),
+            None => return false
+        };
+        f(&mut voxel);
+        let dims = self.dims.clone();
+        self.get_chunk_at_mut(self.get_chunk_pos_i(pos)).is_some_and(|chunk| {chunk.set_at_worldpos(pos, &dims, voxel)})
+    }
+    /// The chunk holding `pos` gets saved again, only set_voxel_at and update_voxel_data_at mark chunks (light spreading alone doesn't)
+    fn mark_modified_at(&mut self, pos:WorldVoxelPos) {
+        match self.get_chunk_at_mut(self.get_chunk_pos_i(pos)) {
+            Some(chunk) => chunk.modified_since_save = true,
+            None => ()
+        }
+    }
+    /// Unpacks the voxel's chunk, modify_voxel_at doesn't
     pub fn get_voxel_at_mut(&mut self, voxel:WorldVoxelPos) -> Option<&mut V> {
         let dims = self.dims.clone();
         self.get_chunk_at_mut(self.get_chunk_pos_i(voxel)).and_then(|chunk| {chunk.get_at_worldpos_mut(voxel, &dims)})
@@ -1000,6 +1648,11 @@ pub fn get_voxel_pos(pos:Vec3Df) -> WorldVoxelPos {
     )
 }
 
+/// Middle of the voxel get_voxel_pos puts a point in
+pub fn get_voxel_center(pos:WorldVoxelPos) -> Vec3Df {
+    get_float_pos(pos) + Vec3Df::all_ones() * 0.5
+}
+
 pub fn get_float_pos(pos:WorldVoxelPos) -> Vec3Df {
     Vec3D::new(
         pos.x as f32,
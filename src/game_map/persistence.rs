@@ -0,0 +1,421 @@
+use std::{collections::HashMap, fs::{self, File, OpenOptions}, io::{self, Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}};
+
+use hord3::horde::geometry::vec3d::Vec3D;
+use to_from_bytes::{FromBytes, ToBytes};
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use super::{spatial_hash::SetGrid, ChunkDims, GameMap, MapChunk, Voxel, VoxelLight, WorldChunkPos, WorldVoxelPos};
+
+// A region is a REGION_SIDE^3 group of chunks stored in a single file :
+// - a fixed size header (magic, version, region coords, chunk dims)
+// - an offset table with one (offset, length) pair per chunk slot, offset 0 meaning "not saved"
+// - the chunk blobs themselves, in whatever order they were written
+// rewriting a chunk reuses its old slot if the new blob fits, otherwise it is appended at the end of the file
+// Everything that isn't a chunk (chunk dims, min light levels, light sources, the entity grid and the LOD factor) goes in the world's meta file.
+// Chunk blobs and the meta are sealed behind their length and a checksum (see seal_blob), checked before decoding since from_bytes trusts
+// its input and panics on anything truncated or corrupted, a bad file is InvalidData instead.
+
+pub const REGION_SIDE:i32 = 4;
+pub const CHUNKS_PER_REGION:usize = (REGION_SIDE * REGION_SIDE * REGION_SIDE) as usize;
+
+const REGION_MAGIC:[u8 ; 4] = *b"HRGN";
+const META_MAGIC:[u8 ; 4] = *b"HMTA";
+const FORMAT_VERSION:u16 = 13;
+const META_FILE_NAME:&str = "world.hmeta";
+const REGION_EXTENSION:&str = "hregion";
+
+// magic + version + region coords + chunk dims
+const HEADER_SIZE:u64 = 4 + 2 + 3 * 4 + 3 * 4;
+const TABLE_ENTRY_SIZE:u64 = 8;
+const DATA_START:u64 = HEADER_SIZE + TABLE_ENTRY_SIZE * CHUNKS_PER_REGION as u64;
+
+// payload length + checksum
+const BLOB_HEADER_SIZE:usize = 4 + 8;
+
+pub type WorldRegionPos = Vec3D<i32>;
+
+/// FNV-1a
+fn checksum(bytes:&[u8]) -> u64 {
+    bytes.iter().fold(0xCBF29CE484222325, |hash, byte| {(hash ^ *byte as u64).wrapping_mul(0x100000001B3)})
+}
+
+/// `payload` behind its length and checksum, read back with decode_blob
+pub fn seal_blob(payload:Vec<u8>) -> Vec<u8> {
+    let mut blob = Vec::with_capacity(BLOB_HEADER_SIZE + payload.len());
+    blob.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    blob.extend_from_slice(&checksum(&payload).to_le_bytes());
+    blob.extend(payload);
+    blob
+}
+
+/// Decodes a blob made by seal_blob, InvalidData if it was truncated or corrupted
+pub fn decode_blob<T:FromBytes>(blob:&[u8]) -> io::Result<T> {
+    let invalid = |what:&str| {io::Error::new(io::ErrorKind::InvalidData, what.to_string())};
+    if blob.len() < BLOB_HEADER_SIZE {
+        return Err(invalid("truncated blob header"))
+    }
+    let length = u32::from_le_bytes(blob[0..4].try_into().unwrap()) as usize;
+    let stored_checksum = u64::from_le_bytes(blob[4..12].try_into().unwrap());
+    let payload = &blob[BLOB_HEADER_SIZE..];
+    if payload.len() < length {
+        return Err(invalid("truncated blob"))
+    }
+    if checksum(&payload[..length]) != stored_checksum {
+        return Err(invalid("corrupted blob"))
+    }
+    Ok(T::from_bytes(&mut payload[..length].iter().copied()))
+}
+
+#[derive(Clone, ToBytes, FromBytes)]
+struct WorldMeta {
+    dims:ChunkDims,
+    min_light_levels:(u8,u8,u8),
+    light_sources:HashMap<WorldVoxelPos, VoxelLight>,
+    set_grid:SetGrid,
+    lod_factor:f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ChunkSlot {
+    offset:u32,
+    length:u32,
+}
+
+impl ChunkSlot {
+    fn empty() -> Self {
+        Self { offset: 0, length: 0 }
+    }
+    fn is_empty(&self) -> bool {
+        self.offset == 0
+    }
+}
+
+struct RegionFile {
+    file:File,
+    table:[ChunkSlot ; CHUNKS_PER_REGION],
+}
+
+impl RegionFile {
+    fn create(path:&Path, region:WorldRegionPos, dims:&ChunkDims) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        let mut header = Vec::with_capacity(DATA_START as usize);
+        header.extend_from_slice(&REGION_MAGIC);
+        header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        for coord in [region.x, region.y, region.z] {
+            header.extend_from_slice(&coord.to_le_bytes());
+        }
+        for side in [dims.chunk_length, dims.chunk_width, dims.chunk_height] {
+            header.extend_from_slice(&(side as u32).to_le_bytes());
+        }
+        header.resize(DATA_START as usize, 0);
+        file.write_all(&header)?;
+        Ok(Self { file, table: [ChunkSlot::empty() ; CHUNKS_PER_REGION] })
+    }
+    fn open(path:&Path, region:WorldRegionPos, dims:&ChunkDims) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut header = [0 ; HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        if header[0..4] != REGION_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a region file", path.display())))
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != FORMAT_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported region version {} in {}", version, path.display())))
+        }
+        let read_u32 = |at:usize| {u32::from_le_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]])};
+        let stored_region = Vec3D::new(read_u32(6) as i32, read_u32(10) as i32, read_u32(14) as i32);
+        if stored_region != region {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} holds region {} {} {}, expected {} {} {}", path.display(), stored_region.x, stored_region.y, stored_region.z, region.x, region.y, region.z)))
+        }
+        if (read_u32(18) as usize, read_u32(22) as usize, read_u32(26) as usize) != (dims.chunk_length, dims.chunk_width, dims.chunk_height) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} was saved with different chunk dimensions", path.display())))
+        }
+        let mut raw_table = vec![0 ; (TABLE_ENTRY_SIZE as usize) * CHUNKS_PER_REGION];
+        file.read_exact(&mut raw_table)?;
+        let mut table = [ChunkSlot::empty() ; CHUNKS_PER_REGION];
+        for (i, slot) in table.iter_mut().enumerate() {
+            let at = i * TABLE_ENTRY_SIZE as usize;
+            slot.offset = u32::from_le_bytes([raw_table[at], raw_table[at + 1], raw_table[at + 2], raw_table[at + 3]]);
+            slot.length = u32::from_le_bytes([raw_table[at + 4], raw_table[at + 5], raw_table[at + 6], raw_table[at + 7]]);
+        }
+        Ok(Self { file, table })
+    }
+    fn open_or_create(path:&Path, region:WorldRegionPos, dims:&ChunkDims) -> io::Result<Self> {
+        if path.exists() {
+            Self::open(path, region, dims)
+        }
+        else {
+            Self::create(path, region, dims)
+        }
+    }
+    fn read_slot(&mut self, slot_index:usize) -> io::Result<Option<Vec<u8>>> {
+        let slot = self.table[slot_index];
+        if slot.is_empty() {
+            return Ok(None)
+        }
+        if (slot.offset as u64) < DATA_START || slot.offset as u64 + slot.length as u64 > self.file.metadata()?.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("chunk slot {} points outside of its region file", slot_index)))
+        }
+        let mut data = vec![0 ; slot.length as usize];
+        self.file.seek(SeekFrom::Start(slot.offset as u64))?;
+        self.file.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+    fn write_slot(&mut self, slot_index:usize, data:&[u8]) -> io::Result<()> {
+        let old_slot = self.table[slot_index];
+        let offset = if !old_slot.is_empty() && data.len() as u32 <= old_slot.length {
+            old_slot.offset as u64
+        }
+        else {
+            self.file.seek(SeekFrom::End(0))?.max(DATA_START)
+        };
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(data)?;
+        let new_slot = ChunkSlot { offset: offset as u32, length: data.len() as u32 };
+        self.table[slot_index] = new_slot;
+        let mut entry = [0 ; TABLE_ENTRY_SIZE as usize];
+        entry[0..4].copy_from_slice(&new_slot.offset.to_le_bytes());
+        entry[4..8].copy_from_slice(&new_slot.length.to_le_bytes());
+        self.file.seek(SeekFrom::Start(HEADER_SIZE + slot_index as u64 * TABLE_ENTRY_SIZE))?;
+        self.file.write_all(&entry)?;
+        Ok(())
+    }
+    fn saved_slots(&self) -> Vec<usize> {
+        (0..CHUNKS_PER_REGION).filter(|i| {!self.table[*i].is_empty()}).collect()
+    }
+}
+
+pub fn get_region_pos(chunk_pos:WorldChunkPos) -> WorldRegionPos {
+    Vec3D::new(
+        chunk_pos.x.div_floor(REGION_SIDE),
+        chunk_pos.y.div_floor(REGION_SIDE),
+        chunk_pos.z.div_floor(REGION_SIDE),
+    )
+}
+
+fn get_slot_index(chunk_pos:WorldChunkPos) -> usize {
+    let local = chunk_pos - get_region_pos(chunk_pos) * REGION_SIDE;
+    (local.x + local.y * REGION_SIDE + local.z * REGION_SIDE * REGION_SIDE) as usize
+}
+
+fn get_chunk_pos_from_slot(region:WorldRegionPos, slot_index:usize) -> WorldChunkPos {
+    let slot = slot_index as i32;
+    region * REGION_SIDE + Vec3D::new(slot % REGION_SIDE, (slot / REGION_SIDE) % REGION_SIDE, slot / (REGION_SIDE * REGION_SIDE))
+}
+
+#[derive(Clone)]
+pub struct WorldSave {
+    folder:PathBuf,
+}
+
+impl WorldSave {
+    pub fn new(folder:PathBuf) -> Self {
+        Self { folder }
+    }
+    pub fn get_folder(&self) -> &Path {
+        &self.folder
+    }
+    pub fn exists(&self) -> bool {
+        self.folder.join(META_FILE_NAME).exists()
+    }
+    fn region_path(&self, region:WorldRegionPos) -> PathBuf {
+        self.folder.join(format!("r.{}.{}.{}.{}", region.x, region.y, region.z, REGION_EXTENSION))
+    }
+    fn saved_regions(&self) -> io::Result<Vec<WorldRegionPos>> {
+        let mut regions = Vec::with_capacity(64);
+        for entry in fs::read_dir(&self.folder)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            let parts:Vec<&str> = name.split('.').collect();
+            if parts.len() == 5 && parts[0] == "r" && parts[4] == REGION_EXTENSION {
+                if let Ok(x) = parts[1].parse::<i32>() && let Ok(y) = parts[2].parse::<i32>() && let Ok(z) = parts[3].parse::<i32>() {
+                    regions.push(Vec3D::new(x, y, z));
+                }
+            }
+        }
+        Ok(regions)
+    }
+    fn read_meta(&self) -> io::Result<WorldMeta> {
+        let bytes = fs::read(self.folder.join(META_FILE_NAME))?;
+        if bytes.len() < 6 || bytes[0..4] != META_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "world meta file is corrupted"))
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != FORMAT_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported world meta version {}", version)))
+        }
+        decode_blob(&bytes[6..])
+    }
+    pub fn save_meta<V:Voxel>(&self, map:&GameMap<V>) -> io::Result<()> {
+        fs::create_dir_all(&self.folder)?;
+        let meta = WorldMeta { dims: map.dims.clone(), min_light_levels: map.min_light_levels, light_sources: map.light_sources.clone(), set_grid: map.set_grid.clone(), lod_factor: map.get_lod_factor() };
+        let mut bytes = Vec::with_capacity(1024);
+        bytes.extend_from_slice(&META_MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend(seal_blob(meta.to_bytes()));
+        fs::write(self.folder.join(META_FILE_NAME), bytes)
+    }
+    pub fn save_world<V:Voxel>(&self, map:&GameMap<V>) -> io::Result<()> {
+        self.save_meta(map)?;
+        let mut by_region:HashMap<WorldRegionPos, Vec<WorldChunkPos>> = HashMap::with_capacity(map.chunks.len() / CHUNKS_PER_REGION + 1);
+        for chunk_pos in map.chunks.keys() {
+            match by_region.get_mut(&get_region_pos(*chunk_pos)) {
+                Some(chunks) => chunks.push(*chunk_pos),
+                None => {by_region.insert(get_region_pos(*chunk_pos), vec![*chunk_pos]);}
+            }
+        }
+        for (region, chunks) in by_region {
+            // a full save rewrites the region from scratch so it doesn't keep dead space around
+            let mut region_file = RegionFile::create(&self.region_path(region), region, &map.dims)?;
+            for chunk_pos in chunks {
+                region_file.write_slot(get_slot_index(chunk_pos), &seal_blob(map.chunks[&chunk_pos].to_bytes()))?;
+            }
+        }
+        Ok(())
+    }
+    pub fn save_chunk<V:Voxel>(&self, map:&GameMap<V>, chunk_pos:WorldChunkPos) -> io::Result<bool> {
+        match map.get_chunk_at(chunk_pos) {
+            Some(chunk) => {
+                fs::create_dir_all(&self.folder)?;
+                let region = get_region_pos(chunk_pos);
+                let mut region_file = RegionFile::open_or_create(&self.region_path(region), region, &map.dims)?;
+                region_file.write_slot(get_slot_index(chunk_pos), &seal_blob(chunk.to_bytes()))?;
+                Ok(true)
+            },
+            None => Ok(false)
+        }
+    }
+    pub fn load_chunk<V:Voxel>(&self, dims:&ChunkDims, chunk_pos:WorldChunkPos) -> io::Result<Option<MapChunk<V>>> {
+        let region = get_region_pos(chunk_pos);
+        let path = self.region_path(region);
+        if !path.exists() {
+            return Ok(None)
+        }
+        let mut region_file = RegionFile::open(&path, region, dims)?;
+        match region_file.read_slot(get_slot_index(chunk_pos))? {
+            Some(bytes) => {
+                let mut chunk = decode_blob::<MapChunk<V>>(&bytes)?;
+                chunk.reset_render_state();
+                Ok(Some(chunk))
+            },
+            None => Ok(None)
+        }
+    }
+    fn load_region_into<V:Voxel>(&self, map:&mut GameMap<V>, region:WorldRegionPos) -> io::Result<usize> {
+        let path = self.region_path(region);
+        if !path.exists() {
+            return Ok(0)
+        }
+        let mut region_file = RegionFile::open(&path, region, &map.dims)?;
+        let mut loaded = 0;
+        for slot in region_file.saved_slots() {
+            let chunk_pos = get_chunk_pos_from_slot(region, slot);
+            if !map.does_chunk_exist(chunk_pos) {
+                if let Some(bytes) = region_file.read_slot(slot)? {
+                    let mut chunk = decode_blob::<MapChunk<V>>(&bytes)?;
+                    chunk.reset_render_state();
+                    map.chunks.insert(chunk_pos, chunk);
+                    map.signal_column_changed(chunk_pos);
+                    loaded += 1;
+                }
+            }
+        }
+        if loaded > 0 {
+            map.rendering_up_to_date = false;
+        }
+        Ok(loaded)
+    }
+    pub fn load_world<V:Voxel>(&self, expected_chunks:usize, voxel_types:Vec<V::VT>, mesh_vec:usize) -> io::Result<GameMap<V>> {
+        let mut map = self.load_empty_world(expected_chunks, voxel_types, mesh_vec)?;
+        for region in self.saved_regions()? {
+            self.load_region_into(&mut map, region)?;
+        }
+        Ok(map)
+    }
+    pub fn load_empty_world<V:Voxel>(&self, expected_chunks:usize, voxel_types:Vec<V::VT>, mesh_vec:usize) -> io::Result<GameMap<V>> {
+        let meta = self.read_meta()?;
+        let mut map = GameMap::new(expected_chunks, meta.dims, voxel_types, meta.min_light_levels, mesh_vec);
+        map.light_sources = meta.light_sources;
+        map.set_grid = meta.set_grid;
+        map.set_lod_factor(meta.lod_factor);
+        Ok(map)
+    }
+    /// Loads every saved region that has at least one chunk within `chunk_radius` chunks of `center`,
+    /// chunks that are already in the map are left untouched
+    pub fn load_regions_around<V:Voxel>(&self, map:&mut GameMap<V>, center:WorldVoxelPos, chunk_radius:i32) -> io::Result<usize> {
+        let center_chunk = map.get_chunk_pos_i(center);
+        let start = get_region_pos(center_chunk - Vec3D::all_ones() * chunk_radius);
+        let end = get_region_pos(center_chunk + Vec3D::all_ones() * chunk_radius);
+        let mut loaded = 0;
+        for x in start.x..=end.x {
+            for y in start.y..=end.y {
+                for z in start.z..=end.z {
+                    loaded += self.load_region_into(map, Vec3D::new(x, y, z))?;
+                }
+            }
+        }
+        Ok(loaded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hord3::horde::geometry::vec3d::Vec3Df;
+
+    use super::*;
+    use crate::{game_engine::{CoolVoxel, ExtraVoxelData}, game_map::ENTITY_GRID_VEC, voxel_registry::get_voxel_types, world_gen::{flat::{FlatGenerator, FlatParams}, WorldGenerator}};
+
+    #[test]
+    fn worlds_round_trip() {
+        let generator = FlatGenerator::new(0, FlatParams::flat());
+        let mut map = GameMap::<CoolVoxel>::new(8, ChunkDims::new(8, 8, 8), get_voxel_types(), (10, 20, 30), 1);
+        map.generate_chunks_batched(Vec3D::new(-1, -1, 0), Vec3D::new(1, 1, 2), &mut |origin, size| {generator.generate_chunk_voxels(origin, size)});
+        let damaged = Vec3D::new(-3, 2, 5);
+        let mut voxel = map.get_voxel_at(damaged).unwrap().clone();
+        voxel.extra_voxel_data = Some(vec![ExtraVoxelData::Health(3), ExtraVoxelData::FluidLevel(2)]);
+        map.update_voxel_data_at(damaged, voxel);
+        map.add_light_source(Vec3D::new(1, 1, 12), VoxelLight::new(200, 255, 128, 0));
+        map.set_grid.insert(4, ENTITY_GRID_VEC, &Vec3Df::new(1.5, -2.5, 12.0));
+
+        let save = WorldSave::new(std::env::temp_dir().join(format!("round_trip_world_{}", std::process::id())));
+        save.save_world(&map).unwrap();
+        let loaded = save.load_world::<CoolVoxel>(8, get_voxel_types(), 1);
+        fs::remove_dir_all(save.get_folder()).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.min_light_levels, (10, 20, 30));
+        assert!(loaded.light_sources == map.light_sources);
+        assert!(loaded.set_grid.location_of(4, ENTITY_GRID_VEC).is_some() && loaded.set_grid.location_of(4, ENTITY_GRID_VEC) == map.set_grid.location_of(4, ENTITY_GRID_VEC));
+        assert!(loaded.get_voxel_at(damaged).unwrap().extra_voxel_data == Some(vec![ExtraVoxelData::Health(3), ExtraVoxelData::FluidLevel(2)]));
+        for z in 0..16 {
+            for y in -8..8 {
+                for x in -8..8 {
+                    let pos = Vec3D::new(x, y, z);
+                    assert!(loaded.get_voxel_at(pos) == map.get_voxel_at(pos));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn damaged_region_files_are_invalid() {
+        let generator = FlatGenerator::new(0, FlatParams::flat());
+        let mut map = GameMap::<CoolVoxel>::new(8, ChunkDims::new(8, 8, 8), get_voxel_types(), (10, 20, 30), 1);
+        map.generate_chunks_batched(Vec3D::new(0, 0, 0), Vec3D::new(1, 1, 2), &mut |origin, size| {generator.generate_chunk_voxels(origin, size)});
+        let save = WorldSave::new(std::env::temp_dir().join(format!("damaged_world_{}", std::process::id())));
+        save.save_world(&map).unwrap();
+        let region_path = save.region_path(Vec3D::new(0, 0, 0));
+        let bytes = fs::read(&region_path).unwrap();
+        // cut in the middle of the last chunk
+        fs::write(&region_path, &bytes[..bytes.len() - 10]).unwrap();
+        let truncated = save.load_world::<CoolVoxel>(8, get_voxel_types(), 1).err().map(|error| {error.kind()});
+        // same length, one byte of a chunk changed
+        let mut flipped = bytes.clone();
+        let last = flipped.len() - 10;
+        flipped[last] ^= 0xFF;
+        fs::write(&region_path, &flipped).unwrap();
+        let corrupted = save.load_world::<CoolVoxel>(8, get_voxel_types(), 1).err().map(|error| {error.kind()});
+        fs::remove_dir_all(save.get_folder()).unwrap();
+        assert!(truncated == Some(io::ErrorKind::InvalidData));
+        assert!(corrupted == Some(io::ErrorKind::InvalidData));
+    }
+}
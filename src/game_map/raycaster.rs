@@ -1,14 +1,9 @@
 
 use core::f32;
 
-use hord3::horde::geometry::{rotation::Orientation, vec3d::Vec3Df};
+use hord3::horde::geometry::{rotation::Orientation, vec3d::{Vec3D, Vec3Df}};
 
-use crate::game_engine::CoolVoxel;
-
-use super::{get_voxel_pos, GameMap, VoxelType};
-
-
-const PRECISION:f32 = 0.25;
+use super::{get_float_pos, GameMap, Voxel, VoxelType, WorldVoxelPos, EXPLORATION};
 
 pub struct Ray {
     start:Vec3Df,
@@ -21,32 +16,170 @@ pub struct RayEnd {
     pub final_length:f32
 }
 
+#[derive(Clone, Debug)]
+pub struct RayHit {
+    pub pos:WorldVoxelPos,
+    /// last voxel the ray went through before the hit, None if the ray started inside a solid voxel
+    pub previous_empty:Option<WorldVoxelPos>,
+    /// index in EXPLORATION of the face that was crossed, None if the ray started inside the voxel
+    pub face:Option<usize>,
+    pub normal:Vec3D<i32>,
+    pub distance:f32,
+    pub voxel_type:usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct RayStep {
+    pub pos:WorldVoxelPos,
+    pub face:Option<usize>,
+    pub normal:Vec3D<i32>,
+    /// distance along the ray at which it entered this voxel
+    pub distance:f32,
+}
+
+/// Amanatides-Woo grid traversal, voxel `pos` covers `pos - 0.5..pos + 0.5` on every axis just like the chunk meshes
+pub struct VoxelTraversal {
+    current:WorldVoxelPos,
+    step:Vec3D<i32>,
+    t_max:Vec3Df,
+    t_delta:Vec3Df,
+    max_length:f32,
+    next_step:Option<RayStep>,
+}
+
+fn get_face_from_normal(normal:Vec3D<i32>) -> Option<usize> {
+    EXPLORATION.iter().position(|dir| {*dir == normal})
+}
+
+fn axis_setup(start:f32, dir:f32) -> (i32, f32, f32) {
+    // shifted by 0.5 so the voxel boundaries land on integers
+    let shifted = start + 0.5;
+    if dir > 0.0 {
+        (1, (shifted.floor() + 1.0 - shifted) / dir, 1.0 / dir)
+    }
+    else if dir < 0.0 {
+        (-1, (shifted - shifted.floor()) / -dir, -1.0 / dir)
+    }
+    else {
+        (0, f32::INFINITY, f32::INFINITY)
+    }
+}
+
+impl VoxelTraversal {
+    pub fn new(start:Vec3Df, direction:Vec3Df, max_length:f32) -> Self {
+        let direction = direction * (1.0 / direction.norme().max(f32::EPSILON));
+        let current = Vec3D::new((start.x + 0.5).floor() as i32, (start.y + 0.5).floor() as i32, (start.z + 0.5).floor() as i32);
+        let (sx, tx, dx) = axis_setup(start.x, direction.x);
+        let (sy, ty, dy) = axis_setup(start.y, direction.y);
+        let (sz, tz, dz) = axis_setup(start.z, direction.z);
+        Self {
+            current,
+            step:Vec3D::new(sx, sy, sz),
+            t_max:Vec3D::new(tx, ty, tz),
+            t_delta:Vec3D::new(dx, dy, dz),
+            max_length,
+            next_step:Some(RayStep { pos: current, face: None, normal: Vec3D::zero(), distance: 0.0 }),
+        }
+    }
+}
+
+impl Iterator for VoxelTraversal {
+    type Item = RayStep;
+    fn next(&mut self) -> Option<Self::Item> {
+        let step = self.next_step.take()?;
+        let (distance, movement) = if self.t_max.x <= self.t_max.y && self.t_max.x <= self.t_max.z {
+            let distance = self.t_max.x;
+            self.t_max.x += self.t_delta.x;
+            (distance, Vec3D::new(self.step.x, 0, 0))
+        }
+        else if self.t_max.y <= self.t_max.z {
+            let distance = self.t_max.y;
+            self.t_max.y += self.t_delta.y;
+            (distance, Vec3D::new(0, self.step.y, 0))
+        }
+        else {
+            let distance = self.t_max.z;
+            self.t_max.z += self.t_delta.z;
+            (distance, Vec3D::new(0, 0, self.step.z))
+        };
+        // a zero direction never crosses anything, its only voxel is the starting one
+        if distance.is_finite() && distance <= self.max_length {
+            self.current = self.current + movement;
+            let normal = Vec3D::zero() - movement;
+            self.next_step = Some(RayStep { pos: self.current, face: get_face_from_normal(normal), normal, distance });
+        }
+        Some(step)
+    }
+}
+
 impl Ray {
     pub fn new(start:Vec3Df, direction:Orientation, max_length:Option<f32>) -> Self {
         Self { start, direction, max_length }
     }
-    pub fn get_end(&self, chunks:&GameMap<CoolVoxel>) -> RayEnd {
-        let mut test = self.start.clone();
-        let mut dir = self.direction.into_vec() * PRECISION;
-        let max_length = self.max_length.unwrap_or(f32::INFINITY);
-        let mut length = 0.0;
-        //dbg!(dir);
-        while length < max_length && chunks.get_type_of_voxel_at(get_voxel_pos(test)).is_some_and(|vox_type| {vox_type.sides_empty() == 0b00111111}) {
-            test += dir;
-            length += PRECISION;
-        }
-        return RayEnd { end:test, final_length:length }
-    }
-    pub fn get_first_back_different(&self, chunks:&GameMap<CoolVoxel>, end:Option<RayEnd>) -> RayEnd {
-        match end {
-            Some(end) => {
-                RayEnd {end:end.end - self.direction.into_vec() * PRECISION, final_length:end.final_length - PRECISION}
+    pub fn traverse(&self) -> VoxelTraversal {
+        VoxelTraversal::new(self.start, self.direction.into_vec(), self.max_length.unwrap_or(f32::INFINITY))
+    }
+    /// First voxel whose type matches `stop_on`, or how far the ray went without a hit : up to its max length, or to where it left the loaded chunks
+    fn cast_or_travel<V:Voxel, F:FnMut(&V::VT) -> bool>(&self, chunks:&GameMap<V>, mut stop_on:F) -> Result<RayHit, f32> {
+        let mut previous = None;
+        for step in self.traverse() {
+            match chunks.get_voxel_at(step.pos) {
+                Some(voxel) => {
+                    if stop_on(&chunks.get_voxel_types()[voxel.voxel_id()]) {
+                        return Ok(RayHit { pos: step.pos, previous_empty: previous, face: step.face, normal: step.normal, distance: step.distance, voxel_type: voxel.voxel_id() })
+                    }
+                },
+                None => return Err(step.distance)
+            }
+            previous = Some(step.pos);
+        }
+        // without a max length the traversal only ends there if the direction is zero
+        Err(self.max_length.unwrap_or(0.0))
+    }
+    /// First voxel whose type matches `stop_on`, the ray also stops (without a hit) when it leaves the loaded chunks
+    pub fn cast_with<V:Voxel, F:FnMut(&V::VT) -> bool>(&self, chunks:&GameMap<V>, stop_on:F) -> Option<RayHit> {
+        self.cast_or_travel(chunks, stop_on).ok()
+    }
+    pub fn cast<V:Voxel>(&self, chunks:&GameMap<V>) -> Option<RayHit> {
+        self.cast_with(chunks, |voxel_type| {!voxel_type.is_completely_empty()})
+    }
+    /// The voxel hit, or where the ray stopped without a hit (its max length or the edge of the loaded chunks)
+    pub fn get_end<V:Voxel>(&self, chunks:&GameMap<V>) -> RayEnd {
+        match self.cast_or_travel(chunks, |voxel_type| {!voxel_type.is_completely_empty()}) {
+            Ok(hit) => RayEnd { end: get_float_pos(hit.pos), final_length: hit.distance },
+            Err(length) => RayEnd { end: self.start + self.direction.into_vec() * length, final_length: length }
+        }
+    }
+    pub fn get_first_back_different<V:Voxel>(&self, chunks:&GameMap<V>, end:Option<RayEnd>) -> RayEnd {
+        match self.cast(chunks) {
+            Some(hit) => match hit.previous_empty {
+                Some(previous) => RayEnd { end: get_float_pos(previous), final_length: hit.distance },
+                None => RayEnd { end: get_float_pos(hit.pos), final_length: 0.0 }
             },
-            None => {
-                let end = self.get_end(chunks);
-                let new_end = end.end - self.direction.into_vec() * PRECISION;
-                RayEnd {end:new_end, final_length:end.final_length - PRECISION}
+            None => match end {
+                Some(end) => end,
+                None => self.get_end(chunks)
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// True if no solid voxel sits strictly between the two positions
+pub fn has_line_of_sight<V:Voxel>(chunks:&GameMap<V>, from:Vec3Df, to:Vec3Df) -> bool {
+    let diff = to - from;
+    let length = diff.norme();
+    let voxel_of = |pos:Vec3Df| {Vec3D::new((pos.x + 0.5).floor() as i32, (pos.y + 0.5).floor() as i32, (pos.z + 0.5).floor() as i32)};
+    let target = voxel_of(to);
+    if length == 0.0 || voxel_of(from) == target {
+        return true
+    }
+    for step in VoxelTraversal::new(from, diff, length) {
+        if step.pos == target {
+            return true
+        }
+        if step.distance > 0.0 && chunks.is_voxel_solid(step.pos) {
+            return false
+        }
+    }
+    true
+}
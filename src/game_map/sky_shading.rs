@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use hord3::{defaults::default_rendering::vectorinator_binned::meshes::{MeshLOD, MeshLODType, MeshTriangles, TrianglePoint}, horde::geometry::vec3d::Vec3Df};
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use super::light_engine::SKY_MAX;
+
+// Skylight follows the time of day without meshing anything again : the meshers build ShadedLODs, where every vertex keeps the colour
+// its block light gives apart from how much skylight reaches it, and those only become coloured MeshLODs for a given sky brightness.
+// The map keeps the ShadedLODs of the chunks any skylight reaches, so when the brightness changes they only get recoloured
+// (on the meshing threads, a few chunks per frame) without reading a single voxel.
+// Smooth lighting means and ambient occlusion apply to both parts the same way.
+
+/// Light of a vertex : the block light colour (min light levels included) and the skylight, 255 being SKY_MAX at full brightness
+#[derive(Clone, Copy, PartialEq, Debug, ToBytes, FromBytes)]
+pub struct VertexShade {
+    block:(u8, u8, u8),
+    sky:u8,
+}
+
+impl VertexShade {
+    pub fn new(block:(u8, u8, u8), sky_level:u8) -> Self {
+        Self { block, sky: (sky_level.min(SKY_MAX) as u32 * 255 / SKY_MAX as u32) as u8 }
+    }
+    /// Mean of several shades, darkened by `factor`
+    pub fn mean(shades:&[VertexShade], factor:f32) -> Self {
+        let count = shades.len().max(1) as f32;
+        let total = shades.iter().fold((0, 0, 0, 0), |total, shade| {(total.0 + shade.block.0 as u32, total.1 + shade.block.1 as u32, total.2 + shade.block.2 as u32, total.3 + shade.sky as u32)});
+        let scale = |sum:u32| {(sum as f32 / count * factor) as u8};
+        Self { block: (scale(total.0), scale(total.1), scale(total.2)), sky: scale(total.3) }
+    }
+    pub fn has_sky(&self) -> bool {
+        self.sky > 0
+    }
+    pub fn color(&self, sky_brightness:u8) -> (u8, u8, u8) {
+        let sky = (self.sky as u32 * sky_brightness as u32 / 255) as u8;
+        (self.block.0.max(sky), self.block.1.max(sky), self.block.2.max(sky))
+    }
+    /// Brightness under a full sky, doesn't depend on the time of day so the meshes don't either
+    pub fn brightness(&self) -> u32 {
+        let (r, g, b) = self.color(255);
+        r as u32 + g as u32 + b as u32
+    }
+}
+
+#[derive(Clone, Copy, ToBytes, FromBytes)]
+pub struct ShadedPoint {
+    index:usize,
+    u:f32,
+    v:f32,
+    shade:VertexShade,
+}
+
+impl ShadedPoint {
+    pub fn new(index:usize, u:f32, v:f32, shade:VertexShade) -> Self {
+        Self { index, u, v, shade }
+    }
+    fn colored(&self, sky_brightness:u8) -> TrianglePoint {
+        let (r, g, b) = self.shade.color(sky_brightness);
+        TrianglePoint::new(self.index, self.u, self.v, r, g, b)
+    }
+}
+
+#[derive(Clone, ToBytes, FromBytes)]
+struct ShadedTriangle {
+    points:(ShadedPoint, ShadedPoint, ShadedPoint),
+    texture:u32,
+}
+
+#[derive(Clone, ToBytes, FromBytes)]
+pub struct ShadedTriangles {
+    triangles:Vec<ShadedTriangle>,
+}
+
+impl ShadedTriangles {
+    pub fn with_capacity(capacity:usize) -> Self {
+        Self { triangles: Vec::with_capacity(capacity) }
+    }
+    pub fn add_triangle(&mut self, p1:ShadedPoint, p2:ShadedPoint, p3:ShadedPoint, texture:u32) {
+        self.triangles.push(ShadedTriangle { points: (p1, p2, p3), texture });
+    }
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+}
+
+/// Same as a MeshLOD, with shades instead of colours
+#[derive(Clone, ToBytes, FromBytes)]
+pub struct ShadedLOD {
+    pub x:Vec<f32>,
+    pub y:Vec<f32>,
+    pub z:Vec<f32>,
+    pub triangles:ShadedTriangles,
+}
+
+impl ShadedLOD {
+    pub fn new(x:Vec<f32>, y:Vec<f32>, z:Vec<f32>, triangles:ShadedTriangles) -> Self {
+        Self { x, y, z, triangles }
+    }
+    pub fn add_points(&mut self, points:&[Vec3Df]) {
+        for point in points {
+            self.x.push(point.x);
+            self.y.push(point.y);
+            self.z.push(point.z);
+        }
+    }
+    pub fn has_sky(&self) -> bool {
+        self.triangles.triangles.iter().any(|triangle| {
+            let (p1, p2, p3) = &triangle.points;
+            p1.shade.has_sky() || p2.shade.has_sky() || p3.shade.has_sky()
+        })
+    }
+    pub fn colored(&self, sky_brightness:u8) -> MeshLOD {
+        let mut triangles = MeshTriangles::with_capacity(self.triangles.len());
+        for triangle in &self.triangles.triangles {
+            let (p1, p2, p3) = &triangle.points;
+            triangles.add_triangle(p1.colored(sky_brightness), p2.colored(sky_brightness), p3.colored(sky_brightness), triangle.texture, 0);
+        }
+        MeshLOD::new(self.x.clone(), self.y.clone(), self.z.clone(), triangles)
+    }
+}
+
+/// Every level of a chunk's meshes before colouring, see ChunkMesher::mesh_chunk
+#[derive(Clone, ToBytes, FromBytes)]
+pub struct ShadedChunk {
+    pub lods:Vec<ShadedLOD>,
+    pub translucent:Option<ShadedLOD>,
+    /// see ChunkMesher::mesh_radius
+    pub radius:f32,
+}
+
+impl ShadedChunk {
+    /// Whether any skylight reaches a vertex, so the chunk changes with the time of day
+    pub fn has_sky(&self) -> bool {
+        self.lods.iter().chain(self.translucent.iter()).any(|lod| {lod.has_sky()})
+    }
+    /// The LODs and translucent level coloured for that sky brightness
+    pub fn colored(&self, sky_brightness:u8) -> (Vec<MeshLODType>, Option<MeshLODType>) {
+        (
+            self.lods.iter().map(|lod| {MeshLODType::Mesh(Arc::new(lod.colored(sky_brightness)))}).collect(),
+            self.translucent.as_ref().map(|lod| {MeshLODType::Mesh(Arc::new(lod.colored(sky_brightness)))})
+        )
+    }
+}
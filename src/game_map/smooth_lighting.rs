@@ -0,0 +1,78 @@
+use hord3::horde::geometry::vec3d::{Vec3D, Vec3Df};
+
+use super::{chunk_borders::ChunkBorders, sky_shading::{ShadedLOD, ShadedPoint, VertexShade}, ChunkDims, ChunkMesher, MapChunk, RenderLayer, Voxel, VoxelLight, VoxelType, WorldVoxelPos, EXPLORATION, PERPENDICULAR, TRIS_INDICES_UVS};
+
+// Smooth lighting : every corner of a face gets the mean shade (block light and skylight) of the non opaque voxels touching it on the face's side
+// (the one in front of the face, the two next to it along the face's sides and the diagonal one), darkened by ambient occlusion
+// from how many of those three neighbours are opaque, both sides being opaque hiding the corner completely.
+// Neighbours are looked up across the 26 chunks around, so corners along the chunk's edges are shaded like any other.
+// Quads are split along the diagonal joining their darkest pair of opposite corners under a full sky, so the shading doesn't stretch one way.
+
+/// Brightness of a corner with 3, 2, 1 or none of its neighbours opaque
+const AO_LEVELS:[f32 ; 4] = [0.5, 0.65, 0.8, 1.0];
+
+/// TRIS_INDICES_UVS.1 splits quads along the 0-1 diagonal, this goes along 2-3 with the same winding
+const FLIPPED_INDICES:[usize ; 6] = [2, 1, 3, 2, 3, 0];
+
+impl<V:Voxel> MapChunk<V> {
+    /// Voxel at a local position inside the chunk or right outside of it, None further away
+    pub fn get_at_local_across<'a>(&'a self, pos:WorldVoxelPos, around:&'a ChunkBorders<V>, dims:&ChunkDims) -> Option<&'a V> {
+        self.get_at_local(pos, dims).or_else(|| {around.get_at_local(pos, dims)})
+    }
+}
+
+/// Adds a quad with one shade per corner (in the order of `points`)
+pub(super) fn add_smooth_quad(lod:&mut ShadedLOD, points:&[Vec3Df ; 4], shades:&[VertexShade ; 4], texture:u32, (u_scale, v_scale):(f32, f32)) {
+    let start_index = lod.x.len();
+    let uvs = TRIS_INDICES_UVS.2;
+    let brightness = |corner:usize| {shades[corner].brightness()};
+    let indices = if brightness(0) + brightness(1) > brightness(2) + brightness(3) {FLIPPED_INDICES} else {TRIS_INDICES_UVS.1};
+    lod.add_points(points);
+    for tri in 0..2 {
+        let point = |k:usize| {
+            let index = indices[tri * 3 + k];
+            ShadedPoint::new(index + start_index, uvs[index].0 * u_scale, uvs[index].1 * v_scale, shades[index])
+        };
+        lod.triangles.add_triangle(point(0), point(1), point(2), texture);
+    }
+}
+
+impl<V:Voxel> ChunkMesher<V> {
+    fn occludes(&self, voxel:Option<&V>) -> bool {
+        voxel.is_some_and(|voxel| {
+            let voxel_type = &self.voxel_types[voxel.voxel_id()];
+            !voxel_type.is_completely_empty() && voxel_type.render_layer() == RenderLayer::Opaque && !self.is_custom(voxel)
+        })
+    }
+    /// Shade of every corner of the `dir` face of the voxel at `local`, in the order of TRIS_INDICES_UVS.0[dir]
+    pub(super) fn get_vertex_colors(&self, chunk:&MapChunk<V>, around:&ChunkBorders<V>, local:Vec3D<i32>, dir:usize) -> [VertexShade ; 4] {
+        let front = local + EXPLORATION[dir];
+        let (first, second) = PERPENDICULAR[dir].clone();
+        let sample = |pos:Vec3D<i32>| {chunk.get_at_local_across(pos, around, &self.dims)};
+        // which way a corner of the face points along an axis
+        let side_of = |corner:&Vec3Df, axis:Vec3D<i32>| {(corner.x * axis.x as f32 + corner.y * axis.y as f32 + corner.z * axis.z as f32).signum() as i32};
+        TRIS_INDICES_UVS.0[dir].map(|corner| {
+            let (along_first, along_second) = (first * side_of(&corner, first), second * side_of(&corner, second));
+            let (side_1, side_2, diagonal) = (sample(front + along_first), sample(front + along_second), sample(front + along_first + along_second));
+            let ao = if self.occludes(side_1) && self.occludes(side_2) {
+                0
+            }
+            else {
+                3 - self.occludes(side_1) as usize - self.occludes(side_2) as usize - self.occludes(diagonal) as usize
+            };
+            let mut samples = Vec::with_capacity(4);
+            for voxel in [sample(front), side_1, side_2, if ao == 0 {None} else {diagonal}] {
+                match voxel {
+                    Some(voxel) => if !self.occludes(Some(voxel)) {
+                        samples.push(self.get_face_shade(voxel.light_level(), voxel.sky_light()));
+                    },
+                    None => ()
+                }
+            }
+            if samples.is_empty() {
+                samples.push(self.get_face_shade(VoxelLight::new(0, 0, 0, 0), 0));
+            }
+            VertexShade::mean(&samples, AO_LEVELS[ao])
+        })
+    }
+}
@@ -0,0 +1,387 @@
+use std::{collections::{HashMap, HashSet}, hash::Hash};
+
+use hord3::horde::geometry::vec3d::{Vec3D, Vec3Df};
+use to_from_bytes::{FromBytes, ToBytes};
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+// Sparse spatial hash : space is cut in cubic cells of `cell_size`, only the occupied ones exist.
+// Ids live in layers (one per kind of thing indexed, entity_1 is layer 2 for the game), each id being in at most one cell per layer,
+// the one of the point it was last moved to. That's why queries give candidates : everything in the cells touching the query,
+// callers pad the query by the size of what they index. Radius and k-nearest queries ask for the exact positions with a closure
+// (the index doesn't keep positions, they change every tick), returning None for an id leaves it out,
+// which is also what callers do for ids of entities that don't exist anymore.
+// Changes from entity ticks go through SetGridUpdate events so they're applied (and synced) like every other world event.
+
+#[derive(Clone, Debug)]
+pub enum SetGridUpdate<I = usize> {
+    AddToSet {grid_slot:Vec3D<i32>, vec_index:usize, add:I},
+    RemoveFromSet {grid_slot:Vec3D<i32>, vec_index:usize, remove:I},
+    MoveFromTo {grid_start:Vec3D<i32>, grid_end:Vec3D<i32>, vec_index:usize, id:I}
+}
+
+#[derive(Clone, ToBytes, FromBytes)]
+pub struct SpatialHash<I: Hash + Eq + Copy + ToBytes + FromBytes> {
+    cell_size:f32,
+    inv_scale:f32,
+    set_capacity:usize,
+    cells:HashMap<Vec3D<i32>, Vec<HashSet<I>>>,
+    /// cell of every id, one map per layer
+    locations:Vec<HashMap<I, Vec3D<i32>>>,
+}
+
+pub type SetGrid = SpatialHash<usize>;
+
+impl<I: Hash + Eq + Copy + ToBytes + FromBytes> SpatialHash<I> {
+    pub fn new(cell_size:f32, layers:usize, set_capacity:usize) -> Self {
+        Self { cell_size, inv_scale: 1.0/cell_size, set_capacity, cells: HashMap::with_capacity(64), locations: (0..layers).map(|_| {HashMap::with_capacity(set_capacity)}).collect() }
+    }
+    pub fn cell_of(&self, point:&Vec3Df) -> Vec3D<i32> {
+        Vec3D::new((point.x * self.inv_scale).floor() as i32, (point.y * self.inv_scale).floor() as i32, (point.z * self.inv_scale).floor() as i32)
+    }
+    pub fn get_cell_size(&self) -> f32 {
+        self.cell_size
+    }
+    pub fn len(&self, layer:usize) -> usize {
+        self.locations[layer].len()
+    }
+    pub fn location_of(&self, id:I, layer:usize) -> Option<Vec3D<i32>> {
+        self.locations[layer].get(&id).copied()
+    }
+    fn add_to_cell(&mut self, cell:Vec3D<i32>, layer:usize, id:I) {
+        let layers = self.locations.len();
+        let set_capacity = self.set_capacity;
+        // an id is only ever in one cell of a layer
+        if let Some(previous) = self.locations[layer].insert(id, cell) && previous != cell {
+            self.remove_from_cell(previous, layer, id);
+        }
+        self.cells.entry(cell).or_insert_with(|| {(0..layers).map(|_| {HashSet::with_capacity(set_capacity)}).collect()})[layer].insert(id);
+    }
+    fn remove_from_cell(&mut self, cell:Vec3D<i32>, layer:usize, id:I) {
+        if let Some(sets) = self.cells.get_mut(&cell) {
+            sets[layer].remove(&id);
+            if sets.iter().all(|set| {set.is_empty()}) {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+    pub fn insert(&mut self, id:I, layer:usize, point:&Vec3Df) {
+        self.add_to_cell(self.cell_of(point), layer, id);
+    }
+    /// Same as insert, ids that weren't in get added
+    pub fn move_to(&mut self, id:I, layer:usize, point:&Vec3Df) {
+        self.insert(id, layer, point);
+    }
+    pub fn remove(&mut self, id:I, layer:usize) {
+        if let Some(cell) = self.locations[layer].remove(&id) {
+            self.remove_from_cell(cell, layer, id);
+        }
+    }
+    pub fn apply_update(&mut self, update:SetGridUpdate<I>) {
+        match update {
+            SetGridUpdate::AddToSet { grid_slot, vec_index, add } => self.add_to_cell(grid_slot, vec_index, add),
+            SetGridUpdate::RemoveFromSet { vec_index, remove, .. } => self.remove(remove, vec_index),
+            SetGridUpdate::MoveFromTo { grid_end, vec_index, id, .. } => self.add_to_cell(grid_end, vec_index, id),
+        }
+    }
+    /// Update to send once the id moved to `end`, None if it stays in the same cell
+    pub fn get_point_move_update(&self, end:&Vec3Df, id:I, vec_index:usize) -> Option<SetGridUpdate<I>> {
+        let grid_end = self.cell_of(end);
+        match self.location_of(id, vec_index) {
+            Some(grid_start) => if grid_start != grid_end {
+                Some(SetGridUpdate::MoveFromTo { grid_start, grid_end, vec_index, id })
+            }
+            else {
+                None
+            },
+            None => Some(SetGridUpdate::AddToSet { grid_slot: grid_end, vec_index, add: id })
+        }
+    }
+    pub fn get_remove_update(&self, id:I, vec_index:usize) -> Option<SetGridUpdate<I>> {
+        self.location_of(id, vec_index).map(|grid_slot| {SetGridUpdate::RemoveFromSet { grid_slot, vec_index, remove: id }})
+    }
+    /// Ids in the cells between `min_cell` and `max_cell` (both included)
+    fn in_cells(&self, min_cell:Vec3D<i32>, max_cell:Vec3D<i32>, layer:usize) -> Vec<I> {
+        // as floats, infinite radiuses give boxes as big as an i32 allows
+        let side = |min:i32, max:i32| {(max as f64 - min as f64 + 1.0).max(0.0)};
+        let volume = side(min_cell.x, max_cell.x) * side(min_cell.y, max_cell.y) * side(min_cell.z, max_cell.z);
+        let mut found = Vec::with_capacity(16);
+        let mut add_cell = |sets:&Vec<HashSet<I>>| {found.extend(sets[layer].iter().copied())};
+        // big boxes go through the occupied cells instead of every cell of the box
+        if volume > self.cells.len() as f64 {
+            for (cell, sets) in &self.cells {
+                if cell.x >= min_cell.x && cell.y >= min_cell.y && cell.z >= min_cell.z && cell.x <= max_cell.x && cell.y <= max_cell.y && cell.z <= max_cell.z {
+                    add_cell(sets);
+                }
+            }
+        }
+        else {
+            for x in min_cell.x..=max_cell.x {
+                for y in min_cell.y..=max_cell.y {
+                    for z in min_cell.z..=max_cell.z {
+                        if let Some(sets) = self.cells.get(&Vec3D::new(x, y, z)) {
+                            add_cell(sets);
+                        }
+                    }
+                }
+            }
+        }
+        found
+    }
+    /// Ids whose cell touches the box between the two corners, in any order
+    pub fn in_aabb(&self, corner_1:Vec3Df, corner_2:Vec3Df, layer:usize) -> Vec<I> {
+        let (cell_1, cell_2) = (self.cell_of(&corner_1), self.cell_of(&corner_2));
+        self.in_cells(
+            Vec3D::new(cell_1.x.min(cell_2.x), cell_1.y.min(cell_2.y), cell_1.z.min(cell_2.z)),
+            Vec3D::new(cell_1.x.max(cell_2.x), cell_1.y.max(cell_2.y), cell_1.z.max(cell_2.z)),
+            layer
+        )
+    }
+    /// Ids at most `radius` away from `center`, with their distance
+    pub fn in_radius(&self, center:Vec3Df, radius:f32, layer:usize, position_of:impl Fn(I) -> Option<Vec3Df>) -> Vec<(I, f32)> {
+        let extent = Vec3Df::all_ones() * radius;
+        self.in_aabb(center - extent, center + extent, layer).into_iter().filter_map(|id| {
+            position_of(id).map(|pos| {(id, pos.dist(&center))}).filter(|(_, dist)| {*dist <= radius})
+        }).collect()
+    }
+    /// Up to `k` ids closest to `center` and no further than `max_radius` (which can be infinite), closest first
+    pub fn k_nearest(&self, center:Vec3Df, k:usize, max_radius:f32, layer:usize, position_of:impl Fn(I) -> Option<Vec3Df>) -> Vec<(I, f32)> {
+        if k == 0 {
+            return Vec::new()
+        }
+        let mut radius = self.cell_size.min(max_radius);
+        loop {
+            let mut found = self.in_radius(center, radius, layer, &position_of);
+            // everything within the radius was looked at, so if there's enough of them the k closest are among them
+            let looked_everywhere = radius >= max_radius || self.in_aabb(center - Vec3Df::all_ones() * radius, center + Vec3Df::all_ones() * radius, layer).len() >= self.len(layer);
+            if found.len() >= k || looked_everywhere {
+                found.sort_by(|(_, dist_1), (_, dist_2)| {dist_1.total_cmp(dist_2)});
+                found.truncate(k);
+                return found
+            }
+            radius = (radius * 2.0).min(max_radius);
+        }
+    }
+    /// Ids in the cells the segment goes through, from `start` to `end`
+    pub fn along_segment(&self, start:Vec3Df, end:Vec3Df, layer:usize) -> Vec<I> {
+        let mut found = Vec::with_capacity(8);
+        let mut cell = self.cell_of(&start);
+        let last = self.cell_of(&end);
+        let dir = end - start;
+        let step = Vec3D::new(dir.x.signum() as i32, dir.y.signum() as i32, dir.z.signum() as i32);
+        // fraction of the segment at which the next cell border is crossed on each axis, and between two borders
+        let first_border = |pos:f32, dir:f32, cell:i32| {
+            if dir > 0.0 {((cell + 1) as f32 * self.cell_size - pos) / dir}
+            else if dir < 0.0 {(cell as f32 * self.cell_size - pos) / dir}
+            else {f32::INFINITY}
+        };
+        let between_borders = |dir:f32| {if dir != 0.0 {self.cell_size / dir.abs()} else {f32::INFINITY}};
+        let mut next = Vec3D::new(first_border(start.x, dir.x, cell.x), first_border(start.y, dir.y, cell.y), first_border(start.z, dir.z, cell.z));
+        let delta = Vec3D::new(between_borders(dir.x), between_borders(dir.y), between_borders(dir.z));
+        // float errors can't make it walk forever
+        let max_steps = (last.x - cell.x).abs() + (last.y - cell.y).abs() + (last.z - cell.z).abs();
+        for _ in 0..=max_steps {
+            if let Some(sets) = self.cells.get(&cell) {
+                found.extend(sets[layer].iter().copied());
+            }
+            if cell == last {
+                break
+            }
+            if next.x <= next.y && next.x <= next.z {
+                cell.x += step.x;
+                next.x += delta.x;
+            }
+            else if next.y <= next.z {
+                cell.y += step.y;
+                next.y += delta.y;
+            }
+            else {
+                cell.z += step.z;
+                next.z += delta.z;
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYER:usize = 1;
+
+    fn points() -> Vec<Vec3Df> {
+        // deterministic spread over negative and positive cells
+        (0..200).map(|i| {
+            let i = i as f32;
+            Vec3Df::new((i * 7.31) % 23.0 - 11.5, (i * 3.17) % 17.0 - 8.5, (i * 5.09) % 13.0 - 6.5)
+        }).collect()
+    }
+
+    fn filled(points:&Vec<Vec3Df>) -> SpatialHash<usize> {
+        let mut grid = SpatialHash::new(2.0, 3, 4);
+        for (id, point) in points.iter().enumerate() {
+            grid.insert(id, LAYER, point);
+        }
+        grid
+    }
+
+    fn brute_force(points:&Vec<Vec3Df>, center:Vec3Df, radius:f32) -> Vec<(usize, f32)> {
+        let mut found:Vec<(usize, f32)> = points.iter().enumerate().map(|(id, point)| {(id, point.dist(&center))}).filter(|(_, dist)| {*dist <= radius}).collect();
+        found.sort_by(|(_, dist_1), (_, dist_2)| {dist_1.total_cmp(dist_2)});
+        found
+    }
+
+    /// Ids whose cell the segment touches, slab test against every cell's box
+    fn brute_force_segment(grid:&SpatialHash<usize>, points:&Vec<Vec3Df>, start:Vec3Df, end:Vec3Df) -> Vec<usize> {
+        let size = grid.get_cell_size();
+        let crosses = |cell:Vec3D<i32>| {
+            let (mut t_min, mut t_max) = (0.0_f32, 1.0_f32);
+            for (start, end, cell) in [(start.x, end.x, cell.x), (start.y, end.y, cell.y), (start.z, end.z, cell.z)] {
+                let (low, high) = (cell as f32 * size, (cell + 1) as f32 * size);
+                let dir = end - start;
+                if dir == 0.0 {
+                    if start < low || start > high {
+                        return false
+                    }
+                }
+                else {
+                    let (t_1, t_2) = ((low - start) / dir, (high - start) / dir);
+                    t_min = t_min.max(t_1.min(t_2));
+                    t_max = t_max.min(t_1.max(t_2));
+                }
+            }
+            t_min <= t_max
+        };
+        let mut found:Vec<usize> = points.iter().enumerate().filter(|(_, point)| {crosses(grid.cell_of(point))}).map(|(id, _)| {id}).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn insert_move_remove() {
+        let mut grid = SpatialHash::new(2.0, 3, 4);
+        grid.insert(5, LAYER, &Vec3Df::new(0.5, 0.5, 0.5));
+        assert_eq!(grid.location_of(5, LAYER), Some(Vec3D::new(0, 0, 0)));
+        assert_eq!(grid.len(LAYER), 1);
+        assert_eq!(grid.len(0), 0);
+        // crossing into negative cells
+        grid.move_to(5, LAYER, &Vec3Df::new(-0.1, 1.9, -2.1));
+        assert_eq!(grid.location_of(5, LAYER), Some(Vec3D::new(-1, 0, -2)));
+        assert_eq!(grid.len(LAYER), 1);
+        assert!(!grid.cells.contains_key(&Vec3D::new(0, 0, 0)));
+        assert!(grid.in_aabb(Vec3Df::new(0.0, 0.0, 0.0), Vec3Df::new(1.0, 1.0, 1.0), LAYER).is_empty());
+        assert_eq!(grid.in_aabb(Vec3Df::new(-1.0, 0.0, -3.0), Vec3Df::new(-0.5, 0.5, -2.5), LAYER), vec![5]);
+        grid.remove(5, LAYER);
+        assert_eq!(grid.location_of(5, LAYER), None);
+        assert_eq!(grid.len(LAYER), 0);
+        assert!(grid.cells.is_empty());
+    }
+
+    #[test]
+    fn updates_follow_points() {
+        let mut grid = SpatialHash::new(2.0, 3, 4);
+        let path = [Vec3Df::new(0.5, 0.5, 0.5), Vec3Df::new(1.5, 0.5, 0.5), Vec3Df::new(2.5, 0.5, 0.5), Vec3Df::new(2.5, -3.0, 0.5)];
+        for point in path.iter() {
+            if let Some(update) = grid.get_point_move_update(point, 0, LAYER) {
+                grid.apply_update(update);
+            }
+            assert_eq!(grid.location_of(0, LAYER), Some(grid.cell_of(point)));
+            assert_eq!(grid.cells.len(), 1);
+        }
+        assert!(grid.get_point_move_update(&Vec3Df::new(3.0, -3.5, 1.0), 0, LAYER).is_none());
+        grid.apply_update(grid.get_remove_update(0, LAYER).unwrap());
+        assert!(grid.cells.is_empty());
+        assert!(grid.get_remove_update(0, LAYER).is_none());
+    }
+
+    #[test]
+    fn in_radius_matches_brute_force() {
+        let points = points();
+        let grid = filled(&points);
+        for center in [Vec3Df::new(0.0, 0.0, 0.0), Vec3Df::new(-7.3, 4.1, 2.2), Vec3Df::new(11.0, -8.0, 6.0)] {
+            for radius in [0.7, 2.0, 3.3, 9.0] {
+                let mut found = grid.in_radius(center, radius, LAYER, |id| {points.get(id).copied()});
+                found.sort_by(|(_, dist_1), (_, dist_2)| {dist_1.total_cmp(dist_2)});
+                let expected = brute_force(&points, center, radius);
+                assert_eq!(found.iter().map(|(id, _)| {*id}).collect::<HashSet<usize>>(), expected.iter().map(|(id, _)| {*id}).collect::<HashSet<usize>>());
+            }
+        }
+    }
+
+    #[test]
+    fn k_nearest_matches_brute_force() {
+        let points = points();
+        let grid = filled(&points);
+        for center in [Vec3Df::new(0.0, 0.0, 0.0), Vec3Df::new(-7.3, 4.1, 2.2), Vec3Df::new(40.0, 40.0, 40.0)] {
+            for k in [1, 5, 37, 500] {
+                let found = grid.k_nearest(center, k, f32::INFINITY, LAYER, |id| {points.get(id).copied()});
+                let expected = brute_force(&points, center, f32::INFINITY);
+                assert_eq!(found.len(), k.min(points.len()));
+                // ties can come in any order, distances can't
+                for ((_, found), (_, expected)) in found.iter().zip(expected.iter()) {
+                    assert_eq!(found, expected);
+                }
+            }
+        }
+        let capped = grid.k_nearest(Vec3Df::new(0.0, 0.0, 0.0), 500, 3.0, LAYER, |id| {points.get(id).copied()});
+        assert_eq!(capped.len(), brute_force(&points, Vec3Df::new(0.0, 0.0, 0.0), 3.0).len());
+    }
+
+    #[test]
+    fn along_segment_matches_brute_force() {
+        let points = points();
+        let grid = filled(&points);
+        let segments = [
+            (Vec3Df::new(-10.3, -7.9, -5.7), Vec3Df::new(9.1, 6.2, 5.3)),
+            // same segment the other way
+            (Vec3Df::new(9.1, 6.2, 5.3), Vec3Df::new(-10.3, -7.9, -5.7)),
+            (Vec3Df::new(8.7, 3.3, -4.1), Vec3Df::new(-9.6, -2.4, 5.9)),
+            // along one axis only
+            (Vec3Df::new(-11.1, 0.3, 1.7), Vec3Df::new(10.9, 0.3, 1.7)),
+            (Vec3Df::new(1.3, 7.7, -0.9), Vec3Df::new(1.3, -8.1, -0.9)),
+            // inside one cell
+            (Vec3Df::new(0.3, 0.4, 0.5), Vec3Df::new(1.1, 1.7, 0.2)),
+            // leaving the filled area
+            (Vec3Df::new(-3.3, 2.9, 0.7), Vec3Df::new(-31.7, 25.1, 13.3)),
+        ];
+        for (start, end) in segments {
+            let mut found = grid.along_segment(start, end, LAYER);
+            found.sort();
+            assert_eq!(found, brute_force_segment(&grid, &points, start, end));
+        }
+    }
+
+    #[test]
+    fn empty_grid() {
+        let grid:SpatialHash<usize> = SpatialHash::new(2.0, 3, 4);
+        let center = Vec3Df::new(1.0, -1.0, 0.0);
+        assert!(grid.in_aabb(center, center + Vec3Df::all_ones() * 10.0, LAYER).is_empty());
+        assert!(grid.in_radius(center, 5.0, LAYER, |_| {Some(center)}).is_empty());
+        assert!(grid.k_nearest(center, 3, f32::INFINITY, LAYER, |_| {Some(center)}).is_empty());
+        assert!(grid.along_segment(center, center * -4.0, LAYER).is_empty());
+    }
+
+    #[test]
+    fn zero_radius() {
+        let points = points();
+        let grid = filled(&points);
+        let found = grid.in_radius(points[3], 0.0, LAYER, |id| {points.get(id).copied()});
+        assert!(found.iter().any(|(id, dist)| {*id == 3 && *dist == 0.0}));
+        assert!(found.iter().all(|(id, _)| {points[*id] == points[3]}));
+        assert!(grid.in_radius(Vec3Df::new(100.0, 100.0, 100.0), 0.0, LAYER, |id| {points.get(id).copied()}).is_empty());
+        assert!(grid.k_nearest(points[3], 0, f32::INFINITY, LAYER, |id| {points.get(id).copied()}).is_empty());
+    }
+
+    #[test]
+    fn missing_positions_are_skipped() {
+        let points = points();
+        let grid = filled(&points);
+        // ids past the end stand for entities that are gone
+        let known = &points[..100];
+        let found = grid.in_radius(Vec3Df::new(0.0, 0.0, 0.0), 50.0, LAYER, |id| {known.get(id).copied()});
+        assert_eq!(found.len(), 100);
+        assert!(found.iter().all(|(id, _)| {*id < 100}));
+        let nearest = grid.k_nearest(Vec3Df::new(0.0, 0.0, 0.0), 150, f32::INFINITY, LAYER, |id| {known.get(id).copied()});
+        assert_eq!(nearest.len(), 100);
+    }
+}
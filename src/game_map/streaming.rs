@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+
+use hord3::horde::geometry::vec3d::{Vec3D, Vec3Df};
+
+use super::{get_voxel_pos, persistence::WorldSave, GameMap, Voxel, WorldChunkPos, WorldVoxelPos};
+
+#[derive(Clone)]
+pub struct StreamingConfig {
+    load_radius:i32,
+    unload_radius:i32,
+    vertical_range:Option<(i32, i32)>,
+    max_loads_per_update:usize,
+}
+
+impl StreamingConfig {
+    /// Radii are in chunks, `unload_radius` is clamped to be at least `load_radius` so chunks at the border don't flicker in and out
+    pub fn new(load_radius:i32, unload_radius:i32, vertical_range:Option<(i32, i32)>, max_loads_per_update:usize) -> Self {
+        Self { load_radius, unload_radius: unload_radius.max(load_radius), vertical_range, max_loads_per_update }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StreamingReport {
+    pub loaded:usize,
+    pub generated:usize,
+    pub unloaded:usize,
+    pub saved:usize,
+}
+
+pub struct ChunkStreamer<V:Voxel, F:FnMut(WorldVoxelPos, Vec3D<i32>) -> Vec<V>> {
+    save:WorldSave,
+    generator:F,
+    config:StreamingConfig,
+}
+
+fn chunk_dist_squared(a:WorldChunkPos, b:WorldChunkPos) -> i32 {
+    let diff = a - b;
+    diff.x * diff.x + diff.y * diff.y + diff.z * diff.z
+}
+
+impl<V:Voxel, F:FnMut(WorldVoxelPos, Vec3D<i32>) -> Vec<V>> ChunkStreamer<V, F> {
+    pub fn new(save:WorldSave, generator:F, config:StreamingConfig) -> Self {
+        Self { save, generator, config }
+    }
+    pub fn get_config(&self) -> &StreamingConfig {
+        &self.config
+    }
+    pub fn set_config(&mut self, config:StreamingConfig) {
+        self.config = config;
+    }
+    fn is_in_vertical_range(&self, chunk_pos:WorldChunkPos) -> bool {
+        match self.config.vertical_range {
+            Some((min, max)) => chunk_pos.z >= min && chunk_pos.z < max,
+            None => true
+        }
+    }
+    fn get_wanted_chunks(&self, centers:&Vec<WorldChunkPos>) -> Vec<(i32, WorldChunkPos)> {
+        let radius = self.config.load_radius;
+        let mut seen = HashSet::with_capacity(centers.len() * ((radius * 2 + 1).pow(3) as usize));
+        let mut wanted = Vec::with_capacity(seen.capacity());
+        for center in centers {
+            for x in -radius..=radius {
+                for y in -radius..=radius {
+                    for z in -radius..=radius {
+                        let chunk_pos = *center + Vec3D::new(x, y, z);
+                        if x * x + y * y + z * z <= radius * radius && self.is_in_vertical_range(chunk_pos) && seen.insert(chunk_pos) {
+                            wanted.push(chunk_pos);
+                        }
+                    }
+                }
+            }
+        }
+        let mut wanted = wanted.into_iter().map(|chunk_pos:WorldChunkPos| {
+            (centers.iter().map(|center| {chunk_dist_squared(chunk_pos, *center)}).min().unwrap_or(0), chunk_pos)
+        }).collect::<Vec<(i32, WorldChunkPos)>>();
+        wanted.sort_by_key(|(dist, _)| {*dist});
+        wanted
+    }
+    /// Loads (or generates if there is no save for them) the missing chunks around `focus_points`, closest first,
+    /// then unloads every chunk that is further than `unload_radius` from all of them, saving it first if it was modified
+    pub fn update(&mut self, map:&mut GameMap<V>, focus_points:&Vec<Vec3Df>) -> StreamingReport {
+        let mut report = StreamingReport::default();
+        if focus_points.len() == 0 {
+            return report
+        }
+        let centers = focus_points.iter().map(|point| {map.get_chunk_pos_i(get_voxel_pos(*point))}).collect::<HashSet<WorldChunkPos>>().into_iter().collect::<Vec<WorldChunkPos>>();
+        for (_, chunk_pos) in self.get_wanted_chunks(&centers) {
+            if report.loaded + report.generated >= self.config.max_loads_per_update {
+                break
+            }
+            if !map.does_chunk_exist(chunk_pos) {
+                match self.save.load_chunk::<V>(map.get_dims(), chunk_pos) {
+                    Ok(Some(chunk)) => {
+                        map.insert_chunk(chunk);
+                        report.loaded += 1;
+                    },
+                    Ok(None) => {
+                        let chunk = map.build_chunk_batched(chunk_pos, &mut self.generator);
+                        map.insert_generated_chunk(chunk);
+                        report.generated += 1;
+                    },
+                    Err(error) => println!("couldn't load chunk {} {} {} : {}", chunk_pos.x, chunk_pos.y, chunk_pos.z, error)
+                }
+            }
+        }
+        let unload_dist = self.config.unload_radius * self.config.unload_radius;
+        let to_unload = map.get_all_chunk_pos().into_iter().filter(|chunk_pos| {
+            centers.iter().all(|center| {chunk_dist_squared(*chunk_pos, *center) > unload_dist})
+        }).collect::<Vec<WorldChunkPos>>();
+        for chunk_pos in to_unload {
+            if map.get_chunk_at(chunk_pos).is_some_and(|chunk| {chunk.is_modified_since_save()}) {
+                match self.save.save_chunk(map, chunk_pos) {
+                    Ok(_) => report.saved += 1,
+                    Err(error) => {
+                        // keeping the chunk around is better than losing the changes
+                        println!("couldn't save chunk {} {} {}, keeping it loaded : {}", chunk_pos.x, chunk_pos.y, chunk_pos.z, error);
+                        continue
+                    }
+                }
+            }
+            map.remove_chunk(chunk_pos);
+            report.unloaded += 1;
+        }
+        report
+    }
+    /// Saves every modified chunk without unloading anything, along with the world metadata
+    pub fn save_all(&mut self, map:&mut GameMap<V>) -> std::io::Result<usize> {
+        self.save.save_meta(map)?;
+        let mut saved = 0;
+        for chunk_pos in map.get_all_chunk_pos() {
+            if map.get_chunk_at(chunk_pos).is_some_and(|chunk| {chunk.is_modified_since_save()}) {
+                self.save.save_chunk(map, chunk_pos)?;
+                map.mark_chunk_saved(chunk_pos);
+                saved += 1;
+            }
+        }
+        Ok(saved)
+    }
+}
@@ -0,0 +1,56 @@
+use hord3::horde::geometry::vec3d::{Vec3D, Vec3Df};
+
+use super::{chunk_borders::ChunkBorders, greedy_mesher::add_quad, sky_shading::{ShadedLOD, ShadedTriangles}, ChunkMesher, MapChunk, RenderLayer, Voxel, VoxelType, DIR_MASK, TRIS_INDICES_UVS};
+
+// Translucent voxels (water, glass...) get their own mesh per chunk, drawn from a later instance vec than the opaque chunks.
+// Their faces aren't merged so they can be sorted back to front, from the camera position the job was sent with,
+// the chunks around the camera get remeshed when it moves to another chunk to keep that order roughly right.
+
+impl<V:Voxel> ChunkMesher<V> {
+    pub(super) fn is_translucent(&self, voxel:&V) -> bool {
+        self.voxel_types[voxel.voxel_id()].render_layer() == RenderLayer::Translucent
+    }
+    pub(super) fn get_translucent_lod(&self, chunk:&MapChunk<V>, around:&ChunkBorders<V>) -> Option<ShadedLOD> {
+        let mut quads = Vec::new();
+        let chunk_origin = Vec3D::new(chunk.origin_worldpos.x as f32, chunk.origin_worldpos.y as f32, chunk.origin_worldpos.z as f32);
+        for x in 0..self.dims.chunk_length_i {
+            for y in 0..self.dims.chunk_width_i {
+                for z in 0..self.dims.chunk_height_i {
+                    let local = Vec3D::new(x, y, z);
+                    let voxel = match chunk.get_at_local(local, &self.dims) {
+                        Some(voxel) => voxel,
+                        None => continue
+                    };
+                    if !self.is_translucent(voxel) {
+                        continue
+                    }
+                    let empty_dirs = chunk.get_empty_directions_local(local, &self.dims, around, &self.voxel_types);
+                    let lights = chunk.get_lights_local(local, &self.dims, around);
+                    let skies = chunk.get_sky_lights_local(local, &self.dims, around);
+                    let local_f = Vec3D::new(x as f32, y as f32, z as f32);
+                    for (i, mask) in DIR_MASK.iter().enumerate() {
+                        if empty_dirs & mask == *mask {
+                            let points = TRIS_INDICES_UVS.0[i] + local_f;
+                            let center = (points[0] + points[1] + points[2] + points[3]) * 0.25 + chunk_origin;
+                            quads.push((center.dist(&self.camera_pos), points, self.get_face_shade(lights[i], skies[i]), self.get_face_texture(voxel, i)));
+                        }
+                    }
+                }
+            }
+        }
+        if quads.is_empty() {
+            return None
+        }
+        // farthest first
+        quads.sort_by(|a, b| {b.0.total_cmp(&a.0)});
+        let mut lod = ShadedLOD::new(Vec::with_capacity(quads.len() * 4), Vec::with_capacity(quads.len() * 4), Vec::with_capacity(quads.len() * 4), ShadedTriangles::with_capacity(quads.len() * 2));
+        for (_, points, shade, texture) in quads {
+            add_quad(&mut lod, &points, shade, texture, (1.0, 1.0));
+        }
+        Some(lod)
+    }
+    pub fn with_camera(mut self, camera_pos:Vec3Df) -> Self {
+        self.camera_pos = camera_pos;
+        self
+    }
+}
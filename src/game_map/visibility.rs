@@ -0,0 +1,230 @@
+use std::collections::{HashSet, VecDeque};
+
+use hord3::{defaults::default_rendering::vectorinator_binned::VectorinatorWrite, horde::geometry::vec3d::Vec3D};
+
+use super::{ChunkDims, GameMap, MapChunk, RenderLayer, Voxel, VoxelModel, VoxelType, WorldChunkPos, WorldVoxelPos, EXPLORATION, OPPOSITE};
+
+// Cave culling : every chunk knows which pairs of its six faces (indexed like EXPLORATION) see each other through voxels that don't block sight,
+// found by flood filling them once and linking every face each empty region touches.
+// Each time the camera changes chunk (or the graph of a reached chunk or the loaded area change), chunks are walked breadth first from the camera's one,
+// only going out through a face linked to the one the walk came in by and never towards the camera along any axis, since a line of sight can't.
+// A chunk can be entered once through each of its faces, as what it leads to depends on the face.
+// Chunks the walk doesn't reach get their instances hidden. Unloaded chunks inside the loaded area count as empty,
+// a camera outside of it shows everything. Graphs are recomputed lazily, only for the chunks whose voxels changed how they block sight,
+// and the chunks are only walked again if a graph the last walk went through really is different.
+
+/// Every pair of faces linked, for empty and unloaded chunks
+const ALL_LINKS:u16 = (1 << 15) - 1;
+
+/// Bit of the link between two different faces, 15 pairs
+fn link_bit(face_1:usize, face_2:usize) -> u16 {
+    let (low, high) = (face_1.min(face_2), face_1.max(face_2));
+    1 << (low * (11 - low) / 2 + high - low - 1)
+}
+
+fn are_linked(links:u16, face_1:usize, face_2:usize) -> bool {
+    links & link_bit(face_1, face_2) != 0
+}
+
+/// Opaque full voxels, cutout, translucent and custom ones can be seen through
+fn blocks_sight<VT:VoxelType>(voxel_type:&VT) -> bool {
+    !voxel_type.is_completely_empty() && voxel_type.render_layer() == RenderLayer::Opaque && !matches!(voxel_type.kind_of_model(), VoxelModel::Custom)
+}
+
+/// Faces of the chunk a local position is against, one bit per face
+fn faces_touched(pos:WorldVoxelPos, dims:&ChunkDims) -> u8 {
+    let against = [pos.z == dims.chunk_height_i - 1, pos.x == dims.chunk_length_i - 1, pos.z == 0, pos.x == 0, pos.y == 0, pos.y == dims.chunk_width_i - 1];
+    against.iter().enumerate().fold(0, |faces, (face, touches)| {faces | ((*touches as u8) << face)})
+}
+
+impl<V:Voxel> MapChunk<V> {
+    fn compute_face_links(&self, dims:&ChunkDims, voxel_types:&Vec<V::VT>) -> u16 {
+        let is_open = |voxel:&V| {!blocks_sight(&voxel_types[voxel.voxel_id()])};
+        if self.voxels.palette_all(is_open) {
+            return ALL_LINKS
+        }
+        if self.voxels.palette_all(|voxel| {!is_open(voxel)}) {
+            return 0
+        }
+        let index_of = |pos:WorldVoxelPos| {pos.x as usize + pos.y as usize * dims.chunk_length + pos.z as usize * dims.chunk_slice_area};
+        let mut visited = vec![false ; dims.chunk_slice_area * dims.chunk_height];
+        let mut links = 0;
+        let mut stack = Vec::with_capacity(64);
+        // only regions touching a face matter, so fills start from the borders
+        for z in 0..dims.chunk_height_i {
+            for y in 0..dims.chunk_width_i {
+                for x in 0..dims.chunk_length_i {
+                    let start = Vec3D::new(x, y, z);
+                    if faces_touched(start, dims) == 0 || visited[index_of(start)] || !self.get_at_local(start, dims).is_some_and(is_open) {
+                        continue
+                    }
+                    visited[index_of(start)] = true;
+                    stack.push(start);
+                    let mut faces = 0;
+                    while let Some(pos) = stack.pop() {
+                        faces |= faces_touched(pos, dims);
+                        for dir in EXPLORATION {
+                            let next = pos + dir;
+                            match self.get_at_local(next, dims) {
+                                Some(voxel) => if !visited[index_of(next)] && is_open(voxel) {
+                                    visited[index_of(next)] = true;
+                                    stack.push(next);
+                                },
+                                None => ()
+                            }
+                        }
+                    }
+                    for face_1 in 0..6 {
+                        for face_2 in face_1 + 1..6 {
+                            if faces & (1 << face_1) != 0 && faces & (1 << face_2) != 0 {
+                                links |= link_bit(face_1, face_2);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        links
+    }
+}
+
+impl<V:Voxel> GameMap<V> {
+    /// The graph of the chunk holding `pos` gets recomputed before the next culling
+    pub(super) fn signal_face_links_changed(&mut self, pos:WorldVoxelPos) {
+        let chunk_pos = self.get_chunk_pos_i(pos);
+        let walked = match self.get_chunk_at_mut(chunk_pos) {
+            Some(chunk) => chunk.face_links.take(),
+            None => return
+        };
+        self.check_links_later(chunk_pos, walked);
+    }
+    /// Graphs that were never computed weren't walked through, so they can't change the walk
+    fn check_links_later(&mut self, chunk_pos:WorldChunkPos, walked:Option<u16>) {
+        match walked {
+            Some(links) => if self.is_chunk_seen(chunk_pos) {
+                self.links_to_check.entry(chunk_pos).or_insert(links);
+            },
+            None => ()
+        }
+    }
+    fn in_visibility_area(&self, pos:WorldChunkPos) -> bool {
+        self.visibility_area.is_some_and(|(min, max)| {pos.x >= min.x && pos.y >= min.y && pos.z >= min.z && pos.x <= max.x && pos.y <= max.y && pos.z <= max.z})
+    }
+    /// A chunk got loaded at `chunk_pos`, replacing one whose graph was `old_links` or nothing (None)
+    pub(super) fn signal_chunk_loaded(&mut self, chunk_pos:WorldChunkPos, old_links:Option<Option<u16>>) {
+        if self.in_visibility_area(chunk_pos) {
+            // unloaded chunks were walked through as empty
+            self.check_links_later(chunk_pos, old_links.unwrap_or(Some(ALL_LINKS)));
+        }
+        else {
+            self.visibility_stale = true;
+        }
+    }
+    /// The chunk at `chunk_pos` got unloaded with its graph
+    pub(super) fn signal_chunk_unloaded(&mut self, chunk_pos:WorldChunkPos, links:Option<u16>) {
+        match self.visibility_area {
+            // the loaded area may shrink
+            Some((min, max)) if chunk_pos.x == min.x || chunk_pos.y == min.y || chunk_pos.z == min.z || chunk_pos.x == max.x || chunk_pos.y == max.y || chunk_pos.z == max.z => self.visibility_stale = true,
+            _ => self.check_links_later(chunk_pos, links)
+        }
+    }
+    /// Whether the last walk reached the chunk
+    pub(super) fn is_chunk_seen(&self, chunk_pos:WorldChunkPos) -> bool {
+        self.seen_chunks.as_ref().is_none_or(|seen| {seen.contains(&chunk_pos)})
+    }
+    /// Computes the graphs that may have changed, true if one the last walk went through is different now
+    fn walked_links_changed(&mut self) -> bool {
+        let to_check:Vec<(WorldChunkPos, u16)> = self.links_to_check.drain().collect();
+        to_check.into_iter().any(|(chunk_pos, walked)| {self.face_links_at(chunk_pos) != walked})
+    }
+    /// Whether replacing `old` by `new` can change which faces of its chunk see each other
+    pub(super) fn changes_face_links(&self, old:&V, new:&V) -> bool {
+        blocks_sight(&self.voxel_types[old.voxel_id()]) != blocks_sight(&self.voxel_types[new.voxel_id()])
+    }
+    fn face_links_at(&mut self, chunk_pos:WorldChunkPos) -> u16 {
+        let (dims, voxel_types) = (&self.dims, &self.voxel_types);
+        match self.chunks.get_mut(&chunk_pos) {
+            Some(chunk) => match chunk.face_links {
+                Some(links) => links,
+                None => {
+                    let links = chunk.compute_face_links(dims, voxel_types);
+                    chunk.face_links = Some(links);
+                    links
+                }
+            },
+            None => ALL_LINKS
+        }
+    }
+    /// Chunks the camera could see into, None if it's outside of the loaded area
+    fn visible_chunks(&mut self, camera_chunk:WorldChunkPos) -> Option<HashSet<WorldChunkPos>> {
+        let mut keys = self.chunks.keys();
+        let first = match keys.next() {
+            Some(first) => *first,
+            None => {
+                self.visibility_area = None;
+                return None
+            }
+        };
+        let (min, max) = keys.fold((first, first), |(min, max), pos| {
+            (Vec3D::new(min.x.min(pos.x), min.y.min(pos.y), min.z.min(pos.z)), Vec3D::new(max.x.max(pos.x), max.y.max(pos.y), max.z.max(pos.z)))
+        });
+        self.visibility_area = Some((min, max));
+        let in_area = |pos:WorldChunkPos| {pos.x >= min.x && pos.y >= min.y && pos.z >= min.z && pos.x <= max.x && pos.y <= max.y && pos.z <= max.z};
+        if !in_area(camera_chunk) {
+            return None
+        }
+        let mut visible = HashSet::with_capacity(self.chunks.len());
+        // chunks and the face they were entered by
+        let mut entered_by = HashSet::with_capacity(self.chunks.len());
+        let mut queue = VecDeque::with_capacity(64);
+        visible.insert(camera_chunk);
+        // face the walk came in by, the camera's chunk is seen from inside
+        queue.push_back((camera_chunk, None));
+        while let Some((pos, entered)) = queue.pop_front() {
+            let links = match entered {
+                Some(_) => self.face_links_at(pos),
+                None => ALL_LINKS
+            };
+            for (dir, step) in EXPLORATION.iter().enumerate() {
+                let next = pos + *step;
+                let away = next - camera_chunk;
+                if away.x * step.x + away.y * step.y + away.z * step.z <= 0 || !in_area(next) || entered_by.contains(&(next, OPPOSITE[dir])) {
+                    continue
+                }
+                match entered {
+                    Some(face) => if face == dir || !are_linked(links, face, dir) {
+                        continue
+                    },
+                    None => ()
+                }
+                visible.insert(next);
+                entered_by.insert((next, OPPOSITE[dir]));
+                queue.push_back((next, Some(OPPOSITE[dir])));
+            }
+        }
+        Some(visible)
+    }
+    /// Hides the instances of the chunks the camera can't see, only walks the chunks again when something changed
+    pub(super) fn cull_hidden_chunks<'a>(&mut self, camera_chunk:WorldChunkPos, render_data:&mut VectorinatorWrite<'a>) {
+        if !self.visibility_stale && self.visibility_camera_chunk == Some(camera_chunk) && !self.walked_links_changed() {
+            return
+        }
+        self.visibility_stale = false;
+        self.visibility_camera_chunk = Some(camera_chunk);
+        self.links_to_check.clear();
+        let visible = self.visible_chunks(camera_chunk);
+        let (mesh_vec, translucent_vec) = (self.mesh_vec, self.translucent_mesh_vec);
+        for (pos, chunk) in self.chunks.iter() {
+            let shown = visible.as_ref().is_none_or(|visible| {visible.contains(pos)});
+            match chunk.mesh_instance {
+                Some(index) => render_data.meshes.instances[mesh_vec].get_instance_mut(index).change_visibility(shown),
+                None => ()
+            }
+            match chunk.translucent_instance {
+                Some(index) => render_data.meshes.instances[translucent_vec].get_instance_mut(index).change_visibility(shown && chunk.has_translucent),
+                None => ()
+            }
+        }
+        self.seen_chunks = visible;
+    }
+}
@@ -0,0 +1,109 @@
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use super::{GameMap, Voxel, VoxelType, WorldVoxelPos, EXPLORATION};
+
+// What keeps a voxel in place once something around it changed :
+// - Static voxels never move (terrain, and everything by default)
+// - Gravity voxels (sand) need something under them
+// - NeedsSupport voxels can also hang from their sides, as long as a chain of solid voxels at the same height
+//   reaches something held up from below (or any static voxel) in at most max_overhang steps
+// Voxels that lose their support are replaced by air and queued in detached_voxels,
+// whoever runs the entities turns them into falling ones and puts them back where they land.
+// Unloaded neighbours count as support so chunk borders don't crumble while streaming.
+
+pub const MAX_DETACHED_PER_CHANGE:usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Debug, ToBytes, FromBytes)]
+pub enum VoxelPhysics {
+    Static,
+    Gravity,
+    NeedsSupport { max_overhang:u8 },
+}
+
+const BELOW:usize = 2;
+const ABOVE:usize = 0;
+const HORIZONTAL:[usize ; 4] = [1, 3, 4, 5];
+
+impl<V:Voxel> GameMap<V> {
+    fn physics_at(&self, pos:WorldVoxelPos) -> Option<VoxelPhysics> {
+        self.get_voxel_at(pos).map(|voxel| {self.voxel_types[voxel.voxel_id()].physics()})
+    }
+    /// Solid below, or not loaded
+    fn held_from_below(&self, pos:WorldVoxelPos) -> bool {
+        match self.get_voxel_at(pos + EXPLORATION[BELOW]) {
+            Some(below) => !self.voxel_types[below.voxel_id()].is_completely_empty(),
+            None => true
+        }
+    }
+    fn is_anchor(&self, pos:WorldVoxelPos) -> bool {
+        match self.physics_at(pos) {
+            Some(VoxelPhysics::Static) => self.is_voxel_solid(pos),
+            Some(_) => self.is_voxel_solid(pos) && self.held_from_below(pos),
+            None => true
+        }
+    }
+    pub fn is_voxel_supported(&self, pos:WorldVoxelPos) -> bool {
+        let voxel = match self.get_voxel_at(pos) {
+            Some(voxel) => voxel,
+            None => return true
+        };
+        let voxel_type = &self.voxel_types[voxel.voxel_id()];
+        if voxel_type.is_completely_empty() {
+            return true
+        }
+        match voxel_type.physics() {
+            VoxelPhysics::Static => true,
+            VoxelPhysics::Gravity => self.held_from_below(pos),
+            VoxelPhysics::NeedsSupport { max_overhang } => {
+                if self.held_from_below(pos) {
+                    return true
+                }
+                let mut visited = vec![pos];
+                let mut front = vec![pos];
+                for _ in 0..max_overhang {
+                    let mut next_front = Vec::with_capacity(front.len() * 4);
+                    for current in front {
+                        for dir in HORIZONTAL {
+                            let next = current + EXPLORATION[dir];
+                            if visited.contains(&next) || (!self.is_voxel_solid(next) && self.get_voxel_at(next).is_some()) {
+                                continue
+                            }
+                            if self.is_anchor(next) {
+                                return true
+                            }
+                            visited.push(next);
+                            next_front.push(next);
+                        }
+                    }
+                    front = next_front;
+                }
+                false
+            }
+        }
+    }
+    /// Detaches every voxel that isn't supported anymore after a change at `pos`, and what was resting on them
+    pub(super) fn settle_around(&mut self, pos:WorldVoxelPos) {
+        let mut to_check = vec![pos, pos + EXPLORATION[ABOVE]];
+        for dir in HORIZONTAL {
+            to_check.push(pos + EXPLORATION[dir]);
+        }
+        let mut detached = 0;
+        while detached < MAX_DETACHED_PER_CHANGE && let Some(checked) = to_check.pop() {
+            if self.is_voxel_supported(checked) {
+                continue
+            }
+            let voxel = self.get_voxel_at(checked).unwrap().clone();
+            self.replace_voxel_at(checked, voxel.as_empty());
+            self.detached_voxels.push((checked, voxel));
+            detached += 1;
+            to_check.push(checked + EXPLORATION[ABOVE]);
+            for dir in HORIZONTAL {
+                to_check.push(checked + EXPLORATION[dir]);
+            }
+        }
+    }
+    /// Voxels that came loose since the last call, with where they were
+    pub fn take_detached_voxels(&mut self) -> Vec<(WorldVoxelPos, V)> {
+        std::mem::take(&mut self.detached_voxels)
+    }
+}
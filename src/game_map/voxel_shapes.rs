@@ -0,0 +1,120 @@
+use std::f32::consts::PI;
+
+use hord3::horde::geometry::vec3d::{Vec3D, Vec3Df};
+
+use super::{chunk_borders::ChunkBorders, greedy_mesher::add_quad, sky_shading::ShadedLOD, ChunkMesher, MapChunk, RenderLayer, Voxel, VoxelModel, VoxelType, EXPLORATION, LUT_ROTAT_1, LUT_ROTAT_2, ROTATES_ON_1, TRIS_INDICES_UVS};
+
+// Orientation byte : the 3 low bits pick which side the underside is against (LUT_ROTAT_2),
+// the 2 next ones a quarter turn around the vertical axis applied before that (LUT_ROTAT_1).
+// Sides are indexed like EXPLORATION everywhere.
+//
+// Custom shapes are made of the 8 octants of the voxel, bit x + 2*y + 4*z of vertices_taken (0 being the negative half on that axis).
+// Their sides_empty should have a bit set for every side that isn't completely covered, so neighbours keep their faces against it.
+
+pub const SLAB_OCTANTS:u8 = 0b00001111;
+/// bottom half plus the top of the -x half
+pub const STAIRS_OCTANTS:u8 = 0b01011111;
+pub const SLAB_SIDES_EMPTY:u8 = 0b00111011;
+pub const STAIRS_SIDES_EMPTY:u8 = 0b00110011;
+
+/// World side that the model side `side` ends up facing
+pub fn orient_side(orientation:u8, side:usize) -> usize {
+    let rotat_1 = ((orientation >> 3) & 0b00000011) as usize;
+    let rotat_2 = (orientation & 0b00000111) as usize;
+    let horizontal = match ROTATES_ON_1.iter().position(|rotates| {*rotates as usize == side}) {
+        Some(i) => LUT_ROTAT_1[rotat_1][i] as usize,
+        None => side
+    };
+    LUT_ROTAT_2[rotat_2.min(5)][horizontal] as usize
+}
+
+/// Model side that ends up facing the world side `dir`
+pub fn model_side_facing(orientation:u8, dir:usize) -> usize {
+    (0..6).find(|side| {orient_side(orientation, *side) == dir}).unwrap_or(dir)
+}
+
+/// Orientation byte for something placed while looking towards `yaw`, only the horizontal rotation is used
+pub fn horizontal_orientation(yaw:f32) -> u8 {
+    let quarter = ((yaw / (PI / 2.0)).round() as i32).rem_euclid(4) as u8;
+    quarter << 3
+}
+
+fn octant_cell(octant:usize) -> Vec3D<i32> {
+    Vec3D::new((octant & 1) as i32, ((octant >> 1) & 1) as i32, ((octant >> 2) & 1) as i32)
+}
+
+fn octant_index(cell:Vec3D<i32>) -> usize {
+    (cell.x + cell.y * 2 + cell.z * 4) as usize
+}
+
+/// Octant mask once the voxel is oriented
+pub fn rotate_octants(octants:u8, orientation:u8) -> u8 {
+    let axis_x = EXPLORATION[orient_side(orientation, 1)];
+    let axis_y = EXPLORATION[orient_side(orientation, 5)];
+    let axis_z = EXPLORATION[orient_side(orientation, 0)];
+    let mut rotated = 0;
+    for octant in 0..8 {
+        if octants & (1 << octant) != 0 {
+            // cells as -1/1 so the rotation happens around the center of the voxel
+            let cell = octant_cell(octant) * 2 - Vec3D::all_ones();
+            let turned = axis_x * cell.x + axis_y * cell.y + axis_z * cell.z;
+            rotated |= 1 << octant_index(Vec3D::new((turned.x + 1) / 2, (turned.y + 1) / 2, (turned.z + 1) / 2));
+        }
+    }
+    rotated
+}
+
+impl<V:Voxel> ChunkMesher<V> {
+    pub(super) fn get_face_texture(&self, voxel:&V, dir:usize) -> u32 {
+        let voxel_type = &self.voxel_types[voxel.voxel_id()];
+        match voxel_type.kind_of_model() {
+            VoxelModel::WrappedTexture(texture) => texture as u32,
+            VoxelModel::SpecifiedTexture(textures) => textures[model_side_facing(voxel.orientation(), dir)] as u32,
+            VoxelModel::Custom => voxel_type.easy_texture() as u32,
+        }
+    }
+    pub(super) fn is_custom(&self, voxel:&V) -> bool {
+        match self.voxel_types[voxel.voxel_id()].kind_of_model() {
+            VoxelModel::Custom => true,
+            _ => false
+        }
+    }
+    /// Half-size quads for every visible side of the filled octants of a custom voxel
+    pub(super) fn add_custom_voxel(&self, lod:&mut ShadedLOD, chunk:&MapChunk<V>, around:&ChunkBorders<V>, local:Vec3D<i32>) {
+        let voxel = match chunk.get_at_local(local, &self.dims) {
+            Some(voxel) => voxel,
+            None => return
+        };
+        let octants = rotate_octants(self.voxel_types[voxel.voxel_id()].vertices_taken(), voxel.orientation());
+        let voxels_around = chunk.get_voxels_around(local, around, &self.dims);
+        let lights = chunk.get_lights_local(local, &self.dims, around);
+        let skies = chunk.get_sky_lights_local(local, &self.dims, around);
+        let local_f = Vec3D::new(local.x as f32, local.y as f32, local.z as f32);
+        for octant in 0..8 {
+            if octants & (1 << octant) == 0 {
+                continue
+            }
+            let cell = octant_cell(octant);
+            let offset = Vec3D::new(cell.x as f32 - 0.5, cell.y as f32 - 0.5, cell.z as f32 - 0.5) * 0.5;
+            for dir in 0..6 {
+                let next = cell + EXPLORATION[dir];
+                let inside = next.x >= 0 && next.x < 2 && next.y >= 0 && next.y < 2 && next.z >= 0 && next.z < 2;
+                if inside {
+                    if octants & (1 << octant_index(next)) != 0 {
+                        continue
+                    }
+                }
+                else {
+                    match voxels_around[dir] {
+                        Some(neighbour) => if self.voxel_types[neighbour.voxel_id()].full_coming_from(dir as u8, neighbour.orientation()) && self.voxel_types[neighbour.voxel_id()].render_layer() == RenderLayer::Opaque {
+                            continue
+                        },
+                        None => ()
+                    }
+                }
+                let points = TRIS_INDICES_UVS.0[dir] * (Vec3Df::all_ones() * 0.5) + (local_f + offset);
+                add_quad(lod, &points, self.get_face_shade(lights[dir], skies[dir]), self.get_face_texture(voxel, dir), (0.5, 0.5));
+            }
+        }
+    }
+}
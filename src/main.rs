@@ -1,23 +1,26 @@
 #![feature(portable_simd)]
 #![feature(int_roundings)]
 #![feature(mpmc_channel)]
-use std::{collections::HashMap, f32::consts::PI, path::PathBuf, simd::Simd, sync::{atomic::{AtomicUsize, Ordering}, mpmc, Arc, RwLock}, thread, time::{Duration, Instant}};
+use std::{collections::HashMap, f32::consts::PI, io, path::PathBuf, simd::Simd, sync::{atomic::{AtomicUsize, Ordering}, mpmc, Arc, RwLock}, thread, time::{Duration, Instant}};
 
 use game_entity::colliders::AABB;
 use cosmic_text::{Color, Font, Metrics};
 use crossbeam::channel::unbounded;
 use cutscene::{camera_movement::{CameraMovement, CameraMovementDuration, CameraMovementElement, CameraSequence}, demo_cutscene::{get_demo_cutscene, get_empty_cutscene}, game_shader::GameShader, real_demo_cutscene::get_real_demo_cutscene, write_in_the_air::get_positions_of_air_written_text, written_texture::get_written_texture_buffer};
 use day_night::DayNight;
-use game_3d_models::{clustered_ent_mesh, grey_sphere_mesh, lit_selection_cube, second_spread_out_ent_mesh, simple_line, sphere_mesh, spread_out_ent_mesh, textured_sphere_mesh, wireframe_sphere_mesh, xyz_mesh};
-use game_engine::{CoolGameEngineBase, CoolVoxel, CoolVoxelType, ExtraData};
-use game_entity::{Collider, GameEntityVec, Movement, NewGameEntity, StaticCollider, StaticGameEntity, StaticMeshInfo, StaticMovement, StaticStats, Stats};
+use falling_voxels::{spawn_falling_voxels, FALLING_VOXEL_HALF_SIZE};
+use game_3d_models::{clustered_ent_mesh, falling_voxel_mesh, grey_sphere_mesh, lit_selection_cube, second_spread_out_ent_mesh, simple_line, sphere_mesh, spread_out_ent_mesh, textured_sphere_mesh, wireframe_sphere_mesh, xyz_mesh};
+use game_engine::{world_tick, CoolGameEngineBase, CoolVoxel, CoolVoxelType, ExtraData};
+use game_entity::{Collider, GameEntityVec, ENTITY_MAX_HEALTH, Movement, NewGameEntity, StaticCollider, StaticGameEntity, StaticMeshInfo, StaticMovement, StaticStats, Stats};
 use game_input_handler::GameInputHandler;
-use game_map::{get_f64_pos, get_float_pos, light_spreader::{LightPos, LightSpread}, ChunkDims, GameMap, VoxelLight};
+use game_map::{get_f64_pos, get_float_pos, meshing_pool::MESHING_POOL, persistence::WorldSave, streaming::{ChunkStreamer, StreamingConfig}, ChunkDims, GameMap, VoxelLight, VoxelType};
 use game_tasks::{GameTask, GameTaskTaskHandler, GameUserEvent};
 use gui_elements::{list_choice::get_list_choice, number_config::get_number_config};
 use hord3::{defaults::{default_frontends::minifb_frontend::MiniFBWindow, default_rendering::vectorinator_binned::{meshes::{Mesh, MeshID, MeshLODS, MeshLODType}, rendering_spaces::ViewportData, shaders::NoOpShader, textures::{argb_to_rgb, rgb_to_argb, TextureSetID}, triangles::{color_u32_to_u8_simd, simd_rgb_to_argb}, Vectorinator}, default_ui::simple_ui::{SimpleUI, UIDimensions, UIElement, UIElementBackground, UIElementContent, UIElementID, UIEvent, UIUnit, UIUserAction, UIVector}}, horde::{frontend::{HordeWindowDimensions, WindowingHandler}, game_engine::{entity::Renderable, world::{WorldComputeHandler, WorldHandler}}, geometry::{plane::EquationPlane, rotation::{Orientation, Rotation}, vec3d::{Vec3D, Vec3Df}}, rendering::{camera::Camera, framebuffer::HordeColorFormat}, scheduler::{HordeScheduler, HordeTaskQueue, HordeTaskSequence, SequencedTask}, sound::{SoundRequest, WaveIdentification, WavePosition, WaveRequest, WaveSink, Waves}}};
 use noise::{NoiseFn, Perlin, Seedable};
-use tile_editor::{get_tile_voxels, TileEditorData};
+use tile_editor::TileEditorData;
+use voxel_registry::{get_voxel_types, VOXEL_REGISTRY};
+use world_gen::{generator_from_args, ROCK};
 
 use crate::{game_entity::{actions::{Action, ActionKind, ActionSource, ActionTimer, ActionsEvent, ActionsUpdate, StaticGameActions}, director::{llm_director::LLMDirector, Director, DirectorKind, StaticDirector}, planner::StaticPlanner}, game_map::get_voxel_pos, proxima_link::ProximaLink};
 
@@ -34,57 +37,48 @@ pub mod game_tiles;
 pub mod cutscene;
 pub mod day_night;
 pub mod proxima_link;
+pub mod world_gen;
+pub mod fluids;
+pub mod falling_voxels;
+pub mod traps;
+pub mod passages;
+pub mod schematic;
+pub mod vox;
+pub mod voxel_registry;
 
-fn main() {
-    let mut world = GameMap::new(100, ChunkDims::new(8, 8, 8), get_tile_voxels(), (255,255,255), 1);
-    let mut perlin = Perlin::new().set_seed(13095);
-    let mut world_height = 15.0;
-    let mut water_level = 10.0;
-    let start = Vec3D::new(-30, -20, -2);
-    let end = Vec3D::new(20, 20, 20);
+/// Frames between two passes packing back the chunks that were edited, see game_map::chunk_storage
+const CHUNK_COMPACTION_INTERVAL:usize = 300;
 
-    let mut ground_at = vec![0; ((end.x - start.x) * 8 * (end.y - start.y) * 8) as usize];
-    let length_f64 = ((end.x - start.x) * 8 ) as f64;
-    world.generate_chunks(start, end, &mut |pos| {
-        let pos_3D = (get_f64_pos(pos) * 0.01);
-        let value_2D = (perlin.get([pos_3D.x, pos_3D.y]) + 1.0) * 0.5;
-        let local_world_height = world_height - (((pos.x - start.x) * 8) as f64/length_f64) * world_height;
-        let actual_height = local_world_height + world_height * value_2D * 2.0;
-        if (pos.z as f64) < actual_height || (pos.z as f64) < water_level {
-            let ground_pos = (pos.x - (start.x * 8) + (pos.y - (start.y * 8)) * ((end.y - start.y) * 8)) as usize;
-            if (pos.z as f64) < water_level {
-                if ground_at[ground_pos] < pos.z {
-                    ground_at[ground_pos] = water_level as i32;
-                }
-                CoolVoxel {voxel_type:7, orient:0, light:VoxelLight::random_light(), extra_voxel_data:None}
-            }
-            else {
-                if ground_at[ground_pos] < pos.z {
-                    ground_at[ground_pos] = pos.z;
-                }
-                CoolVoxel {voxel_type:1 + ((actual_height - water_level)/(6.0*world_height * (1.0/6.0))).clamp(0.0, 5.99) as u16, orient:0, light:VoxelLight::random_light(), extra_voxel_data:None}
-            }
-        } else {
-            CoolVoxel {voxel_type:0, orient:0, light:VoxelLight::zero_light(), extra_voxel_data:None}
+fn main() {
+    let generator = generator_from_args(&std::env::args().collect());
+    let world_save = WorldSave::new(PathBuf::from(format!("saves/{}_{}", generator.name(), generator.seed())));
+    let mut world = match world_save.load_world(100, get_voxel_types(), 1) {
+        Ok(world) => world,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => GameMap::new(100, ChunkDims::new(8, 8, 8), get_voxel_types(), (24,24,24), 1),
+        // generating over a save that can't be read would overwrite it
+        Err(error) => {
+            eprintln!("couldn't load the world saved in {} : {}", world_save.get_folder().display(), error);
+            std::process::exit(1)
         }
-    }
-    );
-    let mut world_clone = world.clone();
-    let mut spare_world = world.clone();
-    {
-        world_clone.change_mesh_vec(10);
-        world_clone.set_min_light_levels((50,50,50));
-        for i in 0..1 {
-            let (x,y) = (fastrand::i32((start.x * 8)..(end.x * 8)), fastrand::i32((start.y * 8)..(end.y * 8)));
-            let light_source = LightPos::new(Vec3D::new(x, y, ground_at[(x - (start.x * 8) + (y - (start.y * 8)) * ((end.y - start.y) * 8)) as usize] + 1), VoxelLight::slightly_less_random_light());
-            let total_light_spread = LightSpread::calc_max_spread(&world_clone, light_source).get_all_spread();
-            for light_pos in total_light_spread {
-                world_clone.get_voxel_at_mut(light_pos.pos()).unwrap().light = light_pos.value().merge_with_other(&world_clone.get_voxel_at(light_pos.pos()).unwrap().light);
-            }
-            println!("light {i} done !");
+    };
+    let world_was_loaded = world.get_all_chunk_pos().len() > 0;
+    let (start, end) = generator.chunk_extent(world.get_chunk_dims_vector()).unwrap_or((Vec3D::new(-30, -20, -2), Vec3D::new(20, 20, 20)));
+    if !world_was_loaded {
+        world.generate_chunks_batched(start, end, &mut |origin, size| {generator.generate_chunk_voxels(origin, size)});
+        world.compute_skylight();
+        world.light_all_emissive_voxels();
+        let (x,y) = (fastrand::i32((start.x * 8)..(end.x * 8)), fastrand::i32((start.y * 8)..(end.y * 8)));
+        let light_pos = world.get_ceiling_at(Vec3D::new(x, y, end.z * 4), end.z * 4) + Vec3D::new(0, 0, 1);
+        world.add_light_source(light_pos, VoxelLight::slightly_less_random_light());
+        match world_save.save_world(&world) {
+            Ok(()) => (),
+            Err(error) => println!("couldn't save the world : {}", error)
         }
     }
-    
+    let unpacked_chunks = world.compact_chunks();
+    let report = world.storage_report();
+    println!("chunk storage : {} chunks ({} uniform, {} paletted, {} recompacted after generation), {} KiB as plain voxels, {} KiB used", report.chunks, report.uniform, report.paletted, unpacked_chunks, report.bytes_unpacked / 1024, report.bytes_used / 1024);
+    let mut streamer = ChunkStreamer::new(world_save.clone(), move |origin, size| {generator.generate_chunk_voxels(origin, size)}, StreamingConfig::new(10, 14, Some((start.z, end.z)), 16));
     let entity_vec = GameEntityVec::new(1000);
     {
         let mut writer = entity_vec.get_write();
@@ -127,7 +121,7 @@ fn main() {
 
         for i in 0..1 {
             let pos = Vec3D::new((fastrand::f32() - 0.5) * 2.0 * 150.0, (fastrand::f32() - 0.5) * 2.0 * 150.0, 150.0);
-            writer.new_ent(NewGameEntity::new(Movement{against_wall:false, touching_ground:false,pos:pos, speed:Vec3D::zero(), orient:Orientation::zero(), rotat:Rotation::from_orientation(Orientation::zero())}, Stats {static_type_id:1, health:0, damage:0, stamina:0, ground_speed:0.2, jump_height:1.0}, Collider{team:0, collider:AABB::new(pos - Vec3D::all_ones() * 0.5, pos + Vec3D::all_ones() * 0.5)}, Director::new_with_random_name(DirectorKind::LLM(LLMDirector::new_with_goals(fastrand::choice(test_goals.iter()).unwrap().clone())))));
+            writer.new_ent(NewGameEntity::new(Movement{against_wall:false, touching_ground:false,pos:pos, speed:Vec3D::zero(), orient:Orientation::zero(), rotat:Rotation::from_orientation(Orientation::zero())}, Stats {static_type_id:1, health:ENTITY_MAX_HEALTH, damage:0, stamina:0, ground_speed:0.2, jump_height:1.0, keys:Vec::new()}, Collider{team:0, collider:AABB::new(pos - Vec3D::all_ones() * 0.5, pos + Vec3D::all_ones() * 0.5)}, Director::new_with_random_name(DirectorKind::LLM(LLMDirector::new_with_goals(fastrand::choice(test_goals.iter()).unwrap().clone())))));
             //writer.new_ent(NewGameEntity::new(Movement{against_wall:false, touching_ground:false,pos:pos, speed:Vec3D::zero(), orient:Orientation::zero(), rotat:Rotation::from_orientation(Orientation::zero())}, Stats {static_type_id:1, health:0, damage:0, stamina:0, ground_speed:0.2, jump_height:1.0}, Collider{team:0, collider:AABB::new(pos - Vec3D::all_ones() * 0.5, pos + Vec3D::all_ones() * 0.5)}, Director::new_with_random_name(DirectorKind::LLM(LLMDirector::new_with_goals(test_goals[i].clone())))));
         }
 
@@ -143,6 +137,10 @@ fn main() {
     };
     
     let entity_vec_2 = GameEntityVec::new(1000);
+    {
+        let mut writer = entity_vec_2.get_write();
+        writer.new_sct(StaticGameEntity{planner:StaticPlanner{},director:StaticDirector {kind:DirectorKind::FallingVoxel(None)},actions:StaticGameActions {base_actions:Vec::with_capacity(8)},movement:StaticMovement{}, mesh_info:StaticMeshInfo{mesh_id:MeshID::Named("FALLING_VOXEL_MESH".to_string()),mesh_data:falling_voxel_mesh(get_voxel_types()[*ROCK as usize].easy_texture() as u32)}, stats:StaticStats{}, collider:StaticCollider{init_aabb:AABB::new(-Vec3D::all_ones()*FALLING_VOXEL_HALF_SIZE, Vec3D::all_ones()*FALLING_VOXEL_HALF_SIZE)}});
+    }
     
     let windowing = WindowingHandler::new::<MiniFBWindow>(HordeWindowDimensions::new(1280, 720), HordeColorFormat::ARGB8888);
     let framebuf = windowing.get_outside_framebuf();
@@ -179,102 +177,13 @@ fn main() {
     {
         println!("START TEXTURE");
         let mut writer = vectorinator.get_write();
-        writer.textures.add_set_with_many_textures(
-            "Testing_Texture".to_string(),
-            vec![
-                (
-                    "neige.png".to_string(),
-                    1,
-                    None
-                ),
-            ]
-        );
-        writer.textures.add_set_with_many_textures(
-            "Testing_Texture_2".to_string(),
-            vec![
-                (
-                    "sable.png".to_string(),
-                    1,
-                    None
-                ),
-            ]
-        );
-        writer.textures.add_set_with_many_textures(
-            "Testing_Texture_3".to_string(),
-            vec![
-                (
-                    "terre_herbe.png".to_string(),
-                    1,
-                    None
-                )
-            ]
-        );
-        writer.textures.add_set_with_many_textures(
-            "Testing_Texture_4".to_string(),
-            vec![
-                (
-                    "terre_cail.png".to_string(),
-                    1,
-                    None
-                )
-            ]
-        );
-        writer.textures.add_set_with_many_textures(
-            "Testing_Texture_5".to_string(),
-            vec![
-                (
-                    "terre.png".to_string(),
-                    1,
-                    None
-                )
-            ]
-        );
-        writer.textures.add_set_with_many_textures(
-            "Testing_Texture_6".to_string(),
-            vec![
-                (
-                    "roche.png".to_string(),
-                    1,
-                    None
-                )
-            ]
-        );
-        writer.textures.add_set_with_many_textures(
-            "Testing_Texture_7".to_string(),
-            vec![
-                (
-                    "eau.png".to_string(),
-                    1,
-                    None
-                )
-            ]
-        );
-        writer.textures.add_set_with_many_textures(
-            "Testing_Texture_8".to_string(),
-            vec![
-                (
-                    "eau_prof.png".to_string(),
-                    1,
-                    None
-                )
-            ]
-        );
-        writer.textures.add_set_with_many_textures(
-            "Testing_Texture_8".to_string(),
-            vec![
-                (
-                    "metal_0.png".to_string(),
-                    1,
-                    None
-                )
-            ]
-        );
+        VOXEL_REGISTRY.register_textures(&mut writer.textures);
         writer.textures.add_generated_texture_set("Testing_text_texture".to_string(), get_written_texture_buffer("TEST\nLOL".to_string(), Metrics::new(300.0, 310.0), "don't_care".to_string(), vec![rgb_to_argb((0,200,0)) ; 1000*1000], 1000, 1000, Color(rgb_to_argb((255,255,255))), (0,0)), 1000, 1000);
         writer.textures.add_generated_texture_set("FULLRED".to_string(), get_written_texture_buffer("".to_string(), Metrics::new(300.0, 310.0), "don't_care".to_string(), vec![rgb_to_argb((255,0,0)) ; 1000*1000], 1000, 1000, Color(rgb_to_argb((255,255,255))), (0,0)), 1000, 1000);
         writer.textures.add_generated_texture_set("FULLGREEN".to_string(), get_written_texture_buffer("".to_string(), Metrics::new(300.0, 310.0), "don't_care".to_string(), vec![rgb_to_argb((0,255,0)) ; 1000*1000], 1000, 1000, Color(rgb_to_argb((255,255,255))), (0,0)), 1000, 1000);
         writer.textures.add_generated_texture_set("FULLBLUE".to_string(), get_written_texture_buffer("".to_string(), Metrics::new(300.0, 310.0), "don't_care".to_string(), vec![rgb_to_argb((0,0,255)) ; 1000*1000], 1000, 1000, Color(rgb_to_argb((255,255,255))), (0,0)), 1000, 1000);
         writer.textures.add_generated_texture_set("FULLWHITE".to_string(), get_written_texture_buffer("".to_string(), Metrics::new(300.0, 310.0), "don't_care".to_string(), vec![rgb_to_argb((255,255,255)) ; 1000*1000], 1000, 1000, Color(rgb_to_argb((255,255,255))), (0,0)), 1000, 1000);
-        let text_herbe = writer.textures.get_text_with_id(match writer.textures.get_id_with_name(&"Testing_Texture_3".to_string()).unwrap() {TextureSetID::ID(id) => id, _ => panic!()});
+        let text_herbe = writer.textures.get_text_with_id(match writer.textures.get_id_with_name(&"terre_herbe.png".to_string()).unwrap() {TextureSetID::ID(id) => id, _ => panic!()});
         let mut datas = Vec::with_capacity(200);
         for i in 0..200 {
             let mut new_data = text_herbe.get_mip_map(0).data.clone();
@@ -380,14 +289,6 @@ fn main() {
 
         475
     );
-    let mut prev_night_status = false;
-    {
-        let mut writer = vectorinator.get_write();
-        world_clone.set_min_light_levels((50, 50, 50));
-        world_clone.change_mesh_vec(10);
-        world_clone.do_render_changes(&mut writer);
-        world_clone.make_meshes_invisible(&mut writer);
-    }   
     // let mut cutscene = get_real_demo_cutscene(&viewport_data);
     for i in 0..75000 {
         //println!("{i}");
@@ -395,15 +296,7 @@ fn main() {
         let mut start = Instant::now();
         input_handler.update_keyboard();
         let (new_fog_col, new_normal_vec, new_night_state) = day_night.get_next_color();
-        //if prev_night_status != new_night_state {
-        //    let mut writer = vectorinator.get_write();
-        //    spare_world = world_handler.world.read().unwrap().clone();
-        //    spare_world.make_meshes_invisible(&mut writer);
-        //    world_clone.make_meshes_visible(&mut writer);
-        //    world_clone.set_grid = spare_world.set_grid.clone();
-        //    *world_handler.world.write().unwrap() = world_clone.clone();
-        //}
-        //prev_night_status = new_night_state;
+        world_handler.world.write().unwrap().set_sky_brightness(day_night.get_sky_brightness());
         let new_camera = {
             let mut writer = vectorinator.get_write();
             //vectorinator.shader_data.do_normals.store(!new_night_state, Ordering::Relaxed);
@@ -440,9 +333,15 @@ fn main() {
                     Err(_) => break
                 }
             }
+            world_tick(&first_ent, &world, engine.extra_data.tick.load(Ordering::Relaxed));
         }
         
         
+        if i % 10 == 0 {
+            let mut focus_points = engine.entity_1.get_read().movement.iter().map(|movement| {movement.pos}).collect::<Vec<Vec3Df>>();
+            focus_points.push(new_camera.pos);
+            streamer.update(&mut world_handler.world.write().unwrap(), &focus_points);
+        }
         tile_editor.cam = new_camera;
         match user_events.try_recv() {
             Ok(evt) => {
@@ -452,6 +351,17 @@ fn main() {
         }
         tile_editor.do_mouse_handling(&mut world_handler.world.write().unwrap());
         tile_editor.handle_keyboard(&input_handler, &mut world_handler.world.write().unwrap());
+        {
+            let world = WorldComputeHandler::from_world_handler(&engine.world);
+            for event in tile_editor.take_world_events() {
+                world.tunnels.send_event(event);
+            }
+        }
+        let detached = world_handler.world.write().unwrap().take_detached_voxels();
+        spawn_falling_voxels(&engine, detached);
+        if i % CHUNK_COMPACTION_INTERVAL == 0 {
+            world_handler.world.write().unwrap().compact_chunks();
+        }
         tile_editor.do_rendering(&vectorinator, &world_handler.world.read().unwrap());
         scheduler.initialise(queue.clone());
         scheduler.tick();
@@ -461,5 +371,10 @@ fn main() {
             thread::sleep(Duration::from_secs_f64(1.0/75.0 - Instant::now().checked_duration_since(start).unwrap().as_secs_f64()));
         }
     }
+    match streamer.save_all(&mut world_handler.world.write().unwrap()) {
+        Ok(saved) => println!("saved {} chunks", saved),
+        Err(error) => println!("couldn't save the world : {}", error)
+    }
+    MESHING_POOL.shutdown();
     scheduler.end_threads();
 }
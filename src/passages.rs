@@ -0,0 +1,121 @@
+use hord3::horde::game_engine::world::WorldComputeHandler;
+
+use crate::{game_engine::{ActivationType, CoolGameEngineTID, CoolVoxel, PassageData}, game_entity::{GameEntityVecRead, Stats}, game_map::{get_voxel_center, GameMap, GameMapEvent, WorldVoxelPos, EXPLORATION}, traps::activate_trap};
+
+// Passages (doors, gates...) are voxels with ExtraVoxelData::IsPassage, opening or closing one swaps its voxel type
+// between the passage's closed and open types, so an open passage is just an empty voxel that still remembers it's a door.
+// Passages that open_with_adjacent flood to every adjacent voxel of the same passage (same key and types),
+// locked ones (key_id != 0) need the key in the entity's Stats.
+// Changes are sent as GameMapEvent::UpdateVoxelAt so they're remeshed and synced like any other voxel change.
+
+/// How far from the voxel center an entity can reach to interact
+pub const INTERACT_RANGE:f32 = 2.0;
+/// How many voxels a single passage can span at most
+pub const MAX_PASSAGE_SIZE:usize = 64;
+/// Extra pathfinding cost of going through a closed passage, the time it takes to open it
+pub const CLOSED_PASSAGE_COST:f64 = 2.0;
+
+/// Every voxel opened or closed along with the one at `pos`
+fn passage_voxels(world:&GameMap<CoolVoxel>, pos:WorldVoxelPos, passage:&PassageData) -> Vec<WorldVoxelPos> {
+    let mut found = vec![pos];
+    if !passage.opens_with_adjacent() {
+        return found
+    }
+    let mut to_explore = vec![pos];
+    while found.len() < MAX_PASSAGE_SIZE && let Some(current) = to_explore.pop() {
+        for dir in EXPLORATION {
+            let next = current + dir;
+            if found.contains(&next) {
+                continue
+            }
+            match world.get_voxel_at(next).and_then(|voxel| {voxel.get_passage()}) {
+                Some(other) => if other.same_passage(passage) && found.len() < MAX_PASSAGE_SIZE {
+                    found.push(next);
+                    to_explore.push(next);
+                },
+                None => ()
+            }
+        }
+    }
+    found
+}
+
+pub fn can_open(passage:&PassageData, stats:&Stats) -> bool {
+    stats.has_key(passage.get_key_id())
+}
+
+/// Opens or closes the whole passage at `pos`, returns false if there's no passage there or it's already in that state
+pub fn set_passage_open(world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>, pos:WorldVoxelPos, open:bool) -> bool {
+    let passage = match world.world.get_voxel_at(pos).and_then(|voxel| {voxel.get_passage()}) {
+        Some(passage) => passage,
+        None => return false
+    };
+    if passage.is_open() == open {
+        return false
+    }
+    for voxel_pos in passage_voxels(&world.world, pos, passage) {
+        match world.world.get_voxel_at(voxel_pos).and_then(|voxel| {voxel.with_passage_open(open)}) {
+            Some(changed) => world.tunnels.send_event(GameMapEvent::UpdateVoxelAt(voxel_pos, changed)),
+            None => ()
+        }
+    }
+    true
+}
+
+pub fn in_reach(agent_id:usize, pos:WorldVoxelPos, first_ent:&GameEntityVecRead<CoolGameEngineTID>) -> bool {
+    first_ent.movement[agent_id].pos.dist(&get_voxel_center(pos)) <= INTERACT_RANGE
+}
+
+/// Opens the passage at `pos` if the agent can, returns whether it's open afterwards
+pub fn open_passage(agent_id:usize, pos:WorldVoxelPos, first_ent:&GameEntityVecRead<CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>) -> bool {
+    match world.world.get_voxel_at(pos).and_then(|voxel| {voxel.get_passage()}) {
+        Some(passage) => if passage.is_open() {
+            true
+        }
+        else if can_open(passage, &first_ent.stats[agent_id]) {
+            set_passage_open(world, pos, true)
+        }
+        else {
+            false
+        },
+        None => false
+    }
+}
+
+/// Toggles the passage at `pos`, or fires the interact trap there. Returns false if nothing happened
+pub fn interact_at(agent_id:usize, pos:WorldVoxelPos, first_ent:&GameEntityVecRead<CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>) -> bool {
+    let voxel = match world.world.get_voxel_at(pos) {
+        Some(voxel) => voxel,
+        None => return false
+    };
+    match voxel.get_passage() {
+        Some(passage) => return can_open(passage, &first_ent.stats[agent_id]) && set_passage_open(world, pos, !passage.is_open()),
+        None => ()
+    }
+    match voxel.get_trap() {
+        Some(trap) => if *trap.get_activation_type() == ActivationType::PlayerInteract && trap.is_ready() {
+            activate_trap(pos, first_ent, world, &mut Vec::new());
+            true
+        }
+        else {
+            false
+        },
+        None => false
+    }
+}
+
+/// Extra cost of walking into `pos` for someone with those stats, None if it can't be walked into
+pub fn walk_cost(world:&GameMap<CoolVoxel>, pos:WorldVoxelPos, stats:&Stats) -> Option<f64> {
+    if !world.is_voxel_solid(pos) {
+        return Some(0.0)
+    }
+    match world.get_voxel_at(pos)?.get_passage() {
+        Some(passage) => if !passage.is_open() && can_open(passage, stats) {
+            Some(CLOSED_PASSAGE_COST)
+        }
+        else {
+            None
+        },
+        None => None
+    }
+}
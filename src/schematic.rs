@@ -0,0 +1,173 @@
+use std::{fs, io, path::Path};
+
+use hord3::horde::geometry::vec3d::Vec3D;
+use to_from_bytes::ToBytes;
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use crate::{game_engine::CoolVoxel, game_map::{persistence::{decode_blob, seal_blob}, voxel_shapes::orient_side, GameMap, Voxel, VoxelType, WorldVoxelPos, EXPLORATION}};
+
+// A schematic is a copied region of voxels (orientation and extra data included), stored relative to the origin it was copied from,
+// so pasting puts that origin where asked. Rotations are quarter turns around the vertical axis (from +x towards +y, like LUT_ROTAT_1),
+// mirrors flip one axis. Both move the voxels around the origin and rewrite the orientation bytes so shaped and oriented voxels
+// (stairs, turrets...) still face the right way ; a mirrored voxel can't really be mirrored, it gets the rotation keeping its top side
+// and as many other sides as possible.
+// Prefabs (houses, towers...) are saved as files so the editor and agent builders can share them.
+// A .hschem file is the magic, the version and the voxels sealed with their length and checksum (see persistence::seal_blob).
+
+const SCHEMATIC_MAGIC:[u8 ; 4] = *b"HSCH";
+const SCHEMATIC_VERSION:u16 = 2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MirrorAxis {
+    X,
+    Y,
+    Z
+}
+
+#[derive(Clone, ToBytes, FromBytes)]
+pub struct Schematic {
+    /// positions relative to the origin
+    voxels:Vec<(WorldVoxelPos, CoolVoxel)>,
+}
+
+/// Index in EXPLORATION of a unit direction
+fn side_of(dir:Vec3D<i32>) -> usize {
+    EXPLORATION.iter().position(|side| {*side == dir}).unwrap()
+}
+
+/// Orientation byte that best turns the sides of a voxel oriented with `orientation` the way `map_dir` turns directions
+fn transform_orientation(orientation:u8, map_dir:&impl Fn(Vec3D<i32>) -> Vec3D<i32>) -> u8 {
+    let wanted:[usize ; 6] = std::array::from_fn(|side| {side_of(map_dir(EXPLORATION[orient_side(orientation, side)]))});
+    let mut best = (orientation, 0);
+    for rotat_1 in 0..4_u8 {
+        for rotat_2 in 0..6_u8 {
+            let candidate = (rotat_1 << 3) | rotat_2;
+            // keeping the top side matters more than the others, for mirrors that can't keep everything
+            let score = (0..6).filter(|side| {orient_side(candidate, *side) == wanted[*side]}).count() * 2 + (orient_side(candidate, 0) == wanted[0]) as usize;
+            if score > best.1 {
+                best = (candidate, score);
+            }
+        }
+    }
+    best.0
+}
+
+impl Schematic {
+    /// Copies the box between `start` and `end` (both included), with positions relative to `origin`. Unloaded voxels are left out
+    pub fn copy_from(map:&GameMap<CoolVoxel>, start:WorldVoxelPos, end:WorldVoxelPos, origin:WorldVoxelPos) -> Self {
+        let mut voxels = Vec::with_capacity(((start.x - end.x).abs() + 1) as usize * ((start.y - end.y).abs() + 1) as usize * ((start.z - end.z).abs() + 1) as usize);
+        for x in start.x.min(end.x)..=start.x.max(end.x) {
+            for y in start.y.min(end.y)..=start.y.max(end.y) {
+                for z in start.z.min(end.z)..=start.z.max(end.z) {
+                    let pos = WorldVoxelPos::new(x, y, z);
+                    match map.get_voxel_at(pos) {
+                        Some(voxel) => voxels.push((pos - origin, voxel.clone())),
+                        None => ()
+                    }
+                }
+            }
+        }
+        Self { voxels }
+    }
+    /// Positions relative to the origin
+    pub fn from_voxels(voxels:Vec<(WorldVoxelPos, CoolVoxel)>) -> Self {
+        Self { voxels }
+    }
+    pub fn get_voxels(&self) -> &Vec<(WorldVoxelPos, CoolVoxel)> {
+        &self.voxels
+    }
+    pub fn len(&self) -> usize {
+        self.voxels.len()
+    }
+    /// Smallest and biggest corner, relative to the origin
+    pub fn bounds(&self) -> (WorldVoxelPos, WorldVoxelPos) {
+        let mut min = Vec3D::all_ones() * i32::MAX;
+        let mut max = Vec3D::all_ones() * i32::MIN;
+        for (pos, _) in &self.voxels {
+            min = Vec3D::new(min.x.min(pos.x), min.y.min(pos.y), min.z.min(pos.z));
+            max = Vec3D::new(max.x.max(pos.x), max.y.max(pos.y), max.z.max(pos.z));
+        }
+        (min, max)
+    }
+    fn transformed(&self, map_dir:impl Fn(Vec3D<i32>) -> Vec3D<i32>) -> Self {
+        Self { voxels: self.voxels.iter().map(|(pos, voxel)| {
+            let mut voxel = voxel.clone();
+            voxel.orient = transform_orientation(voxel.orientation(), &map_dir);
+            (map_dir(*pos), voxel)
+        }).collect() }
+    }
+    /// `turns` quarter turns around the vertical axis going through the origin, negative ones going the other way
+    pub fn rotated(&self, turns:i32) -> Self {
+        match turns.rem_euclid(4) {
+            0 => self.clone(),
+            1 => self.transformed(|pos| {Vec3D::new(-pos.y, pos.x, pos.z)}),
+            2 => self.transformed(|pos| {Vec3D::new(-pos.x, -pos.y, pos.z)}),
+            _ => self.transformed(|pos| {Vec3D::new(pos.y, -pos.x, pos.z)}),
+        }
+    }
+    pub fn mirrored(&self, axis:MirrorAxis) -> Self {
+        match axis {
+            MirrorAxis::X => self.transformed(|pos| {Vec3D::new(-pos.x, pos.y, pos.z)}),
+            MirrorAxis::Y => self.transformed(|pos| {Vec3D::new(pos.x, -pos.y, pos.z)}),
+            MirrorAxis::Z => self.transformed(|pos| {Vec3D::new(pos.x, pos.y, -pos.z)}),
+        }
+    }
+    /// World changes putting the origin at `at`, air is left out unless `with_air` so pasting doesn't carve through what's there
+    pub fn changes_at(&self, map:&GameMap<CoolVoxel>, at:WorldVoxelPos, with_air:bool) -> Vec<(WorldVoxelPos, CoolVoxel)> {
+        self.voxels.iter().filter(|(_, voxel)| {with_air || !map.get_voxel_types()[voxel.voxel_id()].is_completely_empty()}).map(|(pos, voxel)| {(*pos + at, voxel.clone())}).collect()
+    }
+    /// Pastes everything in one batch, returns what was there before so it can be undone as a whole
+    pub fn paste(&self, map:&mut GameMap<CoolVoxel>, at:WorldVoxelPos, with_air:bool) -> Vec<(WorldVoxelPos, CoolVoxel)> {
+        let changes = self.changes_at(map, at, with_air);
+        let previous = changes.iter().filter_map(|(pos, _)| {map.get_voxel_at(*pos).map(|voxel| {(*pos, voxel.clone())})}).collect();
+        map.set_voxels_batch(changes);
+        previous
+    }
+    pub fn save(&self, path:&Path) -> io::Result<()> {
+        match path.parent() {
+            Some(folder) => fs::create_dir_all(folder)?,
+            None => ()
+        }
+        let blob = seal_blob(self.to_bytes());
+        let mut bytes = Vec::with_capacity(blob.len() + 6);
+        bytes.extend_from_slice(&SCHEMATIC_MAGIC);
+        bytes.extend_from_slice(&SCHEMATIC_VERSION.to_le_bytes());
+        bytes.extend(blob);
+        fs::write(path, bytes)
+    }
+    pub fn load(path:&Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < 6 || bytes[0..4] != SCHEMATIC_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a schematic file"))
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != SCHEMATIC_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported schematic version {}", version)))
+        }
+        decode_blob(&bytes[6..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game_map::VoxelLight, world_gen::ROCK};
+
+    #[test]
+    fn damaged_files_are_invalid() {
+        let schematic = Schematic::from_voxels((0..4).map(|x| {(Vec3D::new(x, 0, 0), CoolVoxel::new(*ROCK, 0, VoxelLight::zero_light(), None))}).collect());
+        let path = std::env::temp_dir().join(format!("damaged_schematic_{}.hschem", std::process::id()));
+        schematic.save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(Schematic::load(&path).unwrap().voxels, schematic.voxels);
+        fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        let truncated = Schematic::load(&path).err().unwrap();
+        let mut flipped = bytes.clone();
+        *flipped.last_mut().unwrap() ^= 0xFF;
+        fs::write(&path, &flipped).unwrap();
+        let corrupted = Schematic::load(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(truncated.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(corrupted.kind(), io::ErrorKind::InvalidData);
+    }
+}
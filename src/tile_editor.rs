@@ -3,26 +3,20 @@ use std::{collections::{HashMap, HashSet, VecDeque}, f32::consts::{PI, SQRT_2},
 use cosmic_text::{Color, Metrics};
 use hord3::{defaults::{default_rendering::vectorinator_binned::{meshes::{Mesh, MeshID, MeshInstance, MeshLODS, MeshLODType}, shaders::NoOpShader, textures::Textures, Vectorinator}, default_ui::simple_ui::{SimpleUI, SimpleUISave, TextCentering, UIElement, UIElementBackground, UIElementContent, UIElementID}}, horde::{frontend::{interact::Button, MouseState}, geometry::{rotation::Orientation, vec3d::{Vec3D, Vec3Df}}, rendering::camera::Camera}};
 
-use crate::{cutscene::game_shader::GameShader, game_3d_models::{lit_selection_cube, selection_cube}, game_engine::{CoolVoxel, CoolVoxelType}, game_input_handler::GameInputHandler, game_map::{get_chunk_pos_i, get_float_pos, get_voxel_pos, light_spreader::{LightPos, LightSpread}, raycaster::Ray, GameMap, Voxel, VoxelLight, WorldChunkPos, WorldVoxelPos}, game_tasks::GameUserEvent, gui_elements::{editor_gui_elements::{light_spreader_elts, voxel_type_choice}, list_choice}};
+use crate::{cutscene::game_shader::GameShader, game_3d_models::{lit_selection_cube, selection_cube}, game_engine::{CoolVoxel, ExtraVoxelData}, game_input_handler::GameInputHandler, game_map::{get_chunk_pos_i, get_float_pos, get_voxel_pos, light_spreader::{LightPos, LightSpread}, raycaster::Ray, voxel_shapes::horizontal_orientation, GameMap, GameMapEvent, Voxel, VoxelLight, WorldChunkPos, WorldVoxelPos}, game_tasks::GameUserEvent, schematic::{MirrorAxis, Schematic}, vox::{export_chunks, read_vox, VoxColorTable}, voxel_registry::{get_voxel_types, VOXEL_REGISTRY}, world_gen::AIR, gui_elements::{editor_gui_elements::{light_spreader_elts, voxel_type_choice}, list_choice}};
 
 
 pub const CHUNK_SIZE:usize = 8;
 pub const CHUNK_SIZE_F:f32 = CHUNK_SIZE as f32;
+/// Where Ctrl+S and Ctrl+L save and load the copied zone
+pub const CLIPBOARD_SCHEMATIC:&str = "schematics/clipboard.hschem";
+/// Ctrl+E writes the chosen tile chunks there, Ctrl+I puts that model in the clipboard
+pub const EXPORTED_VOX:&str = "schematics/export.vox";
+pub const IMPORTED_VOX:&str = "schematics/import.vox";
 
-pub fn get_tile_voxels() -> Vec<CoolVoxelType> {
-    vec![
-        CoolVoxelType::new(0b00111111, 0, VoxelLight::new(247, 255, 255, 255), None, "Air".to_string(), Some(PathBuf::from("textures/arbre.png")), None),
-        CoolVoxelType::new(0, 1, VoxelLight::zero_light(), None, "Sand".to_string(), Some(PathBuf::from("textures/sable.png")), None),
-        CoolVoxelType::new(0, 2, VoxelLight::zero_light(), None, "Flowers".to_string(), Some(PathBuf::from("textures/terre_herbe.png")), None),
-        CoolVoxelType::new(0, 3, VoxelLight::zero_light(), None, "Grassy Ground".to_string(), Some(PathBuf::from("textures/terre_cail.png")), None),
-        CoolVoxelType::new(0, 4, VoxelLight::zero_light(), None, "Ground".to_string(), Some(PathBuf::from("textures/terre.png")), None),
-        CoolVoxelType::new(0, 5, VoxelLight::zero_light(), None, "Rock".to_string(), Some(PathBuf::from("textures/roche.png")), None),
-        CoolVoxelType::new(0, 0, VoxelLight::zero_light(), None, "Snow".to_string(), Some(PathBuf::from("textures/neige.png")), None),
-        CoolVoxelType::new(0, 6, VoxelLight::zero_light(), None, "Water".to_string(), Some(PathBuf::from("textures/eau.png")), None),
-        CoolVoxelType::new(0, 7, VoxelLight::zero_light(), None, "Deep Water".to_string(), Some(PathBuf::from("textures/eau_prof.png")), None),
-        CoolVoxelType::new(0, 8, VoxelLight::zero_light(), None, "Metal".to_string(), Some(PathBuf::from("textures/metal_0.png")), None),
-        CoolVoxelType::new(0, 3, VoxelLight::zero_light(), None, "Text Test".to_string(), None, None),
-    ]
+/// Extra data a freshly placed voxel of that type starts with (traps, passages...)
+fn base_extra_data(chunks:&GameMap<CoolVoxel>, voxel_type:usize) -> Option<Vec<ExtraVoxelData>> {
+    chunks.get_voxel_types()[voxel_type].base_extra_voxel_data.clone().map(|data| {vec![data]})
 }
 
 pub fn get_selector_cube_mesh() -> Mesh {
@@ -62,10 +56,10 @@ impl TileEditingTool {
     pub fn ui_elems(&self) -> Vec<Vec<UIElement<GameUserEvent>>> {
         match self {
             TileEditingTool::PlaceAndDestroy { chosen, empty_voxel } => {
-                voxel_type_choice(get_tile_voxels(), "PlaceAndDestroy".to_string())
+                voxel_type_choice(get_voxel_types(), "PlaceAndDestroy".to_string())
             },
             TileEditingTool::ChooseTileChunks => vec![],
-            TileEditingTool::ZonedPlaceAndDestroy { chosen, empty_voxel, start, end, action } => voxel_type_choice(get_tile_voxels(), "ZonedPlaceAndDestroy".to_string()),
+            TileEditingTool::ZonedPlaceAndDestroy { chosen, empty_voxel, start, end, action } => voxel_type_choice(get_voxel_types(), "ZonedPlaceAndDestroy".to_string()),
             TileEditingTool::MakeLight { strength, color } => light_spreader_elts()
         }
     }
@@ -78,54 +72,27 @@ impl TileEditingTool {
         match self {
             TileEditingTool::PlaceAndDestroy {chosen, empty_voxel } => {
                 if editor_data.mouse_state.get_deltas_and_scroll().left >= 2 { // Destroy
-                    let end = ray.get_end(&chunks);
-                    //dbg!(end.final_length);
-                    let voxel_types = chunks.get_voxel_types().clone();
-                    let mut modified_at = None;
-                    match chunks.get_voxel_at_mut(get_voxel_pos(end.end)) {
-                        Some(voxel) => {
-                            //println!("FOUND A VOXEL");
-                            //dbg!(voxel.voxel_id());
-                            if voxel.voxel_id() != *empty_voxel {
-                                editor_data.action_queue.push_back(EditorAction::ModifyVoxel { position: get_voxel_pos(end.end), previous_state:voxel.clone() });
-                                modified_at = Some(get_voxel_pos(end.end));
-                                // chunks.modified_this_pos_signal_remesh(get_voxel_pos(end.end));
-                                voxel.voxel_type = *empty_voxel as u16;
-                                //println!("TEST");
-                            }
-                        },
-                        None => ()
-                    }
-                    match modified_at {
-                        Some(pos) => {
-                            chunks.modified_this_pos_signal_remesh(pos);
+                    let hit = ray.cast(&chunks).and_then(|hit| {chunks.get_voxel_at(hit.pos).map(|voxel| {(hit.pos, voxel.clone())})});
+                    match hit {
+                        Some((pos, mut voxel)) => if voxel.voxel_id() != *empty_voxel {
+                            editor_data.action_queue.push_back(EditorAction::ModifyVoxel { position: pos, previous_state:voxel.clone() });
+                            voxel.voxel_type = *empty_voxel as u16;
+                            voxel.extra_voxel_data = base_extra_data(chunks, *empty_voxel);
+                            chunks.set_voxel_at(pos, voxel);
                         },
                         None => ()
                     }
                     self.clone()
                 }
                 else if editor_data.mouse_state.get_deltas_and_scroll().right >= 2 { // Place
-                    let end = ray.get_first_back_different(&chunks, None);
-                    //dbg!(end.final_length);
-                    let voxel_types = chunks.get_voxel_types().clone();
-                    let mut modified_at = None;
-                    match chunks.get_voxel_at_mut(get_voxel_pos(end.end)) {
-                        Some(voxel) => {
-                            //println!("FOUND A VOXEL");
-                            //dbg!(voxel.voxel_id());
-                            if voxel.voxel_id() != *chosen {
-                                editor_data.action_queue.push_back(EditorAction::ModifyVoxel { position: get_voxel_pos(end.end), previous_state:voxel.clone() });
-                                modified_at = Some(get_voxel_pos(end.end));
-                                // chunks.modified_this_pos_signal_remesh(get_voxel_pos(end.end));
-                                voxel.voxel_type = *chosen as u16;
-                            }
-                            //println!("TEST");
-                        },
-                        None => ()
-                    }
-                    match modified_at {
-                        Some(pos) => {
-                            chunks.modified_this_pos_signal_remesh(pos);
+                    let place = ray.cast(&chunks).and_then(|hit| {hit.previous_empty}).and_then(|pos| {chunks.get_voxel_at(pos).map(|voxel| {(pos, voxel.clone())})});
+                    match place {
+                        Some((pos, mut voxel)) => if voxel.voxel_id() != *chosen {
+                            editor_data.action_queue.push_back(EditorAction::ModifyVoxel { position: pos, previous_state:voxel.clone() });
+                            voxel.voxel_type = *chosen as u16;
+                            voxel.orient = horizontal_orientation(editor_data.cam.orient.yaw);
+                            voxel.extra_voxel_data = base_extra_data(chunks, *chosen);
+                            chunks.set_voxel_at(pos, voxel);
                         },
                         None => ()
                     }
@@ -183,31 +150,19 @@ impl TileEditingTool {
                                 for x in start.x.min(end.x)..=start.x.max(end.x) {
                                     for y in start.y.min(end.y)..=start.y.max(end.y) {
                                         for z in start.z.min(end.z)..=start.z.max(end.z) {
-                                            let mut modified = false;
-                                            match chunks.get_voxel_at_mut(WorldVoxelPos::new(x, y, z)) {
-                                                Some(vox) => {
-                                                    match act.clone() {
-                                                        ZoneAction::Place => {
-                                                            if vox.voxel_id() != *chosen {
-                                                                modified = true;
-                                                                changes.push((WorldVoxelPos::new(x, y, z), vox.clone()));
-                                                            }
-                                                            vox.voxel_type = *chosen as u16;
-                                                        },
-                                                        ZoneAction::Destroy => {
-                                                            if vox.voxel_id() != *empty_voxel {
-                                                                modified = true;
-                                                                changes.push((WorldVoxelPos::new(x, y, z), vox.clone()));
-                                                            }
-                                                            vox.voxel_type = *empty_voxel as u16;
-                                                        },
-                                                    }
+                                            let new_type = match act.clone() {
+                                                ZoneAction::Place => *chosen,
+                                                ZoneAction::Destroy => *empty_voxel,
+                                            };
+                                            match chunks.get_voxel_at(WorldVoxelPos::new(x, y, z)).cloned() {
+                                                Some(mut vox) => if vox.voxel_id() != new_type {
+                                                    changes.push((WorldVoxelPos::new(x, y, z), vox.clone()));
+                                                    vox.voxel_type = new_type as u16;
+                                                    vox.extra_voxel_data = base_extra_data(chunks, new_type);
+                                                    chunks.set_voxel_at(WorldVoxelPos::new(x, y, z), vox);
                                                 },
                                                 None => ()
                                             }
-                                            if modified {
-                                                chunks.modified_this_pos_signal_remesh(Vec3D::new(x, y, z));
-                                            }
                                             
                                         }
                                     }
@@ -238,31 +193,12 @@ impl TileEditingTool {
                     self.clone()
                 }
                 else if editor_data.mouse_state.get_deltas_and_scroll().right >= 2 { // Place
-                    let end = ray.get_first_back_different(&chunks, None);
-                    //dbg!(end.final_length);
-                    let voxel_types = chunks.get_voxel_types().clone();
-                    let mut modified_at = None;
-                    match chunks.get_voxel_at_mut(get_voxel_pos(end.end)) {
-                        Some(voxel) => {
-                            //println!("FOUND A VOXEL");
-                            //dbg!(voxel.voxel_id());
-                            modified_at = Some(get_voxel_pos(end.end));
-                            //println!("TEST");
-                        },
-                        None => ()
-                    }
+                    let modified_at = ray.cast(&chunks).and_then(|hit| {hit.previous_empty}).filter(|pos| {chunks.get_voxel_at(*pos).is_some()});
                     match modified_at {
                         Some(pos) => {
-                            let mut changes = Vec::with_capacity(64);
-                            let mut light = LightSpread::calc_max_spread(chunks, LightPos::new(pos, VoxelLight::new(*strength, color.0, color.1, color.2)));
-                            let max_spread = light.get_all_spread();
-                            for light_pos in max_spread {
-                                changes.push((light_pos.pos(), chunks.get_voxel_at_mut(light_pos.pos()).unwrap().clone()));
-                                chunks.get_voxel_at_mut(light_pos.pos()).unwrap().light = light_pos.value().merge_with_other(&chunks.get_voxel_at(light_pos.pos()).unwrap().light);
-                                chunks.modified_this_pos_signal_remesh(light_pos.pos());
-                            }
-                            editor_data.action_queue.push_back(EditorAction::ModifyVoxels { positions_previous: changes });
-                            
+                            let previous = chunks.get_light_sources().get(&pos).copied();
+                            chunks.add_light_source(pos, VoxelLight::new(*strength, color.0, color.1, color.2));
+                            editor_data.action_queue.push_back(EditorAction::PlaceLight { position: pos, previous });
                         },
                         None => ()
                     }
@@ -388,7 +324,7 @@ impl TileEditingTool {
         match self {
             TileEditingTool::PlaceAndDestroy { chosen, empty_voxel } => {
                 *chosen = new_chosen;
-                let type_used = get_tile_voxels()[new_chosen].clone();
+                let type_used = get_voxel_types()[new_chosen].clone();
                 ui.change_content_of(UIElementID::Name(
                     "PlaceAndDestroy Image Title".to_string()),
                     0,
@@ -402,7 +338,7 @@ impl TileEditingTool {
             },
             TileEditingTool::ZonedPlaceAndDestroy { chosen, empty_voxel, start, end, action } => {
                 *chosen = new_chosen;
-                let type_used = get_tile_voxels()[new_chosen].clone();
+                let type_used = get_voxel_types()[new_chosen].clone();
                 ui.change_content_of(UIElementID::Name(
                     "ZonedPlaceAndDestroy Image Title".to_string()),
                     0,
@@ -428,21 +364,25 @@ pub struct TileEditorData {
     tools:HashMap<String, TileEditingTool>,
     chosen_tool:String,
     ui:SimpleUI<GameUserEvent>,
-    action_queue:VecDeque<EditorAction>
+    action_queue:VecDeque<EditorAction>,
+    /// last copied zone, see schematic.rs
+    clipboard:Option<Schematic>,
+    /// events for the world, sent once the editor let go of the map (see take_world_events)
+    world_events:Vec<GameMapEvent<CoolVoxel>>,
 }
 
 pub enum EditorAction {
     ModifyVoxel {position:WorldVoxelPos, previous_state:CoolVoxel},
     ChooseTiles {position:WorldChunkPos, added:bool},
-    ModifyVoxels {positions_previous: Vec<(WorldChunkPos, CoolVoxel)>}
+    ModifyVoxels {positions_previous: Vec<(WorldChunkPos, CoolVoxel)>},
+    PlaceLight {position:WorldVoxelPos, previous:Option<VoxelLight>}
 }
 
 impl EditorAction {
     pub fn reverse_action(self, editor_data:&mut TileEditorData, chunks:&mut GameMap<CoolVoxel>) {
         match self {
             EditorAction::ModifyVoxel { position, previous_state } => {
-                *chunks.get_voxel_at_mut(position).unwrap() = previous_state;
-                chunks.modified_this_pos_signal_remesh(position);
+                chunks.set_voxel_at(position, previous_state);
             },
             EditorAction::ChooseTiles { position, added } => {
                 if added {
@@ -453,9 +393,14 @@ impl EditorAction {
                 }
             },
             EditorAction::ModifyVoxels { positions_previous } => {
-                for (position, previous_state) in positions_previous {
-                    *chunks.get_voxel_at_mut(position).unwrap() = previous_state;
-                    chunks.modified_this_pos_signal_remesh(position);
+                for (position, previous_state) in positions_previous.into_iter().rev() {
+                    chunks.set_voxel_at(position, previous_state);
+                }
+            },
+            EditorAction::PlaceLight { position, previous } => {
+                match previous {
+                    Some(light) => chunks.add_light_source(position, light),
+                    None => {chunks.remove_light_source(position);}
                 }
             }
         }
@@ -471,12 +416,17 @@ impl TileEditorData {
             cam,
             mouse_state,
             tile_chunks:HashSet::with_capacity(128),
-            tools:HashMap::from([("LightSpreader".to_string(), TileEditingTool::MakeLight { strength: 255, color: (255,255,255) }),("TerrainModifier".to_string(), TileEditingTool::PlaceAndDestroy { chosen: 0, empty_voxel: 0 }), ("TileChooser".to_string(), TileEditingTool::ChooseTileChunks), ("TerrainZoneModifier".to_string(), TileEditingTool::ZonedPlaceAndDestroy { chosen: 0, empty_voxel: 0, start: Vec3D::zero(), end: Vec3D::zero(), action:None })]),
+            tools:HashMap::from([("LightSpreader".to_string(), TileEditingTool::MakeLight { strength: 255, color: (255,255,255) }),("TerrainModifier".to_string(), TileEditingTool::PlaceAndDestroy { chosen: *AIR as usize, empty_voxel: *AIR as usize }), ("TileChooser".to_string(), TileEditingTool::ChooseTileChunks), ("TerrainZoneModifier".to_string(), TileEditingTool::ZonedPlaceAndDestroy { chosen: *AIR as usize, empty_voxel: *AIR as usize, start: Vec3D::zero(), end: Vec3D::zero(), action:None })]),
             chosen_tool: "TerrainModifier".to_string(),
             ui,
-            action_queue:VecDeque::with_capacity(128)
+            action_queue:VecDeque::with_capacity(128),
+            clipboard:None,
+            world_events:Vec::with_capacity(4),
         }
     }
+    pub fn take_world_events(&mut self) -> Vec<GameMapEvent<CoolVoxel>> {
+        std::mem::take(&mut self.world_events)
+    }
     pub fn do_mouse_handling(&mut self, chunks: &mut GameMap<CoolVoxel>) {
         let new_tool = self.tools.get(&self.chosen_tool.clone()).unwrap().clone().handle_mouse_state(self, chunks);
         *self.tools.get_mut(&self.chosen_tool.clone()).unwrap() = new_tool;
@@ -491,6 +441,58 @@ impl TileEditorData {
             let latest_action = self.action_queue.pop_back().unwrap();
             latest_action.reverse_action(self, chunks);
         } 
+        if game_input.get_current_keyboard().contains(&Button::Ctrl) && game_input.is_newly_pressed(&Button::W) {
+            self.world_events.push(GameMapEvent::RepairWalls);
+        }
+        if game_input.get_current_keyboard().contains(&Button::Ctrl) && game_input.is_newly_pressed(&Button::C) {
+            match self.tools.get("TerrainZoneModifier") {
+                Some(TileEditingTool::ZonedPlaceAndDestroy { start, end, .. }) => {
+                    let origin = Vec3D::new(start.x.min(end.x), start.y.min(end.y), start.z.min(end.z));
+                    self.clipboard = Some(Schematic::copy_from(chunks, *start, *end, origin));
+                },
+                _ => ()
+            }
+        }
+        if game_input.get_current_keyboard().contains(&Button::Ctrl) && game_input.is_newly_pressed(&Button::R) {
+            self.clipboard = self.clipboard.as_ref().map(|schematic| {schematic.rotated(1)});
+        }
+        if game_input.get_current_keyboard().contains(&Button::Ctrl) && game_input.is_newly_pressed(&Button::M) {
+            self.clipboard = self.clipboard.as_ref().map(|schematic| {schematic.mirrored(MirrorAxis::X)});
+        }
+        if game_input.get_current_keyboard().contains(&Button::Ctrl) && game_input.is_newly_pressed(&Button::V) && let Some(schematic) = &self.clipboard {
+            let ray = Ray::new(self.cam.pos, Orientation::new(self.cam.orient.yaw - PI/2.0, self.cam.orient.roll - PI/8.0, 0.0), Some(100.0));
+            match ray.cast(&chunks).and_then(|hit| {hit.previous_empty}) {
+                Some(at) => {
+                    let previous = schematic.paste(chunks, at, false);
+                    self.action_queue.push_back(EditorAction::ModifyVoxels { positions_previous: previous });
+                },
+                None => ()
+            }
+        }
+        if game_input.get_current_keyboard().contains(&Button::Ctrl) && game_input.is_newly_pressed(&Button::S) && let Some(schematic) = &self.clipboard {
+            match schematic.save(&PathBuf::from(CLIPBOARD_SCHEMATIC)) {
+                Ok(()) => (),
+                Err(error) => println!("couldn't save the schematic : {}", error)
+            }
+        }
+        if game_input.get_current_keyboard().contains(&Button::Ctrl) && game_input.is_newly_pressed(&Button::E) {
+            match export_chunks(&PathBuf::from(EXPORTED_VOX), &VoxColorTable::default_table(), chunks, &self.tile_chunks) {
+                Ok(()) => (),
+                Err(error) => println!("couldn't export the chosen chunks : {}", error)
+            }
+        }
+        if game_input.get_current_keyboard().contains(&Button::Ctrl) && game_input.is_newly_pressed(&Button::I) {
+            match read_vox(&PathBuf::from(IMPORTED_VOX), &VoxColorTable::default_table()) {
+                Ok(schematic) => self.clipboard = Some(schematic),
+                Err(error) => println!("couldn't import the vox model : {}", error)
+            }
+        }
+        if game_input.get_current_keyboard().contains(&Button::Ctrl) && game_input.is_newly_pressed(&Button::L) {
+            match Schematic::load(&PathBuf::from(CLIPBOARD_SCHEMATIC)) {
+                Ok(schematic) => self.clipboard = Some(schematic),
+                Err(error) => println!("couldn't load the schematic : {}", error)
+            }
+        }
     }
     pub fn handle_user_event(&mut self, evt:GameUserEvent) {
         match evt {
@@ -502,7 +504,7 @@ impl TileEditorData {
 
                 }
                 else if name.trim() == "PlaceAndDestroy" || name.trim() == "ZonedPlaceAndDestroy" {
-                    let voxel_type = get_tile_voxels().into_iter().enumerate().find(|(i, voxel_t)| { voxel_t.name == value.trim()}).unwrap().0;
+                    let voxel_type = VOXEL_REGISTRY.id_of(value.trim()).unwrap() as usize;
                     self.tools.get_mut(&self.chosen_tool.clone()).unwrap().change_chosen_unwrap(voxel_type, &mut self.ui);
                 }
                 match self.ui_list_choices.get_mut(&name) {
@@ -529,7 +531,7 @@ impl TileEditorData {
     }
 
     pub fn initial_ui_work(&mut self, textures:&Textures) {
-        for voxel_type in get_tile_voxels() {
+        for voxel_type in get_voxel_types() {
             match voxel_type.texture_path {
                 Some(path) => self.ui.add_image(PathBuf::from(path), Some(voxel_type.name)),
                 None => self.ui.add_image_from_id(textures, voxel_type.texture),
@@ -0,0 +1,197 @@
+use hord3::horde::{game_engine::world::WorldComputeHandler, geometry::vec3d::{Vec3D, Vec3Df}};
+
+use crate::{game_engine::{ActivationType, CoolGameEngineTID, CoolVoxel, TrapAction, TrapCooldown, TrapData}, game_entity::{colliders::AABB, director::DirectorKind, GameEntityVecRead, MovementEvent, MovementEventVariant, StatEvent, StatEventVariant}, game_map::{get_float_pos, get_voxel_center, voxel_shapes::orient_side, GameMap, GameMapEvent, Voxel, WorldVoxelPos, ENTITY_GRID_VEC, EXPLORATION}};
+
+// Trap voxels are indexed per chunk by the GameMap, every TRAP_TICK_INTERVAL ticks each of them either cools down
+// or checks whether something activates it :
+// - contact traps look for entity_1 colliders touching the voxel (standing on it counts) through the set_grid
+// - periodic traps fire on their own
+// - projectile contact traps fire when a trap projectile hits them
+// - interact traps only fire through activate_trap
+// Firing a trap with activate_with_all_adjacent also fires every ready adjacent trap that has it, once per tick.
+// Cooldowns are stored in the voxel's TrapData, the traps cooling down in a tick are updated through a single GameMapEvent::UpdateVoxelDataBatch.
+// Damage goes through StatEventVariant::TakeDamage, entities it brings down to 0 health die (see game_engine::kill_entity).
+//
+// The design doc's traps are presets at the bottom : spikes and magma are damage over time (short cooldown / periodic),
+// electric floors chain through adjacent tiles, turrets shoot projectiles.
+
+pub const TRAP_TICK_INTERVAL:usize = 2;
+pub const PROJECTILE_RANGE:i32 = 24;
+const CONTACT_MARGIN:f32 = 0.1;
+const EFFECT_PUSH:f32 = 0.4;
+/// the set_grid indexes entities by their position, their colliders reach up to that far around it
+const ENTITY_GRID_MARGIN:f32 = 2.0;
+
+fn voxel_box(pos:WorldVoxelPos) -> AABB {
+    AABB::new(get_float_pos(pos), get_float_pos(pos) + Vec3Df::all_ones())
+}
+
+fn trap_direction(voxel:&CoolVoxel) -> Vec3D<i32> {
+    EXPLORATION[orient_side(voxel.orientation(), 0)]
+}
+
+/// entity_1 entities whose collider overlaps `zone`
+fn entities_in(first_ent:&GameEntityVecRead<CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>, zone:&AABB) -> Vec<usize> {
+    let (start, stop) = zone.get_both_points();
+    let mut found = Vec::with_capacity(4);
+    let margin = Vec3Df::all_ones() * ENTITY_GRID_MARGIN;
+    for id in world.world.set_grid.in_aabb(start - margin, stop + margin, ENTITY_GRID_VEC) {
+        // the grid can still hold ids of entities that are gone
+        if !found.contains(&id) && first_ent.collider.get(id).is_some_and(|collider| {collider.collider.collision_aabb(zone)}) {
+            found.push(id);
+        }
+    }
+    found
+}
+
+fn triggers_on(activation:&ActivationType, director_kind:&DirectorKind) -> bool {
+    match activation {
+        ActivationType::AnyEntityContact => true,
+        ActivationType::PlayerContact => *director_kind == DirectorKind::Player,
+        ActivationType::MonsterContact => *director_kind != DirectorKind::Player,
+        _ => false
+    }
+}
+
+fn should_activate(trap:&TrapData, pos:WorldVoxelPos, first_ent:&GameEntityVecRead<CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>, tick:usize) -> bool {
+    match trap.get_activation_type() {
+        ActivationType::Periodic(period) => tick % (*period).max(1) < TRAP_TICK_INTERVAL,
+        ActivationType::ProjectileContact | ActivationType::PlayerInteract => false,
+        activation => {
+            let zone = AABB::new(get_float_pos(pos) - Vec3Df::all_ones() * CONTACT_MARGIN, get_float_pos(pos) + Vec3Df::all_ones() * (1.0 + CONTACT_MARGIN));
+            entities_in(first_ent, world, &zone).into_iter().any(|id| {triggers_on(activation, first_ent.director[id].get_kind())})
+        }
+    }
+}
+
+/// Goes voxel by voxel from the trap in its direction, hurts the first entity or voxel in the way.
+/// Returns the trap it hit if it reacts to projectiles
+fn shoot_projectile(pos:WorldVoxelPos, dir:Vec3D<i32>, damage:i32, first_ent:&GameEntityVecRead<CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>) -> Option<WorldVoxelPos> {
+    for step in 1..=PROJECTILE_RANGE {
+        let cell = pos + dir * step;
+        match entities_in(first_ent, world, &voxel_box(cell)).first() {
+            Some(id) => {
+                first_ent.tunnels.stats_out.send(StatEvent::new(*id, None, StatEventVariant::TakeDamage(damage)));
+                return None
+            },
+            None => ()
+        }
+        let voxel = world.world.get_voxel_at(cell)?;
+        if world.world.is_voxel_solid(cell) {
+            match voxel.get_trap() {
+                Some(trap) => if *trap.get_activation_type() == ActivationType::ProjectileContact {
+                    return Some(cell)
+                },
+                None => ()
+            }
+            world.tunnels.send_event(GameMapEvent::DamageVoxel(cell, damage.max(0) as u16));
+            return None
+        }
+    }
+    None
+}
+
+/// Does the trap's action and starts its cooldown, returns the traps this one set off
+fn fire(pos:WorldVoxelPos, voxel:&CoolVoxel, trap:&TrapData, first_ent:&GameEntityVecRead<CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>) -> Vec<WorldVoxelPos> {
+    world.tunnels.send_event(GameMapEvent::UpdateVoxelDataAt(pos, voxel.with_trap(trap.fired())));
+    let mut set_off = Vec::new();
+    match trap.get_action() {
+        TrapAction::StraightDamage { hitbox } => {
+            for id in entities_in(first_ent, world, &(*hitbox + get_voxel_center(pos))) {
+                first_ent.tunnels.stats_out.send(StatEvent::new(id, None, StatEventVariant::TakeDamage(trap.get_damage())));
+            }
+        },
+        TrapAction::Projectile => match shoot_projectile(pos, trap_direction(voxel), trap.get_damage(), first_ent, world) {
+            Some(hit_trap) => set_off.push(hit_trap),
+            None => ()
+        },
+        TrapAction::Effect => {
+            let dir = trap_direction(voxel);
+            for id in entities_in(first_ent, world, &voxel_box(pos + dir)) {
+                first_ent.tunnels.movement_out.send(MovementEvent::new(id, None, MovementEventVariant::AddToSpeed(get_float_pos(dir) * EFFECT_PUSH)));
+            }
+        }
+    }
+    if trap.activates_with_all_adjacent() {
+        for dir in EXPLORATION {
+            match world.world.get_voxel_at(pos + dir).and_then(|neighbour| {neighbour.get_trap()}) {
+                Some(neighbour_trap) => if neighbour_trap.activates_with_all_adjacent() {
+                    set_off.push(pos + dir);
+                },
+                None => ()
+            }
+        }
+    }
+    set_off
+}
+
+/// Fires the trap at `pos` if it's ready, and everything it sets off. `fired` keeps what already went off this tick
+pub fn activate_trap(pos:WorldVoxelPos, first_ent:&GameEntityVecRead<CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>, fired:&mut Vec<WorldVoxelPos>) {
+    let mut to_fire = vec![pos];
+    while let Some(current) = to_fire.pop() {
+        if fired.contains(&current) {
+            continue
+        }
+        let voxel = match world.world.get_voxel_at(current) {
+            Some(voxel) => voxel,
+            None => continue
+        };
+        match voxel.get_trap() {
+            Some(trap) => if trap.is_ready() {
+                fired.push(current);
+                to_fire.extend(fire(current, voxel, trap, first_ent, world));
+            },
+            None => ()
+        }
+    }
+}
+
+pub fn trap_tick(first_ent:&GameEntityVecRead<CoolGameEngineTID>, world:&WorldComputeHandler<GameMap<CoolVoxel>, CoolGameEngineTID>, tick:usize) {
+    let mut fired = Vec::new();
+    let mut cooled_traps = Vec::new();
+    for chunk_pos in world.world.get_chunks_with_traps() {
+        for pos in world.world.get_traps_in_chunk(chunk_pos) {
+            let voxel = match world.world.get_voxel_at(pos) {
+                Some(voxel) => voxel,
+                None => continue
+            };
+            let trap = match voxel.get_trap() {
+                Some(trap) => trap,
+                None => continue
+            };
+            if !trap.is_ready() {
+                match trap.cooled_by(TRAP_TICK_INTERVAL) {
+                    Some(cooled) => cooled_traps.push((pos, voxel.with_trap(cooled))),
+                    None => ()
+                }
+            }
+            else if !fired.contains(&pos) && should_activate(trap, pos, first_ent, world, tick) {
+                activate_trap(pos, first_ent, world, &mut fired);
+            }
+        }
+    }
+    if cooled_traps.len() > 0 {
+        world.tunnels.send_event(GameMapEvent::UpdateVoxelDataBatch(cooled_traps));
+    }
+}
+
+/// Hitbox covering the voxel right above a trap
+fn above_hitbox(half_width:f32) -> AABB {
+    AABB::new(Vec3Df::new(-half_width, -half_width, 0.5), Vec3Df::new(half_width, half_width, 1.5))
+}
+
+pub fn spike_trap() -> TrapData {
+    TrapData::new(ActivationType::AnyEntityContact, TrapAction::StraightDamage { hitbox: above_hitbox(0.5) }, TrapCooldown::Ticks { max: 30, current: 0 }, false, 5)
+}
+
+pub fn magma_trap() -> TrapData {
+    TrapData::new(ActivationType::Periodic(20), TrapAction::StraightDamage { hitbox: above_hitbox(0.5) }, TrapCooldown::Ticks { max: 0, current: 0 }, false, 3)
+}
+
+pub fn electric_trap() -> TrapData {
+    TrapData::new(ActivationType::AnyEntityContact, TrapAction::StraightDamage { hitbox: above_hitbox(0.6) }, TrapCooldown::Ticks { max: 60, current: 0 }, true, 8)
+}
+
+pub fn turret_trap(period:usize, damage:i32) -> TrapData {
+    TrapData::new(ActivationType::Periodic(period), TrapAction::Projectile, TrapCooldown::Ticks { max: period, current: 0 }, false, damage)
+}
@@ -0,0 +1,431 @@
+use std::{collections::{HashMap, HashSet}, fs, io, path::Path};
+
+use hord3::horde::geometry::vec3d::Vec3D;
+
+use crate::{game_engine::CoolVoxel, game_map::{GameMap, Voxel, VoxelLight, VoxelType, WorldChunkPos, WorldVoxelPos}, schematic::Schematic, world_gen::{ARENA_FLOOR, BREAKABLE_WALL, BUILDING_ROOF, BUILDING_WALL, DEEP_WATER, FENCE, GRASSY_GROUND, GROUND, METAL, REINFORCED_WALL, ROCK, SAND, SNOW, WATER}};
+
+// MagicaVoxel .vox files : "VOX " and a version, then a MAIN chunk whose children are SIZE / XYZI pairs (one per model)
+// and an optional RGBA palette. Every chunk is an id, its content size, its children size, then content and children.
+// XYZI voxels are 4 bytes (x, y, z, colour index), colour index i being palette entry i - 1, z is up like in the GameMap.
+// Colours are turned into voxel types with a VoxColorTable (closest colour wins), and back with the first colour listed for a type.
+// Models are imported as schematics with their origin at the model's corner. Files with several models have a scene graph
+// (nTRN transforms, nGRP groups, nSHP shapes) : the translations of the transforms are added up to place the centre of each model,
+// then the whole scene is moved so its corner is the origin. Rotations of the transforms and animation frames past the first are ignored.
+
+const VOX_MAGIC:[u8 ; 4] = *b"VOX ";
+const VOX_VERSION:i32 = 150;
+/// Biggest model side MagicaVoxel accepts
+pub const VOX_MAX_SIZE:i32 = 256;
+
+pub struct VoxColorTable {
+    entries:Vec<((u8, u8, u8), u16)>,
+}
+
+impl VoxColorTable {
+    pub fn new(entries:Vec<((u8, u8, u8), u16)>) -> Self {
+        Self { entries }
+    }
+    /// Rough colours of the default tiles
+    pub fn default_table() -> Self {
+        Self::new(vec![
+            ((214, 196, 140), *SAND),
+            ((96, 150, 60), *GRASSY_GROUND),
+            ((120, 85, 55), *GROUND),
+            ((120, 120, 120), *ROCK),
+            ((240, 240, 250), *SNOW),
+            ((60, 120, 200), *WATER),
+            ((30, 60, 140), *DEEP_WATER),
+            ((170, 175, 185), *METAL),
+            ((190, 160, 120), *BUILDING_WALL),
+            ((150, 50, 40), *BUILDING_ROOF),
+            ((210, 190, 150), *ARENA_FLOOR),
+            ((90, 90, 90), *FENCE),
+            ((150, 140, 130), *BREAKABLE_WALL),
+            ((60, 60, 70), *REINFORCED_WALL),
+        ])
+    }
+    pub fn type_for(&self, color:(u8, u8, u8)) -> u16 {
+        let distance = |other:(u8, u8, u8)| {(color.0 as i32 - other.0 as i32).pow(2) + (color.1 as i32 - other.1 as i32).pow(2) + (color.2 as i32 - other.2 as i32).pow(2)};
+        self.entries.iter().min_by_key(|(entry_color, _)| {distance(*entry_color)}).map(|(_, voxel_type)| {*voxel_type}).unwrap_or(*ROCK)
+    }
+    pub fn color_for(&self, voxel_type:u16) -> Option<(u8, u8, u8)> {
+        self.entries.iter().find(|(_, entry_type)| {*entry_type == voxel_type}).map(|(color, _)| {*color})
+    }
+}
+
+fn invalid(text:&str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, text.to_string())
+}
+
+fn read_i32(bytes:&[u8], at:usize) -> io::Result<i32> {
+    bytes.get(at..at.checked_add(4).ok_or_else(|| {invalid("truncated vox file")})?).map(|slice| {i32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]])}).ok_or_else(|| {invalid("truncated vox file")})
+}
+
+/// Sizes and counts can't be negative
+fn read_size(bytes:&[u8], at:usize) -> io::Result<usize> {
+    usize::try_from(read_i32(bytes, at)?).map_err(|_| {invalid("negative size in vox file")})
+}
+
+fn add(a:usize, b:usize) -> io::Result<usize> {
+    a.checked_add(b).ok_or_else(|| {invalid("vox sizes overflow")})
+}
+
+/// Reads scene graph chunks field by field, every read checked against the end of the chunk
+struct NodeReader<'a> {
+    content:&'a [u8],
+    at:usize,
+}
+
+impl<'a> NodeReader<'a> {
+    fn i32(&mut self) -> io::Result<i32> {
+        let value = read_i32(self.content, self.at)?;
+        self.at += 4;
+        Ok(value)
+    }
+    fn size(&mut self) -> io::Result<usize> {
+        usize::try_from(self.i32()?).map_err(|_| {invalid("negative size in vox file")})
+    }
+    fn string(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.size()?;
+        let string = self.content.get(self.at..add(self.at, len)?).ok_or_else(|| {invalid("truncated vox node")})?;
+        self.at += len;
+        Ok(string)
+    }
+    fn dict(&mut self) -> io::Result<Vec<(&'a [u8], &'a [u8])>> {
+        let count = self.size()?;
+        let mut dict = Vec::with_capacity(count.min(16));
+        for _ in 0..count {
+            dict.push((self.string()?, self.string()?));
+        }
+        Ok(dict)
+    }
+}
+
+enum SceneNode {
+    Transform { child:i32, translation:WorldVoxelPos },
+    Group { children:Vec<i32> },
+    Shape { models:Vec<usize> },
+}
+
+/// "_t" of a transform frame, "x y z"
+fn parse_translation(value:&[u8]) -> io::Result<WorldVoxelPos> {
+    let text = std::str::from_utf8(value).map_err(|_| {invalid("bad vox translation")})?;
+    let coords = text.split_whitespace().map(|coord| {coord.parse::<i32>().map_err(|_| {invalid("bad vox translation")})}).collect::<io::Result<Vec<i32>>>()?;
+    match coords[..] {
+        [x, y, z] => Ok(WorldVoxelPos::new(x, y, z)),
+        _ => Err(invalid("bad vox translation"))
+    }
+}
+
+fn read_node(id:&[u8], content:&[u8]) -> io::Result<(i32, SceneNode)> {
+    let mut reader = NodeReader { content, at: 0 };
+    let node_id = reader.i32()?;
+    reader.dict()?;
+    let node = match id {
+        b"nTRN" => {
+            let child = reader.i32()?;
+            // reserved id and layer
+            reader.i32()?;
+            reader.i32()?;
+            let frames = reader.size()?;
+            let mut translation = Vec3D::new(0, 0, 0);
+            if frames > 0 {
+                match reader.dict()?.into_iter().find(|(key, _)| {*key == b"_t"}) {
+                    Some((_, value)) => translation = parse_translation(value)?,
+                    None => ()
+                }
+            }
+            SceneNode::Transform { child, translation }
+        },
+        b"nGRP" => {
+            let count = reader.size()?;
+            let mut children = Vec::with_capacity(count.min(64));
+            for _ in 0..count {
+                children.push(reader.i32()?);
+            }
+            SceneNode::Group { children }
+        },
+        _ => {
+            let count = reader.size()?;
+            let mut models = Vec::with_capacity(count.min(64));
+            for _ in 0..count {
+                models.push(reader.size()?);
+                reader.dict()?;
+            }
+            SceneNode::Shape { models }
+        }
+    };
+    Ok((node_id, node))
+}
+
+/// Nested deeper than this, the scene graph is taken as looping
+const MAX_SCENE_DEPTH:usize = 64;
+
+/// Adds up the translations from `node` down to the models, giving each model reached the position of its centre
+fn place_models(nodes:&HashMap<i32, SceneNode>, node:i32, translation:WorldVoxelPos, centres:&mut Vec<Option<WorldVoxelPos>>, depth:usize) -> io::Result<()> {
+    if depth > MAX_SCENE_DEPTH {
+        return Err(invalid("vox scene graph is too deep"))
+    }
+    match nodes.get(&node) {
+        Some(SceneNode::Transform { child, translation:own }) => place_models(nodes, *child, translation + *own, centres, depth + 1)?,
+        Some(SceneNode::Group { children }) => for child in children {
+            place_models(nodes, *child, translation, centres, depth + 1)?;
+        },
+        Some(SceneNode::Shape { models }) => for model in models {
+            match centres.get_mut(*model) {
+                Some(centre) => *centre = Some(translation),
+                None => return Err(invalid("vox shape refers to a missing model"))
+            }
+        },
+        None => ()
+    }
+    Ok(())
+}
+
+pub fn decode_vox(bytes:&[u8], table:&VoxColorTable) -> io::Result<Schematic> {
+    if bytes.len() < 8 || bytes[0..4] != VOX_MAGIC {
+        return Err(invalid("not a vox file"))
+    }
+    let mut palette = None;
+    // size and voxels of every model
+    let mut models:Vec<(WorldVoxelPos, Vec<(WorldVoxelPos, u8)>)> = Vec::with_capacity(4);
+    let mut size = Vec3D::new(0, 0, 0);
+    let mut nodes = HashMap::with_capacity(8);
+    // chunks are read flat, MAIN's children simply follow its (empty) content
+    let mut at = 8;
+    while add(at, 12)? <= bytes.len() {
+        let id = &bytes[at..at + 4];
+        let content = at + 12;
+        let content_end = add(content, read_size(bytes, at + 4)?)?;
+        let data = bytes.get(content..content_end).ok_or_else(|| {invalid("truncated vox chunk")})?;
+        match id {
+            b"SIZE" => size = Vec3D::new(read_i32(data, 0)?, read_i32(data, 4)?, read_i32(data, 8)?),
+            b"XYZI" => {
+                let count = read_size(data, 0)?;
+                let voxels = data.get(4..add(4, count.checked_mul(4).ok_or_else(|| {invalid("vox sizes overflow")})?)?).ok_or_else(|| {invalid("truncated vox voxels")})?;
+                models.push((size, voxels.chunks_exact(4).map(|voxel| {(WorldVoxelPos::new(voxel[0] as i32, voxel[1] as i32, voxel[2] as i32), voxel[3])}).collect()));
+            },
+            b"RGBA" => {
+                let colors = data.get(0..256 * 4).ok_or_else(|| {invalid("truncated vox palette")})?;
+                palette = Some(colors.chunks_exact(4).map(|color| {(color[0], color[1], color[2])}).collect::<Vec<(u8, u8, u8)>>());
+            },
+            b"nTRN" | b"nGRP" | b"nSHP" => {
+                let (node_id, node) = read_node(id, data)?;
+                nodes.insert(node_id, node);
+            },
+            _ => ()
+        }
+        at = if id == b"MAIN" {content_end} else {add(content_end, read_size(bytes, at + 8)?)?};
+    }
+    // the scene graph (when there's one) places the centre of every model, rotations aren't applied
+    let mut centres = vec![None ; models.len()];
+    place_models(&nodes, 0, Vec3D::new(0, 0, 0), &mut centres, 0)?;
+    let color_of = |index:u8| {
+        match &palette {
+            Some(palette) => palette[(index as usize + 255) % 256],
+            None => fallback_color(index)
+        }
+    };
+    let mut voxels = Vec::with_capacity(models.iter().map(|(_, voxels)| {voxels.len()}).sum());
+    for ((size, model_voxels), centre) in models.into_iter().zip(centres.iter()) {
+        let offset = match centre {
+            Some(centre) => *centre - Vec3D::new(size.x / 2, size.y / 2, size.z / 2),
+            None => Vec3D::new(0, 0, 0)
+        };
+        voxels.extend(model_voxels.into_iter().map(|(pos, index)| {(pos + offset, CoolVoxel::new(table.type_for(color_of(index)), 0, VoxelLight::zero_light(), None))}));
+    }
+    let mut schematic = Schematic::from_voxels(voxels);
+    // translations can put models anywhere, the scene's corner becomes the origin
+    if centres.iter().any(|centre| {centre.is_some()}) {
+        let min = schematic.bounds().0;
+        schematic = Schematic::from_voxels(schematic.get_voxels().iter().map(|(pos, voxel)| {(*pos - min, voxel.clone())}).collect());
+    }
+    Ok(schematic)
+}
+
+fn push_chunk(bytes:&mut Vec<u8>, id:&[u8 ; 4], content:&[u8], children_size:usize) {
+    bytes.extend_from_slice(id);
+    bytes.extend_from_slice(&(content.len() as i32).to_le_bytes());
+    bytes.extend_from_slice(&(children_size as i32).to_le_bytes());
+    bytes.extend_from_slice(content);
+}
+
+/// Writes the non empty voxels as one model, moved so its smallest corner is at 0
+pub fn encode_vox(schematic:&Schematic, table:&VoxColorTable, map:&GameMap<CoolVoxel>) -> io::Result<Vec<u8>> {
+    let solid = schematic.get_voxels().iter().filter(|(_, voxel)| {!map.get_voxel_types()[voxel.voxel_id()].is_completely_empty()}).collect::<Vec<&(WorldVoxelPos, CoolVoxel)>>();
+    let (min, max) = Schematic::from_voxels(solid.iter().map(|voxel| {(*voxel).clone()}).collect()).bounds();
+    let size = if solid.is_empty() {Vec3D::all_ones()} else {max - min + Vec3D::all_ones()};
+    if size.x > VOX_MAX_SIZE || size.y > VOX_MAX_SIZE || size.z > VOX_MAX_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "region too big for a vox model"))
+    }
+    // one palette entry per voxel type, the types without a colour get a grey from their id
+    let mut used_types = Vec::with_capacity(16);
+    let mut xyzi = Vec::with_capacity(4 + solid.len() * 4);
+    xyzi.extend_from_slice(&(solid.len() as i32).to_le_bytes());
+    for (pos, voxel) in &solid {
+        let index = match used_types.iter().position(|used| {*used == voxel.voxel_type}) {
+            Some(index) => index,
+            None => {
+                if used_types.len() == 255 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many voxel types for a vox palette"))
+                }
+                used_types.push(voxel.voxel_type);
+                used_types.len() - 1
+            }
+        };
+        let local = *pos - min;
+        xyzi.extend_from_slice(&[local.x as u8, local.y as u8, local.z as u8, index as u8 + 1]);
+    }
+    let mut rgba = vec![0_u8 ; 256 * 4];
+    for (i, voxel_type) in used_types.iter().enumerate() {
+        let color = table.color_for(*voxel_type).unwrap_or(fallback_color(*voxel_type as u8));
+        rgba[i * 4..i * 4 + 4].copy_from_slice(&[color.0, color.1, color.2, 255]);
+    }
+    let mut size_content = Vec::with_capacity(12);
+    for side in [size.x, size.y, size.z] {
+        size_content.extend_from_slice(&side.to_le_bytes());
+    }
+    let mut children = Vec::with_capacity(xyzi.len() + rgba.len() + 48);
+    push_chunk(&mut children, b"SIZE", &size_content, 0);
+    push_chunk(&mut children, b"XYZI", &xyzi, 0);
+    push_chunk(&mut children, b"RGBA", &rgba, 0);
+    let mut bytes = Vec::with_capacity(children.len() + 20);
+    bytes.extend_from_slice(&VOX_MAGIC);
+    bytes.extend_from_slice(&VOX_VERSION.to_le_bytes());
+    push_chunk(&mut bytes, b"MAIN", &[], children.len());
+    bytes.extend(children);
+    Ok(bytes)
+}
+
+pub fn read_vox(path:&Path, table:&VoxColorTable) -> io::Result<Schematic> {
+    decode_vox(&fs::read(path)?, table)
+}
+
+pub fn write_vox(path:&Path, schematic:&Schematic, table:&VoxColorTable, map:&GameMap<CoolVoxel>) -> io::Result<()> {
+    match path.parent() {
+        Some(folder) => fs::create_dir_all(folder)?,
+        None => ()
+    }
+    fs::write(path, encode_vox(schematic, table, map)?)
+}
+
+/// Every loaded voxel of those chunks, relative to the smallest chunk origin
+pub fn chunks_schematic(map:&GameMap<CoolVoxel>, chunks:&HashSet<WorldChunkPos>) -> Schematic {
+    let origin = chunks.iter().fold(Vec3D::all_ones() * i32::MAX, |min, chunk_pos| {
+        let chunk_origin = map.get_chunk_origin(*chunk_pos);
+        Vec3D::new(min.x.min(chunk_origin.x), min.y.min(chunk_origin.y), min.z.min(chunk_origin.z))
+    });
+    let last = map.get_chunk_dims_vector() - Vec3D::all_ones();
+    let mut voxels = Vec::with_capacity(chunks.len() * 512);
+    for chunk_pos in chunks {
+        let start = map.get_chunk_origin(*chunk_pos);
+        voxels.extend(Schematic::copy_from(map, start, start + last, origin).get_voxels().iter().cloned());
+    }
+    Schematic::from_voxels(voxels)
+}
+
+pub fn export_chunks(path:&Path, table:&VoxColorTable, map:&GameMap<CoolVoxel>, chunks:&HashSet<WorldChunkPos>) -> io::Result<()> {
+    write_vox(path, &chunks_schematic(map, chunks), table, map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game_map::ChunkDims, voxel_registry::get_voxel_types, world_gen::AIR};
+
+    const SCENE:&str = "data/tests/scene.vox";
+
+    fn empty_map() -> GameMap<CoolVoxel> {
+        GameMap::new(1, ChunkDims::new(8, 8, 8), get_voxel_types(), (0, 0, 0), 1)
+    }
+
+    fn voxel(voxel_type:u16) -> CoolVoxel {
+        CoolVoxel::new(voxel_type, 0, VoxelLight::zero_light(), None)
+    }
+
+    fn sorted_types(voxels:impl Iterator<Item = (WorldVoxelPos, u16)>) -> Vec<(WorldVoxelPos, u16)> {
+        let mut voxels = voxels.collect::<Vec<(WorldVoxelPos, u16)>>();
+        voxels.sort_by_key(|(pos, _)| {(pos.x, pos.y, pos.z)});
+        voxels
+    }
+
+    /// Whether writing the schematic and reading it back gives the same voxel types at the same places (once moved to its corner).
+    /// Only holds if every type used has its own colour in the table
+    fn round_trips(schematic:&Schematic, table:&VoxColorTable, map:&GameMap<CoolVoxel>) -> bool {
+        let read_back = decode_vox(&encode_vox(schematic, table, map).unwrap(), table).unwrap();
+        let solid = schematic.get_voxels().iter().filter(|(_, voxel)| {!map.get_voxel_types()[voxel.voxel_id()].is_completely_empty()}).cloned().collect::<Vec<(WorldVoxelPos, CoolVoxel)>>();
+        let min = Schematic::from_voxels(solid.clone()).bounds().0;
+        sorted_types(solid.iter().map(|(pos, voxel)| {(*pos, voxel.voxel_type)})) == sorted_types(read_back.get_voxels().iter().map(|(pos, voxel)| {(*pos + min, voxel.voxel_type)}))
+    }
+
+    #[test]
+    fn scene_translations_are_applied() {
+        let schematic = read_vox(Path::new(SCENE), &VoxColorTable::default_table()).unwrap();
+        let expected = sorted_types([
+            (Vec3D::new(0, 0, 0), *SAND),
+            (Vec3D::new(1, 1, 1), *ROCK),
+            (Vec3D::new(10, 1, 0), *WATER),
+            (Vec3D::new(11, 1, 0), *WATER),
+            (Vec3D::new(12, 1, 0), *WATER),
+        ].into_iter());
+        assert_eq!(sorted_types(schematic.get_voxels().iter().map(|(pos, voxel)| {(*pos, voxel.voxel_type)})), expected);
+    }
+
+    #[test]
+    fn scene_round_trips() {
+        let table = VoxColorTable::default_table();
+        let schematic = read_vox(Path::new(SCENE), &table).unwrap();
+        assert!(round_trips(&schematic, &table, &empty_map()));
+    }
+
+    #[test]
+    fn built_schematic_round_trips() {
+        let table = VoxColorTable::default_table();
+        let schematic = Schematic::from_voxels(vec![
+            (Vec3D::new(-3, 2, 5), voxel(*SAND)),
+            (Vec3D::new(-2, 2, 5), voxel(*METAL)),
+            (Vec3D::new(4, -1, 7), voxel(*DEEP_WATER)),
+            (Vec3D::new(0, 0, 6), voxel(*AIR)),
+            (Vec3D::new(1, 9, 5), voxel(*REINFORCED_WALL)),
+        ]);
+        assert!(round_trips(&schematic, &table, &empty_map()));
+    }
+
+    #[test]
+    fn truncated_files_are_invalid() {
+        let bytes = fs::read(SCENE).unwrap();
+        let table = VoxColorTable::default_table();
+        for len in 0..bytes.len() {
+            match decode_vox(&bytes[..len], &table) {
+                Ok(_) => (),
+                Err(error) => assert_eq!(error.kind(), io::ErrorKind::InvalidData)
+            }
+        }
+    }
+
+    #[test]
+    fn bad_sizes_are_invalid() {
+        let table = VoxColorTable::default_table();
+        let with_chunk = |id:&[u8 ; 4], content_size:i32, children_size:i32, content:&[u8]| {
+            let mut bytes = Vec::with_capacity(32 + content.len());
+            bytes.extend_from_slice(&VOX_MAGIC);
+            bytes.extend_from_slice(&VOX_VERSION.to_le_bytes());
+            bytes.extend_from_slice(id);
+            bytes.extend_from_slice(&content_size.to_le_bytes());
+            bytes.extend_from_slice(&children_size.to_le_bytes());
+            bytes.extend_from_slice(content);
+            bytes
+        };
+        let huge_count = i32::MAX.to_le_bytes();
+        for bytes in [
+            with_chunk(b"XYZI", -1, 0, &[]),
+            with_chunk(b"XYZI", 4, 0, &huge_count),
+            with_chunk(b"XYZI", i32::MAX, 0, &huge_count),
+            with_chunk(b"XYZI", 4, -1, &[0, 0, 0, 0]),
+            with_chunk(b"RGBA", 16, 0, &[0 ; 16]),
+            with_chunk(b"nGRP", 12, 0, &[0, 0, 0, 0, 0, 0, 0, 0, 255, 255, 255, 127]),
+        ] {
+            assert_eq!(decode_vox(&bytes, &table).err().map(|error| {error.kind()}), Some(io::ErrorKind::InvalidData));
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::{collections::HashMap, fs, io, path::Path, sync::LazyLock};
+
+use hord3::defaults::default_rendering::vectorinator_binned::textures::Textures;
+use serde::Deserialize;
+
+use crate::{game_engine::{CoolVoxelType, ExtraVoxelData, PassageData}, game_map::{cutout::CutoutMask, voxel_physics::VoxelPhysics, voxel_shapes::{SLAB_OCTANTS, SLAB_SIDES_EMPTY, STAIRS_OCTANTS, STAIRS_SIDES_EMPTY}, RenderLayer, VoxelLight}, traps::{electric_trap, magma_trap, spike_trap, turret_trap}};
+
+// Voxel types and their textures, read from data/voxel_types.json.
+// A type's id is its position in "voxel_types", so types can only be appended without breaking saved worlds.
+// Every texture file gets its own texture set named after the file, in the order of "textures" then of first use by a type,
+// its id being that position (registered before anything else).
+// Lights are [level, r, g, b], empty sides and sides of face textures are indexed like EXPLORATION.
+// Cutout types get the mask of their texture's transparent texels (see game_map::cutout).
+
+pub const VOXEL_TYPES_PATH:&str = "data/voxel_types.json";
+/// Where the texture files are read from
+const TEXTURES_FOLDER:&str = "textures";
+
+pub static VOXEL_REGISTRY:LazyLock<VoxelRegistry> = LazyLock::new(|| {
+    VoxelRegistry::load(Path::new(VOXEL_TYPES_PATH)).unwrap_or_else(|error| {panic!("couldn't load the voxel types from {} : {}", VOXEL_TYPES_PATH, error)})
+});
+
+/// Id of the voxel type with that name, panics if there's none (names of the data file are as fixed as code)
+pub fn voxel_id(name:&str) -> u16 {
+    VOXEL_REGISTRY.id_of(name).unwrap_or_else(|| {panic!("no voxel type named {} in {}", name, VOXEL_TYPES_PATH)})
+}
+
+pub fn get_voxel_types() -> Vec<CoolVoxelType> {
+    VOXEL_REGISTRY.get_types().clone()
+}
+
+#[derive(Deserialize)]
+struct RegistryFile {
+    #[serde(default)]
+    textures:Vec<String>,
+    voxel_types:Vec<VoxelTypeEntry>,
+}
+
+#[derive(Deserialize)]
+struct VoxelTypeEntry {
+    name:String,
+    /// texture of every side, also the one custom shapes use
+    #[serde(default)]
+    texture:Option<String>,
+    #[serde(default)]
+    face_textures:Option<[String ; 6]>,
+    /// image shown in the editor
+    #[serde(default)]
+    icon:Option<String>,
+    #[serde(default)]
+    empty_sides:u8,
+    #[serde(default)]
+    light_passthrough:[u8 ; 4],
+    #[serde(default)]
+    light_emission:Option<[u8 ; 4]>,
+    #[serde(default)]
+    render_layer:LayerEntry,
+    #[serde(default)]
+    physics:PhysicsEntry,
+    #[serde(default)]
+    shape:Option<ShapeEntry>,
+    /// health of the voxel, unbreakable without it
+    #[serde(default)]
+    hardness:Option<u16>,
+    #[serde(default)]
+    repairs:bool,
+    #[serde(default)]
+    extra_data:Option<ExtraDataEntry>,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum LayerEntry {
+    #[default]
+    Opaque,
+    Cutout,
+    Translucent,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum PhysicsEntry {
+    #[default]
+    Static,
+    Gravity,
+    NeedsSupport { max_overhang:u8 },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ShapeEntry {
+    Slab,
+    Stairs,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TrapEntry {
+    Spike,
+    Magma,
+    Electric,
+    Turret { period:usize, damage:i32 },
+}
+
+fn opens_with_adjacent_default() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExtraDataEntry {
+    Trap(TrapEntry),
+    /// `closed` and `open` are the names of the types the passage switches between
+    Passage {
+        key_id:u16,
+        closed:String,
+        open:String,
+        #[serde(default = "opens_with_adjacent_default")]
+        open_with_adjacent:bool,
+        #[serde(default)]
+        possible_corridor:bool,
+        #[serde(default)]
+        is_entry:bool,
+        #[serde(default)]
+        is_open:bool,
+    },
+    LightSource([u8 ; 4]),
+}
+
+fn invalid_data(message:String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn to_light([level, r, g, b]:[u8 ; 4]) -> VoxelLight {
+    VoxelLight::new(level, r, g, b)
+}
+
+pub struct VoxelRegistry {
+    types:Vec<CoolVoxelType>,
+    /// texture files, a file's texture id being its index
+    textures:Vec<String>,
+    ids:HashMap<String, u16>,
+}
+
+/// Mask of the transparent texels of a PNG, None if it has none
+fn read_cutout_mask(path:&Path) -> io::Result<Option<CutoutMask>> {
+    let decoding_error = |error:png::DecodingError| {invalid_data(format!("{} : {}", path.display(), error))};
+    let mut decoder = png::Decoder::new(fs::File::open(path)?);
+    // palettes with transparency get an alpha channel, 16 bit samples become 8 bit ones
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(decoding_error)?;
+    let mut buffer = vec![0 ; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(decoding_error)?;
+    let (color_type, _) = reader.output_color_type();
+    let alpha_sample = match color_type {
+        png::ColorType::Rgba => 3,
+        png::ColorType::GrayscaleAlpha => 1,
+        _ => return Ok(None)
+    };
+    let alpha:Vec<u8> = buffer[..info.buffer_size()].chunks_exact(color_type.samples()).map(|texel| {texel[alpha_sample]}).collect();
+    Ok(CutoutMask::from_alpha(info.width as usize, info.height as usize, &alpha))
+}
+
+impl VoxelRegistry {
+    pub fn load(path:&Path) -> io::Result<Self> {
+        let file:RegistryFile = serde_json::from_str(&fs::read_to_string(path)?).map_err(|error| {invalid_data(format!("{} : {}", path.display(), error))})?;
+        let mut ids = HashMap::with_capacity(file.voxel_types.len());
+        for (id, entry) in file.voxel_types.iter().enumerate() {
+            if ids.insert(entry.name.clone(), id as u16).is_some() {
+                return Err(invalid_data(format!("two voxel types are named {}", entry.name)))
+            }
+        }
+        let mut textures = file.textures;
+        let mut texture_id = |file:&String| {
+            match textures.iter().position(|texture| {texture == file}) {
+                Some(id) => id,
+                None => {
+                    textures.push(file.clone());
+                    textures.len() - 1
+                }
+            }
+        };
+        let mut types = Vec::with_capacity(file.voxel_types.len());
+        for entry in file.voxel_types {
+            let type_id = |name:&String| {ids.get(name).copied().ok_or_else(|| {invalid_data(format!("{} refers to an unknown voxel type {}", entry.name, name))})};
+            let extra_data = match &entry.extra_data {
+                Some(ExtraDataEntry::Trap(trap)) => Some(ExtraVoxelData::IsTrap(match trap {
+                    TrapEntry::Spike => spike_trap(),
+                    TrapEntry::Magma => magma_trap(),
+                    TrapEntry::Electric => electric_trap(),
+                    TrapEntry::Turret { period, damage } => turret_trap(*period, *damage),
+                })),
+                Some(ExtraDataEntry::Passage { key_id, closed, open, open_with_adjacent, possible_corridor, is_entry, is_open }) => {
+                    Some(ExtraVoxelData::IsPassage(PassageData::new(*open_with_adjacent, *key_id, *possible_corridor, *is_entry, type_id(closed)?, type_id(open)?).with_open(*is_open)))
+                },
+                Some(ExtraDataEntry::LightSource(light)) => Some(ExtraVoxelData::IsLightSource(to_light(*light))),
+                None => None
+            };
+            let texture = entry.texture.as_ref().map(&mut texture_id).unwrap_or(0);
+            let mut voxel_type = CoolVoxelType::new(entry.empty_sides, texture, to_light(entry.light_passthrough), entry.light_emission.map(to_light), entry.name.clone(), entry.icon.as_ref().map(|icon| {icon.into()}), extra_data)
+                .with_render_layer(match entry.render_layer {
+                    LayerEntry::Opaque => RenderLayer::Opaque,
+                    LayerEntry::Cutout => RenderLayer::Cutout,
+                    LayerEntry::Translucent => RenderLayer::Translucent,
+                })
+                .with_physics(match entry.physics {
+                    PhysicsEntry::Static => VoxelPhysics::Static,
+                    PhysicsEntry::Gravity => VoxelPhysics::Gravity,
+                    PhysicsEntry::NeedsSupport { max_overhang } => VoxelPhysics::NeedsSupport { max_overhang },
+                });
+            if entry.render_layer == LayerEntry::Cutout && let Some(file) = &entry.texture {
+                voxel_type = voxel_type.with_cutout_mask(read_cutout_mask(&Path::new(TEXTURES_FOLDER).join(file))?);
+            }
+            if let Some(faces) = &entry.face_textures {
+                voxel_type = voxel_type.with_face_textures(faces.each_ref().map(&mut texture_id));
+            }
+            voxel_type = match entry.shape {
+                Some(ShapeEntry::Slab) => voxel_type.with_shape(SLAB_OCTANTS, SLAB_SIDES_EMPTY),
+                Some(ShapeEntry::Stairs) => voxel_type.with_shape(STAIRS_OCTANTS, STAIRS_SIDES_EMPTY),
+                None => voxel_type
+            };
+            if let Some(hardness) = entry.hardness {
+                voxel_type = voxel_type.with_health(hardness, entry.repairs);
+            }
+            types.push(voxel_type);
+        }
+        Ok(Self { types, textures, ids })
+    }
+    pub fn get_types(&self) -> &Vec<CoolVoxelType> {
+        &self.types
+    }
+    pub fn id_of(&self, name:&str) -> Option<u16> {
+        self.ids.get(name).copied()
+    }
+    pub fn get_textures(&self) -> &Vec<String> {
+        &self.textures
+    }
+    /// One texture set per texture file, has to be done before any other set is added so the ids match
+    pub fn register_textures(&self, textures:&mut Textures) {
+        for file in &self.textures {
+            textures.add_set_with_many_textures(file.clone(), vec![(file.clone(), 1, None)]);
+        }
+    }
+}
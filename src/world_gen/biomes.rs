@@ -0,0 +1,163 @@
+use hord3::horde::geometry::vec3d::Vec3D;
+use noise::{NoiseFn, Perlin, Seedable};
+
+use crate::{game_engine::CoolVoxel, game_map::WorldVoxelPos};
+
+use super::{empty_voxel, solid_voxel, water_voxel, WorldGenerator, FLOWERS, GRASSY_GROUND, GROUND, ROCK, SAND, SNOW};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Biome {
+    Desert,
+    Plains,
+    Forest,
+    Tundra,
+    Mountains,
+}
+
+impl Biome {
+    /// temperature and moisture are both in 0..1
+    pub fn from_climate(temperature:f64, moisture:f64, mountain_factor:f64) -> Self {
+        if mountain_factor > 0.7 {
+            Self::Mountains
+        }
+        else if temperature < 0.3 {
+            Self::Tundra
+        }
+        else if temperature > 0.65 && moisture < 0.4 {
+            Self::Desert
+        }
+        else if moisture > 0.55 {
+            Self::Forest
+        }
+        else {
+            Self::Plains
+        }
+    }
+    fn surface_type(&self, column_height:f64, snow_line:f64) -> u16 {
+        match self {
+            Self::Desert => *SAND,
+            Self::Plains => *GRASSY_GROUND,
+            Self::Forest => *FLOWERS,
+            Self::Tundra => *SNOW,
+            Self::Mountains => if column_height > snow_line {*SNOW} else {*ROCK},
+        }
+    }
+    fn under_surface_type(&self) -> u16 {
+        match self {
+            Self::Desert => *SAND,
+            Self::Plains | Self::Forest => *GROUND,
+            Self::Tundra | Self::Mountains => *ROCK,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BiomeParams {
+    pub base_height:f64,
+    pub height_variation:f64,
+    pub mountain_height:f64,
+    pub water_level:f64,
+    pub deep_water_depth:f64,
+    pub snow_line:f64,
+    pub height_scale:f64,
+    pub climate_scale:f64,
+    pub surface_depth:i32,
+    pub beach_height:f64,
+}
+
+impl Default for BiomeParams {
+    fn default() -> Self {
+        Self { base_height: 14.0, height_variation: 10.0, mountain_height: 40.0, water_level: 10.0, deep_water_depth: 4.0, snow_line: 45.0, height_scale: 0.01, climate_scale: 0.002, surface_depth: 3, beach_height: 1.5 }
+    }
+}
+
+struct BiomeColumn {
+    height:f64,
+    biome:Biome,
+}
+
+pub struct BiomeGenerator {
+    seed:u32,
+    params:BiomeParams,
+    height:Perlin,
+    mountains:Perlin,
+    temperature:Perlin,
+    moisture:Perlin,
+}
+
+impl BiomeGenerator {
+    pub fn new(seed:u32, params:BiomeParams) -> Self {
+        Self {
+            seed,
+            params,
+            height: Perlin::new().set_seed(seed),
+            mountains: Perlin::new().set_seed(seed.wrapping_add(1)),
+            temperature: Perlin::new().set_seed(seed.wrapping_add(2)),
+            moisture: Perlin::new().set_seed(seed.wrapping_add(3)),
+        }
+    }
+    pub fn biome_at(&self, x:i32, y:i32) -> Biome {
+        self.column(x, y).biome
+    }
+    fn column(&self, x:i32, y:i32) -> BiomeColumn {
+        let (xf, yf) = (x as f64, y as f64);
+        let temperature = (self.temperature.get([xf * self.params.climate_scale, yf * self.params.climate_scale]) + 1.0) * 0.5;
+        let moisture = (self.moisture.get([xf * self.params.climate_scale, yf * self.params.climate_scale]) + 1.0) * 0.5;
+        let mountain_factor = (self.mountains.get([xf * self.params.climate_scale * 2.0, yf * self.params.climate_scale * 2.0]) + 1.0) * 0.5;
+        let hills = (self.height.get([xf * self.params.height_scale, yf * self.params.height_scale]) + 1.0) * 0.5;
+        // mountains rise smoothly from 0.5 so there is no cliff at the biome border
+        let mountain_boost = ((mountain_factor - 0.5) * 2.0).clamp(0.0, 1.0).powi(2) * self.params.mountain_height;
+        let height = self.params.base_height + hills * self.params.height_variation + mountain_boost;
+        BiomeColumn { height, biome: Biome::from_climate(temperature, moisture, mountain_factor) }
+    }
+    fn voxel_in_column(&self, z:i32, column:&BiomeColumn) -> CoolVoxel {
+        let zf = z as f64;
+        if zf < column.height {
+            if column.height < self.params.water_level + self.params.beach_height {
+                solid_voxel(*SAND)
+            }
+            else if zf >= column.height - 1.0 {
+                solid_voxel(column.biome.surface_type(column.height, self.params.snow_line))
+            }
+            else if zf >= column.height - 1.0 - self.params.surface_depth as f64 {
+                solid_voxel(column.biome.under_surface_type())
+            }
+            else {
+                solid_voxel(*ROCK)
+            }
+        }
+        else if zf < self.params.water_level {
+            water_voxel(self.params.water_level - column.height, self.params.deep_water_depth)
+        }
+        else {
+            empty_voxel()
+        }
+    }
+}
+
+impl WorldGenerator for BiomeGenerator {
+    fn voxel_at(&self, pos:WorldVoxelPos) -> CoolVoxel {
+        self.voxel_in_column(pos.z, &self.column(pos.x, pos.y))
+    }
+    fn generate_chunk_voxels(&self, origin:WorldVoxelPos, size:Vec3D<i32>) -> Vec<CoolVoxel> {
+        let mut columns = Vec::with_capacity((size.x * size.y) as usize);
+        for y in origin.y..origin.y + size.y {
+            for x in origin.x..origin.x + size.x {
+                columns.push(self.column(x, y));
+            }
+        }
+        let mut voxels = Vec::with_capacity((size.x * size.y * size.z) as usize);
+        for z in origin.z..origin.z + size.z {
+            for column in columns.iter() {
+                voxels.push(self.voxel_in_column(z, column));
+            }
+        }
+        voxels
+    }
+    fn name(&self) -> String {
+        "biomes".to_string()
+    }
+    fn seed(&self) -> u32 {
+        self.seed
+    }
+}
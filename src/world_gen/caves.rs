@@ -0,0 +1,134 @@
+use hord3::horde::geometry::vec3d::Vec3D;
+use noise::{NoiseFn, Perlin, Seedable};
+
+use crate::{game_engine::CoolVoxel, game_map::WorldVoxelPos};
+
+use super::{empty_voxel, get_height_band_type, scatter_value, solid_voxel, water_voxel, WorldGenerator, GLOWING_ORE, GROUND, ROCK};
+
+#[derive(Clone, Debug)]
+pub struct CaveParams {
+    pub base_height:f64,
+    pub height_variation:f64,
+    pub water_level:f64,
+    pub deep_water_depth:f64,
+    pub height_scale:f64,
+    /// how much the 3D noise can push the surface around, higher means bigger overhangs and floating bits
+    pub overhang_strength:f64,
+    pub overhang_scale:f64,
+    pub cave_scale:f64,
+    /// caves are carved where both cave noises are within this of 0, which gives long tunnels instead of blobs
+    pub cave_width:f64,
+    /// no caves below this height, so the world always has a floor
+    pub bedrock_height:i32,
+    /// caves stay this many voxels below the surface unless they are wide enough to break through
+    pub cave_roof_thickness:f64,
+    /// share of the rock voxels right above or under a cave that are glowing ore
+    pub glowing_ore_chance:f64,
+}
+
+impl Default for CaveParams {
+    fn default() -> Self {
+        Self { base_height: 16.0, height_variation: 24.0, water_level: 10.0, deep_water_depth: 4.0, height_scale: 0.008, overhang_strength: 8.0, overhang_scale: 0.04, cave_scale: 0.03, cave_width: 0.08, bedrock_height: -14, cave_roof_thickness: 3.0, glowing_ore_chance: 0.01 }
+    }
+}
+
+pub struct CaveGenerator {
+    seed:u32,
+    params:CaveParams,
+    height:Perlin,
+    overhangs:Perlin,
+    caves_a:Perlin,
+    caves_b:Perlin,
+}
+
+impl CaveGenerator {
+    pub fn new(seed:u32, params:CaveParams) -> Self {
+        Self {
+            seed,
+            params,
+            height: Perlin::new().set_seed(seed),
+            overhangs: Perlin::new().set_seed(seed.wrapping_add(1)),
+            caves_a: Perlin::new().set_seed(seed.wrapping_add(2)),
+            caves_b: Perlin::new().set_seed(seed.wrapping_add(3)),
+        }
+    }
+    fn column_height(&self, x:i32, y:i32) -> f64 {
+        let value = (self.height.get([x as f64 * self.params.height_scale, y as f64 * self.params.height_scale]) + 1.0) * 0.5;
+        self.params.base_height + value * self.params.height_variation
+    }
+    fn density(&self, pos:WorldVoxelPos, column_height:f64) -> f64 {
+        let p = [pos.x as f64 * self.params.overhang_scale, pos.y as f64 * self.params.overhang_scale, pos.z as f64 * self.params.overhang_scale];
+        (column_height - pos.z as f64) + self.overhangs.get(p) * self.params.overhang_strength
+    }
+    fn is_cave(&self, pos:WorldVoxelPos, density:f64) -> bool {
+        if pos.z <= self.params.bedrock_height || density < self.params.cave_roof_thickness * 0.5 {
+            return false
+        }
+        let p = [pos.x as f64 * self.params.cave_scale, pos.y as f64 * self.params.cave_scale, pos.z as f64 * self.params.cave_scale * 1.5];
+        let width = if density < self.params.cave_roof_thickness {self.params.cave_width * 0.5} else {self.params.cave_width};
+        self.caves_a.get(p).abs() < width && self.caves_b.get(p).abs() < width
+    }
+    /// Whether the voxel right above or under is part of a cave
+    fn next_to_cave(&self, pos:WorldVoxelPos, column_height:f64) -> bool {
+        [1, -1].into_iter().any(|dz| {
+            let next = pos + Vec3D::new(0, 0, dz);
+            let density = self.density(next, column_height);
+            density > 0.0 && self.is_cave(next, density)
+        })
+    }
+    fn voxel_in_column(&self, pos:WorldVoxelPos, column_height:f64) -> CoolVoxel {
+        let density = self.density(pos, column_height);
+        if density > 0.0 {
+            if self.is_cave(pos, density) {
+                empty_voxel()
+            }
+            else if density < 1.0 {
+                solid_voxel(get_height_band_type(pos.z as f64, self.params.water_level, self.params.height_variation * 0.5))
+            }
+            else if density < 4.0 {
+                solid_voxel(*GROUND)
+            }
+            else if scatter_value(self.seed, pos) < self.params.glowing_ore_chance && self.next_to_cave(pos, column_height) {
+                solid_voxel(*GLOWING_ORE)
+            }
+            else {
+                solid_voxel(*ROCK)
+            }
+        }
+        else if (pos.z as f64) < self.params.water_level {
+            water_voxel(self.params.water_level - column_height, self.params.deep_water_depth)
+        }
+        else {
+            empty_voxel()
+        }
+    }
+}
+
+impl WorldGenerator for CaveGenerator {
+    fn voxel_at(&self, pos:WorldVoxelPos) -> CoolVoxel {
+        self.voxel_in_column(pos, self.column_height(pos.x, pos.y))
+    }
+    fn generate_chunk_voxels(&self, origin:WorldVoxelPos, size:Vec3D<i32>) -> Vec<CoolVoxel> {
+        let mut heights = Vec::with_capacity((size.x * size.y) as usize);
+        for y in origin.y..origin.y + size.y {
+            for x in origin.x..origin.x + size.x {
+                heights.push(self.column_height(x, y));
+            }
+        }
+        let mut voxels = Vec::with_capacity((size.x * size.y * size.z) as usize);
+        for z in origin.z..origin.z + size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    voxels.push(self.voxel_in_column(Vec3D::new(origin.x + x, origin.y + y, z), heights[(x + y * size.x) as usize]));
+                }
+            }
+        }
+        voxels
+    }
+    fn name(&self) -> String {
+        "caves".to_string()
+    }
+    fn seed(&self) -> u32 {
+        self.seed
+    }
+}
@@ -0,0 +1,133 @@
+use hord3::horde::geometry::vec3d::Vec3D;
+
+use crate::{game_engine::CoolVoxel, game_map::WorldVoxelPos};
+
+use super::{empty_voxel, solid_voxel, WorldGenerator, ARENA_FLOOR, GRASSY_GROUND, GROUND, LAMP, METAL, ROCK};
+
+#[derive(Clone, Debug)]
+pub struct ArenaParams {
+    pub center:Vec3D<i32>,
+    pub radius:i32,
+    pub wall_thickness:i32,
+    pub wall_height:i32,
+    pub wall_type:u16,
+    pub floor_type:u16,
+    /// a few pillars spread around the arena to hide behind, 0 for none
+    pub pillar_count:usize,
+    pub pillar_radius:i32,
+    /// put on top of every pillar, lit when the arena is generated
+    pub lamp_type:Option<u16>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FlatParams {
+    pub ground_level:i32,
+    pub top_type:u16,
+    pub fill_type:u16,
+    pub fill_depth:i32,
+    pub bottom_type:u16,
+    pub arena:Option<ArenaParams>,
+}
+
+impl FlatParams {
+    pub fn flat() -> Self {
+        Self { ground_level: 10, top_type: *GRASSY_GROUND, fill_type: *GROUND, fill_depth: 3, bottom_type: *ROCK, arena: None }
+    }
+    pub fn arena() -> Self {
+        Self {
+            ground_level: 10,
+            top_type: *GRASSY_GROUND,
+            fill_type: *GROUND,
+            fill_depth: 3,
+            bottom_type: *ROCK,
+            arena: Some(ArenaParams { center: Vec3D::new(0, 0, 10), radius: 48, wall_thickness: 2, wall_height: 8, wall_type: *METAL, floor_type: *ARENA_FLOOR, pillar_count: 6, pillar_radius: 2, lamp_type: Some(*LAMP) })
+        }
+    }
+}
+
+pub struct FlatGenerator {
+    seed:u32,
+    params:FlatParams,
+    pillars:Vec<Vec3D<i32>>,
+}
+
+impl FlatGenerator {
+    pub fn new(seed:u32, params:FlatParams) -> Self {
+        let pillars = match &params.arena {
+            Some(arena) => {
+                let mut rng = fastrand::Rng::with_seed(seed as u64);
+                (0..arena.pillar_count).map(|_| {
+                    let angle = rng.f32() * std::f32::consts::TAU;
+                    let dist = (0.3 + rng.f32() * 0.5) * arena.radius as f32;
+                    arena.center + Vec3D::new((angle.cos() * dist) as i32, (angle.sin() * dist) as i32, 0)
+                }).collect()
+            },
+            None => Vec::new()
+        };
+        Self { seed, params, pillars }
+    }
+    fn arena_voxel(&self, pos:WorldVoxelPos, arena:&ArenaParams) -> Option<CoolVoxel> {
+        let (dx, dy) = (pos.x - arena.center.x, pos.y - arena.center.y);
+        let dist_squared = dx * dx + dy * dy;
+        let outer = arena.radius + arena.wall_thickness;
+        if dist_squared > outer * outer {
+            return None
+        }
+        let height = pos.z - self.params.ground_level;
+        if dist_squared > arena.radius * arena.radius {
+            if height >= 0 && height < arena.wall_height {
+                return Some(solid_voxel(arena.wall_type))
+            }
+            return None
+        }
+        if height == -1 {
+            return Some(solid_voxel(arena.floor_type))
+        }
+        if height == arena.wall_height && let Some(lamp_type) = arena.lamp_type && self.pillars.iter().any(|pillar| {pillar.x == pos.x && pillar.y == pos.y}) {
+            return Some(solid_voxel(lamp_type))
+        }
+        if height >= 0 && height < arena.wall_height {
+            for pillar in self.pillars.iter() {
+                let (px, py) = (pos.x - pillar.x, pos.y - pillar.y);
+                if px * px + py * py <= arena.pillar_radius * arena.pillar_radius {
+                    return Some(solid_voxel(arena.wall_type))
+                }
+            }
+        }
+        None
+    }
+}
+
+impl WorldGenerator for FlatGenerator {
+    fn voxel_at(&self, pos:WorldVoxelPos) -> CoolVoxel {
+        match &self.params.arena {
+            Some(arena) => match self.arena_voxel(pos, arena) {
+                Some(voxel) => return voxel,
+                None => ()
+            },
+            None => ()
+        }
+        let depth = self.params.ground_level - pos.z;
+        if depth <= 0 {
+            empty_voxel()
+        }
+        else if depth == 1 {
+            solid_voxel(self.params.top_type)
+        }
+        else if depth <= 1 + self.params.fill_depth {
+            solid_voxel(self.params.fill_type)
+        }
+        else {
+            solid_voxel(self.params.bottom_type)
+        }
+    }
+    fn name(&self) -> String {
+        match self.params.arena {
+            Some(_) => "arena".to_string(),
+            None => "flat".to_string()
+        }
+    }
+    fn seed(&self) -> u32 {
+        self.seed
+    }
+}
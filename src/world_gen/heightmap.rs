@@ -0,0 +1,216 @@
+use std::{fs::File, io, path::{Path, PathBuf}};
+
+use hord3::horde::geometry::vec3d::Vec3D;
+
+use crate::{game_engine::CoolVoxel, game_map::{WorldChunkPos, WorldVoxelPos}, vox::VoxColorTable};
+
+use super::{empty_voxel, get_height_band_type, solid_voxel, water_voxel, WorldGenerator, GRASSY_GROUND, ROCK, SAND, SNOW};
+
+// Terrain read from a grayscale PNG (8 or 16 bits, colour images use the mean of their channels) :
+// each pixel is a column, black at base_height and white at base_height + vertical_scale.
+// An optional splat map of the same size picks the column's type by closest colour, otherwise it's the usual height bands.
+// Pixel (0, 0) is the image's top left corner, the image is centered on the world origin with its rows going towards +y.
+// There's no seed, so the generator reports a hash of the columns and params instead : the world is saved in another folder
+// as soon as the image, the splat map or a param changes, instead of loading chunks generated from something else.
+
+#[derive(Clone, Debug)]
+pub struct HeightmapParams {
+    pub base_height:f64,
+    pub vertical_scale:f64,
+    pub water_level:f64,
+    pub deep_water_depth:f64,
+    /// height covered by each band of get_height_band_type
+    pub band_scale:f64,
+}
+
+impl Default for HeightmapParams {
+    fn default() -> Self {
+        Self { base_height: 0.0, vertical_scale: 60.0, water_level: 10.0, deep_water_depth: 4.0, band_scale: 10.0 }
+    }
+}
+
+/// Colours of the splat map
+pub fn default_splat_table() -> VoxColorTable {
+    VoxColorTable::new(vec![
+        ((255, 230, 150), *SAND),
+        ((0, 255, 0), *GRASSY_GROUND),
+        ((128, 128, 128), *ROCK),
+        ((255, 255, 255), *SNOW),
+    ])
+}
+
+struct Image {
+    width:usize,
+    height:usize,
+    /// RGB of every pixel, 0 to 1, row by row
+    pixels:Vec<(f64, f64, f64)>,
+}
+
+fn decoding_error(error:png::DecodingError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+fn read_png(path:&Path) -> io::Result<Image> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    // palettes and 1/2/4 bit images become 8 bit ones, 16 bit ones are left alone
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(decoding_error)?;
+    let mut buffer = vec![0 ; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(decoding_error)?;
+    let (color_type, bit_depth) = reader.output_color_type();
+    let sample_bytes = if bit_depth == png::BitDepth::Sixteen {2} else {1};
+    let max = if sample_bytes == 2 {u16::MAX as f64} else {u8::MAX as f64};
+    let samples = color_type.samples();
+    let sample = |bytes:&[u8], i:usize| {
+        if sample_bytes == 2 {
+            u16::from_be_bytes([bytes[i * 2], bytes[i * 2 + 1]]) as f64 / max
+        }
+        else {
+            bytes[i] as f64 / max
+        }
+    };
+    let mut pixels = Vec::with_capacity(info.width as usize * info.height as usize);
+    for pixel in buffer[..info.buffer_size()].chunks_exact(samples * sample_bytes) {
+        pixels.push(match color_type {
+            png::ColorType::Grayscale | png::ColorType::GrayscaleAlpha => {
+                let value = sample(pixel, 0);
+                (value, value, value)
+            },
+            _ => (sample(pixel, 0), sample(pixel, 1), sample(pixel, 2))
+        });
+    }
+    Ok(Image { width: info.width as usize, height: info.height as usize, pixels })
+}
+
+/// FNV-1a over everything the terrain depends on, folded down to 32 bits
+fn terrain_hash(width:usize, heights:&[f64], surface_types:Option<&Vec<u16>>, params:&HeightmapParams) -> u32 {
+    let mut hash:u64 = 0xCBF29CE484222325;
+    let mut feed = |bytes:&[u8]| {
+        for byte in bytes {
+            hash = (hash ^ *byte as u64).wrapping_mul(0x100000001B3);
+        }
+    };
+    feed(&(width as u64).to_le_bytes());
+    // heights already account for base_height and vertical_scale
+    for height in heights {
+        feed(&height.to_bits().to_le_bytes());
+    }
+    match surface_types {
+        Some(types) => for voxel_type in types {
+            feed(&voxel_type.to_le_bytes());
+        },
+        None => feed(&[0xFF])
+    }
+    for param in [params.water_level, params.deep_water_depth, params.band_scale] {
+        feed(&param.to_bits().to_le_bytes());
+    }
+    (hash ^ (hash >> 32)) as u32
+}
+
+pub struct HeightmapGenerator {
+    name:String,
+    /// see terrain_hash
+    hash:u32,
+    params:HeightmapParams,
+    width:usize,
+    height:usize,
+    heights:Vec<f64>,
+    /// column types from the splat map
+    surface_types:Option<Vec<u16>>,
+}
+
+impl HeightmapGenerator {
+    pub fn new(heightmap:&Path, splat_map:Option<&Path>, params:HeightmapParams) -> io::Result<Self> {
+        let image = read_png(heightmap)?;
+        let heights = image.pixels.iter().map(|(r, g, b)| {params.base_height + (r + g + b) / 3.0 * params.vertical_scale}).collect();
+        let surface_types = match splat_map {
+            Some(path) => {
+                let splat = read_png(path)?;
+                if splat.width != image.width || splat.height != image.height {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "the splat map isn't the same size as the heightmap"))
+                }
+                let table = default_splat_table();
+                Some(splat.pixels.iter().map(|(r, g, b)| {table.type_for(((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8))}).collect())
+            },
+            None => None
+        };
+        let name = format!("heightmap_{}", heightmap.file_stem().map(|stem| {stem.to_string_lossy().to_string()}).unwrap_or_default());
+        let hash = terrain_hash(image.width, &heights, surface_types.as_ref(), &params);
+        Ok(Self { name, hash, params, width: image.width, height: image.height, heights, surface_types })
+    }
+    /// Index of the pixel for that column, None outside of the image
+    fn pixel_index(&self, x:i32, y:i32) -> Option<usize> {
+        let image_x = x + (self.width / 2) as i32;
+        let image_y = y + (self.height / 2) as i32;
+        if image_x < 0 || image_y < 0 || image_x >= self.width as i32 || image_y >= self.height as i32 {
+            None
+        }
+        else {
+            Some(image_x as usize + image_y as usize * self.width)
+        }
+    }
+    fn voxel_in_column(&self, z:i32, pixel:usize) -> CoolVoxel {
+        let column_height = self.heights[pixel];
+        if (z as f64) < column_height {
+            match &self.surface_types {
+                Some(types) => solid_voxel(types[pixel]),
+                None => solid_voxel(get_height_band_type(column_height, self.params.water_level, self.params.band_scale))
+            }
+        }
+        else if (z as f64) < self.params.water_level {
+            water_voxel(self.params.water_level - column_height, self.params.deep_water_depth)
+        }
+        else {
+            empty_voxel()
+        }
+    }
+}
+
+impl WorldGenerator for HeightmapGenerator {
+    fn voxel_at(&self, pos:WorldVoxelPos) -> CoolVoxel {
+        match self.pixel_index(pos.x, pos.y) {
+            Some(pixel) => self.voxel_in_column(pos.z, pixel),
+            None => empty_voxel()
+        }
+    }
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    /// Names the save folder, see terrain_hash
+    fn seed(&self) -> u32 {
+        self.hash
+    }
+    /// Every chunk the image covers, from a chunk under the lowest column to one above the highest column or the water
+    fn chunk_extent(&self, chunk_dims:Vec3D<i32>) -> Option<(WorldChunkPos, WorldChunkPos)> {
+        let min = Vec3D::new(-((self.width / 2) as i32), -((self.height / 2) as i32), 0);
+        let max = min + Vec3D::new(self.width as i32 - 1, self.height as i32 - 1, 0);
+        let top = self.heights.iter().fold(self.params.water_level, |top, height| {top.max(*height)}).ceil() as i32;
+        Some((
+            Vec3D::new(min.x.div_floor(chunk_dims.x), min.y.div_floor(chunk_dims.y), (self.params.base_height.floor().min(0.0) as i32).div_floor(chunk_dims.z) - 1),
+            Vec3D::new(max.x.div_floor(chunk_dims.x) + 1, max.y.div_floor(chunk_dims.y) + 1, top.div_floor(chunk_dims.z) + 1),
+        ))
+    }
+}
+
+/// Reads `--heightmap <png>`, `--splat <png>`, `--vscale <f64>` and `--water <f64>`, None without a heightmap
+pub fn heightmap_from_args(args:&Vec<String>) -> Option<io::Result<HeightmapGenerator>> {
+    let mut heightmap = None;
+    let mut splat = None;
+    let mut params = HeightmapParams::default();
+    for i in 0..args.len().saturating_sub(1) {
+        match args[i].as_str() {
+            "--heightmap" => heightmap = Some(PathBuf::from(&args[i + 1])),
+            "--splat" => splat = Some(PathBuf::from(&args[i + 1])),
+            "--vscale" => match args[i + 1].parse::<f64>() {
+                Ok(scale) => params.vertical_scale = scale,
+                Err(_) => println!("invalid vertical scale {}, using {}", args[i + 1], params.vertical_scale)
+            },
+            "--water" => match args[i + 1].parse::<f64>() {
+                Ok(level) => params.water_level = level,
+                Err(_) => println!("invalid water level {}, using {}", args[i + 1], params.water_level)
+            },
+            _ => ()
+        }
+    }
+    heightmap.map(|path| {HeightmapGenerator::new(&path, splat.as_deref(), params)})
+}
@@ -0,0 +1,80 @@
+use hord3::horde::geometry::vec3d::Vec3D;
+use noise::{NoiseFn, Perlin, Seedable};
+
+use crate::{game_engine::CoolVoxel, game_map::{get_f64_pos, WorldVoxelPos}};
+
+use super::{empty_voxel, get_height_band_type, solid_voxel, WorldGenerator, WATER};
+
+#[derive(Clone, Debug)]
+pub struct HillsParams {
+    pub world_height:f64,
+    pub water_level:f64,
+    pub noise_scale:f64,
+    /// the terrain slopes down along x, starting at `ramp_start_x` and losing `world_height` over `ramp_length` voxels
+    pub ramp_start_x:i32,
+    pub ramp_length:f64,
+}
+
+impl Default for HillsParams {
+    fn default() -> Self {
+        Self { world_height: 15.0, water_level: 10.0, noise_scale: 0.01, ramp_start_x: -30, ramp_length: 50.0 }
+    }
+}
+
+pub struct HillsGenerator {
+    seed:u32,
+    params:HillsParams,
+    perlin:Perlin,
+}
+
+impl HillsGenerator {
+    pub fn new(seed:u32, params:HillsParams) -> Self {
+        Self { seed, params, perlin: Perlin::new().set_seed(seed) }
+    }
+    pub fn column_height(&self, x:i32, y:i32) -> f64 {
+        let pos_2D = get_f64_pos(Vec3D::new(x, y, 0)) * self.params.noise_scale;
+        let value_2D = (self.perlin.get([pos_2D.x, pos_2D.y]) + 1.0) * 0.5;
+        let local_world_height = self.params.world_height - ((x - self.params.ramp_start_x) as f64/self.params.ramp_length) * self.params.world_height;
+        local_world_height + self.params.world_height * value_2D * 2.0
+    }
+    fn voxel_in_column(&self, z:i32, column_height:f64) -> CoolVoxel {
+        if (z as f64) < column_height || (z as f64) < self.params.water_level {
+            if (z as f64) < self.params.water_level {
+                solid_voxel(*WATER)
+            }
+            else {
+                solid_voxel(get_height_band_type(column_height, self.params.water_level, self.params.world_height))
+            }
+        }
+        else {
+            empty_voxel()
+        }
+    }
+}
+
+impl WorldGenerator for HillsGenerator {
+    fn voxel_at(&self, pos:WorldVoxelPos) -> CoolVoxel {
+        self.voxel_in_column(pos.z, self.column_height(pos.x, pos.y))
+    }
+    fn generate_chunk_voxels(&self, origin:WorldVoxelPos, size:Vec3D<i32>) -> Vec<CoolVoxel> {
+        let mut heights = Vec::with_capacity((size.x * size.y) as usize);
+        for y in origin.y..origin.y + size.y {
+            for x in origin.x..origin.x + size.x {
+                heights.push(self.column_height(x, y));
+            }
+        }
+        let mut voxels = Vec::with_capacity((size.x * size.y * size.z) as usize);
+        for z in origin.z..origin.z + size.z {
+            for column_height in heights.iter() {
+                voxels.push(self.voxel_in_column(z, *column_height));
+            }
+        }
+        voxels
+    }
+    fn name(&self) -> String {
+        "hills".to_string()
+    }
+    fn seed(&self) -> u32 {
+        self.seed
+    }
+}
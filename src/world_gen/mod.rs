@@ -0,0 +1,159 @@
+use std::sync::LazyLock;
+
+use hord3::horde::geometry::vec3d::Vec3D;
+
+use crate::{game_engine::CoolVoxel, game_map::{VoxelLight, WorldChunkPos, WorldVoxelPos}, voxel_registry::voxel_id};
+
+pub mod hills;
+pub mod biomes;
+pub mod caves;
+pub mod flat;
+pub mod heightmap;
+
+// voxel types the game refers to, see voxel_registry.rs
+pub static AIR:LazyLock<u16> = LazyLock::new(|| {voxel_id("Air")});
+pub static SAND:LazyLock<u16> = LazyLock::new(|| {voxel_id("Sand")});
+pub static FLOWERS:LazyLock<u16> = LazyLock::new(|| {voxel_id("Flowers")});
+pub static GRASSY_GROUND:LazyLock<u16> = LazyLock::new(|| {voxel_id("Grassy Ground")});
+pub static GROUND:LazyLock<u16> = LazyLock::new(|| {voxel_id("Ground")});
+pub static ROCK:LazyLock<u16> = LazyLock::new(|| {voxel_id("Rock")});
+pub static SNOW:LazyLock<u16> = LazyLock::new(|| {voxel_id("Snow")});
+pub static WATER:LazyLock<u16> = LazyLock::new(|| {voxel_id("Water")});
+pub static DEEP_WATER:LazyLock<u16> = LazyLock::new(|| {voxel_id("Deep Water")});
+pub static METAL:LazyLock<u16> = LazyLock::new(|| {voxel_id("Metal")});
+pub static ARENA_FLOOR:LazyLock<u16> = LazyLock::new(|| {voxel_id("Arena Floor")});
+pub static BUILDING_WALL:LazyLock<u16> = LazyLock::new(|| {voxel_id("Building Wall")});
+pub static BUILDING_ROOF:LazyLock<u16> = LazyLock::new(|| {voxel_id("Building Roof")});
+pub static ROCK_SLAB:LazyLock<u16> = LazyLock::new(|| {voxel_id("Rock Slab")});
+pub static ROCK_STAIRS:LazyLock<u16> = LazyLock::new(|| {voxel_id("Rock Stairs")});
+pub static FENCE:LazyLock<u16> = LazyLock::new(|| {voxel_id("Fence")});
+pub static BREAKABLE_WALL:LazyLock<u16> = LazyLock::new(|| {voxel_id("Breakable Wall")});
+pub static REPAIRING_WALL:LazyLock<u16> = LazyLock::new(|| {voxel_id("Repairing Wall")});
+pub static REINFORCED_WALL:LazyLock<u16> = LazyLock::new(|| {voxel_id("Reinforced Wall")});
+pub static SPIKE_TRAP:LazyLock<u16> = LazyLock::new(|| {voxel_id("Spike Trap")});
+pub static MAGMA_TRAP:LazyLock<u16> = LazyLock::new(|| {voxel_id("Magma Trap")});
+pub static ELECTRIC_TRAP:LazyLock<u16> = LazyLock::new(|| {voxel_id("Electric Trap")});
+pub static TURRET:LazyLock<u16> = LazyLock::new(|| {voxel_id("Turret")});
+pub static DOOR:LazyLock<u16> = LazyLock::new(|| {voxel_id("Door")});
+pub static OPEN_DOOR:LazyLock<u16> = LazyLock::new(|| {voxel_id("Open Door")});
+pub static LOCKED_DOOR:LazyLock<u16> = LazyLock::new(|| {voxel_id("Locked Door")});
+pub static OPEN_LOCKED_DOOR:LazyLock<u16> = LazyLock::new(|| {voxel_id("Open Locked Door")});
+pub static LAMP:LazyLock<u16> = LazyLock::new(|| {voxel_id("Lamp")});
+pub static GLOWING_ORE:LazyLock<u16> = LazyLock::new(|| {voxel_id("Glowing Ore")});
+
+static HEIGHT_BANDS:LazyLock<[u16 ; 6]> = LazyLock::new(|| {[*SAND, *FLOWERS, *GRASSY_GROUND, *GROUND, *ROCK, *SNOW]});
+
+pub trait WorldGenerator: Send + Sync {
+    fn voxel_at(&self, pos:WorldVoxelPos) -> CoolVoxel;
+    /// Voxels of the `size` box starting at `origin`, x first then y then z (same layout as `MapChunk`)
+    fn generate_chunk_voxels(&self, origin:WorldVoxelPos, size:Vec3D<i32>) -> Vec<CoolVoxel> {
+        let mut voxels = Vec::with_capacity((size.x * size.y * size.z) as usize);
+        for z in origin.z..origin.z + size.z {
+            for y in origin.y..origin.y + size.y {
+                for x in origin.x..origin.x + size.x {
+                    voxels.push(self.voxel_at(Vec3D::new(x, y, z)));
+                }
+            }
+        }
+        voxels
+    }
+    fn name(&self) -> String;
+    fn seed(&self) -> u32;
+    /// Chunks (start included, end excluded) to generate up front for generators that only cover part of the world,
+    /// None for endless ones
+    fn chunk_extent(&self, _chunk_dims:Vec3D<i32>) -> Option<(WorldChunkPos, WorldChunkPos)> {
+        None
+    }
+}
+
+/// Same banding as the original hills : the higher the column above the water, the further down the list
+/// (sand, flowers, grassy ground, ground, rock, snow)
+pub fn get_height_band_type(column_height:f64, water_level:f64, band_scale:f64) -> u16 {
+    HEIGHT_BANDS[((column_height - water_level)/band_scale).clamp(0.0, 5.99) as usize]
+}
+
+pub fn solid_voxel(voxel_type:u16) -> CoolVoxel {
+    CoolVoxel::new(voxel_type, 0, VoxelLight::zero_light(), None)
+}
+
+pub fn empty_voxel() -> CoolVoxel {
+    CoolVoxel::new(*AIR, 0, VoxelLight::zero_light(), None)
+}
+
+/// Value in 0..1 that only depends on the seed and the position, so features scattered with it (glowing ores, lamps...)
+/// come back at the same places whenever a chunk is generated again
+pub fn scatter_value(seed:u32, pos:WorldVoxelPos) -> f64 {
+    let mut hash = seed as u64 ^ (pos.x as u32 as u64).wrapping_mul(0x9E3779B97F4A7C15) ^ (pos.y as u32 as u64).wrapping_mul(0xC2B2AE3D27D4EB4F) ^ (pos.z as u32 as u64).wrapping_mul(0x165667B19E3779F9);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xFF51AFD7ED558CCD);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xC4CEB9FE1A85EC53);
+    hash ^= hash >> 33;
+    (hash >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+pub fn water_voxel(depth:f64, deep_water_depth:f64) -> CoolVoxel {
+    if depth > deep_water_depth {
+        solid_voxel(*DEEP_WATER)
+    }
+    else {
+        solid_voxel(*WATER)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GeneratorKind {
+    Hills,
+    Biomes,
+    Caves,
+    Flat,
+    Arena,
+}
+
+impl GeneratorKind {
+    pub fn from_name(name:&str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "hills" => Some(Self::Hills),
+            "biomes" => Some(Self::Biomes),
+            "caves" => Some(Self::Caves),
+            "flat" => Some(Self::Flat),
+            "arena" => Some(Self::Arena),
+            _ => None
+        }
+    }
+    pub fn make_generator(&self, seed:u32) -> Box<dyn WorldGenerator> {
+        match self {
+            Self::Hills => Box::new(hills::HillsGenerator::new(seed, hills::HillsParams::default())),
+            Self::Biomes => Box::new(biomes::BiomeGenerator::new(seed, biomes::BiomeParams::default())),
+            Self::Caves => Box::new(caves::CaveGenerator::new(seed, caves::CaveParams::default())),
+            Self::Flat => Box::new(flat::FlatGenerator::new(seed, flat::FlatParams::flat())),
+            Self::Arena => Box::new(flat::FlatGenerator::new(seed, flat::FlatParams::arena())),
+        }
+    }
+}
+
+/// Reads `--gen <hills|biomes|caves|flat|arena>` and `--seed <u32>` from the command line,
+/// defaulting to the original hills with seed 13095. `--heightmap <png>` takes over if given, see heightmap::heightmap_from_args
+pub fn generator_from_args(args:&Vec<String>) -> Box<dyn WorldGenerator> {
+    match heightmap::heightmap_from_args(args) {
+        Some(Ok(generator)) => return Box::new(generator),
+        Some(Err(error)) => println!("couldn't read the heightmap : {}", error),
+        None => ()
+    }
+    let mut kind = GeneratorKind::Hills;
+    let mut seed = 13095;
+    for i in 0..args.len().saturating_sub(1) {
+        match args[i].as_str() {
+            "--gen" => match GeneratorKind::from_name(&args[i + 1]) {
+                Some(new_kind) => kind = new_kind,
+                None => println!("unknown generator {}, using {:?}", args[i + 1], kind)
+            },
+            "--seed" => match args[i + 1].parse::<u32>() {
+                Ok(new_seed) => seed = new_seed,
+                Err(_) => println!("invalid seed {}, using {}", args[i + 1], seed)
+            },
+            _ => ()
+        }
+    }
+    kind.make_generator(seed)
+}